@@ -0,0 +1,4 @@
+// migrations are embedded, so new ones have to trigger a rebuild
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,3 @@
+ALTER TABLE "users" ADD COLUMN IF NOT EXISTS quota BIGINT;
+ALTER TABLE "users" ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE "users" ADD COLUMN IF NOT EXISTS password_reset BOOLEAN NOT NULL DEFAULT FALSE;
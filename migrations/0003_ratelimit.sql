@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS rate_limit (
+    key VARCHAR PRIMARY KEY,
+    window_start BIGINT NOT NULL,
+    hits BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS login_failure (
+    key VARCHAR PRIMARY KEY,
+    failures INTEGER NOT NULL,
+    last_failure BIGINT NOT NULL,
+    locked_until BIGINT
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS account_deletion (
+    user_uuid VARCHAR PRIMARY KEY,
+    scheduled_for BIGINT NOT NULL,
+    /* set while a worker purges the account, expired leases are picked up again */
+    lease_until BIGINT,
+
+    timestamp bigint DEFAULT EXTRACT(EPOCH FROM NOW())
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS email_change (
+    token_hash VARCHAR PRIMARY KEY,
+    user_uuid VARCHAR NOT NULL UNIQUE,
+    new_email VARCHAR NOT NULL,
+    expires BIGINT NOT NULL,
+
+    timestamp bigint DEFAULT EXTRACT(EPOCH FROM NOW())
+);
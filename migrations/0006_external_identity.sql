@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS external_identity (
+    issuer VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    user_uuid VARCHAR NOT NULL,
+
+    timestamp bigint DEFAULT EXTRACT(EPOCH FROM NOW()),
+    PRIMARY KEY (issuer, subject)
+);
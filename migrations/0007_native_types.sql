@@ -0,0 +1,74 @@
+/* uuids were stored as text and timestamps as unix seconds, switch to the native types */
+ALTER TABLE "users"
+    ALTER COLUMN uuid TYPE UUID USING uuid::UUID,
+    ALTER COLUMN tokenid TYPE UUID USING tokenid::UUID,
+    ALTER COLUMN timestamp DROP DEFAULT,
+    ALTER COLUMN timestamp TYPE TIMESTAMPTZ USING COALESCE(to_timestamp(timestamp), NOW()),
+    ALTER COLUMN timestamp SET DEFAULT NOW(),
+    ALTER COLUMN timestamp SET NOT NULL;
+
+ALTER TABLE file
+    ALTER COLUMN reference_uuid TYPE UUID USING reference_uuid::UUID,
+    ALTER COLUMN owner_uuid TYPE UUID USING owner_uuid::UUID,
+    ALTER COLUMN timestamp DROP DEFAULT,
+    ALTER COLUMN timestamp TYPE TIMESTAMPTZ USING COALESCE(to_timestamp(timestamp), NOW()),
+    ALTER COLUMN timestamp SET DEFAULT NOW(),
+    ALTER COLUMN timestamp SET NOT NULL;
+
+ALTER TABLE account_deletion
+    ALTER COLUMN user_uuid TYPE UUID USING user_uuid::UUID,
+    ALTER COLUMN scheduled_for TYPE TIMESTAMPTZ USING to_timestamp(scheduled_for),
+    ALTER COLUMN lease_until TYPE TIMESTAMPTZ USING to_timestamp(lease_until),
+    ALTER COLUMN timestamp DROP DEFAULT,
+    ALTER COLUMN timestamp TYPE TIMESTAMPTZ USING COALESCE(to_timestamp(timestamp), NOW()),
+    ALTER COLUMN timestamp SET DEFAULT NOW(),
+    ALTER COLUMN timestamp SET NOT NULL;
+
+ALTER TABLE email_change
+    ALTER COLUMN user_uuid TYPE UUID USING user_uuid::UUID,
+    ALTER COLUMN expires TYPE TIMESTAMPTZ USING to_timestamp(expires),
+    ALTER COLUMN timestamp DROP DEFAULT,
+    ALTER COLUMN timestamp TYPE TIMESTAMPTZ USING COALESCE(to_timestamp(timestamp), NOW()),
+    ALTER COLUMN timestamp SET DEFAULT NOW(),
+    ALTER COLUMN timestamp SET NOT NULL;
+
+ALTER TABLE external_identity
+    ALTER COLUMN user_uuid TYPE UUID USING user_uuid::UUID,
+    ALTER COLUMN timestamp DROP DEFAULT,
+    ALTER COLUMN timestamp TYPE TIMESTAMPTZ USING COALESCE(to_timestamp(timestamp), NOW()),
+    ALTER COLUMN timestamp SET DEFAULT NOW(),
+    ALTER COLUMN timestamp SET NOT NULL;
+
+/* rate_limit and login_failure keep their unix seconds, their windows are plain integer arithmetic */
+
+/* files of users that no longer exist would block the foreign key, their content is still on disk */
+DO $$
+DECLARE
+    orphaned BIGINT;
+BEGIN
+    SELECT COUNT(*) INTO orphaned FROM file WHERE owner_uuid NOT IN (SELECT uuid FROM "users");
+    IF orphaned > 0 THEN
+        RAISE EXCEPTION '% files belong to users that no longer exist', orphaned
+            USING HINT = 'Find them with: SELECT * FROM file WHERE owner_uuid NOT IN (SELECT uuid FROM "users"); '
+                         'then move their content off disk and delete the rows, or reassign them to an existing user';
+    END IF;
+END $$;
+
+/* the remaining rows of users that no longer exist can't be reached anymore */
+DELETE FROM account_deletion WHERE user_uuid NOT IN (SELECT uuid FROM "users");
+DELETE FROM email_change WHERE user_uuid NOT IN (SELECT uuid FROM "users");
+DELETE FROM external_identity WHERE user_uuid NOT IN (SELECT uuid FROM "users");
+
+/* files have to be removed from disk first, so deleting their owner is refused */
+ALTER TABLE file
+    ADD CONSTRAINT file_owner_uuid_fkey FOREIGN KEY (owner_uuid) REFERENCES "users" (uuid);
+ALTER TABLE account_deletion
+    ADD CONSTRAINT account_deletion_user_uuid_fkey FOREIGN KEY (user_uuid) REFERENCES "users" (uuid) ON DELETE CASCADE;
+ALTER TABLE email_change
+    ADD CONSTRAINT email_change_user_uuid_fkey FOREIGN KEY (user_uuid) REFERENCES "users" (uuid) ON DELETE CASCADE;
+ALTER TABLE external_identity
+    ADD CONSTRAINT external_identity_user_uuid_fkey FOREIGN KEY (user_uuid) REFERENCES "users" (uuid) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS file_owner_uuid_idx ON file (owner_uuid);
+CREATE INDEX IF NOT EXISTS external_identity_user_uuid_idx ON external_identity (user_uuid);
+CREATE INDEX IF NOT EXISTS account_deletion_scheduled_for_idx ON account_deletion (scheduled_for);
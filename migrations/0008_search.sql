@@ -0,0 +1,16 @@
+/* fuzzy filename matching */
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+/* files stored before types were detected keep the generic type */
+ALTER TABLE file ADD COLUMN mime_type VARCHAR NOT NULL DEFAULT 'application/octet-stream';
+
+CREATE INDEX file_filename_trgm_idx ON file USING GIN (filename gin_trgm_ops);
+
+/* text extracted from text-like files, kept apart so loading file rows stays cheap */
+CREATE TABLE file_text (
+    reference_uuid UUID PRIMARY KEY REFERENCES file (reference_uuid) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    search_vector TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', content)) STORED
+);
+
+CREATE INDEX file_text_search_vector_idx ON file_text USING GIN (search_vector);
@@ -0,0 +1,7 @@
+/* tags are stored lowercase and sorted, metadata maps string keys to string values */
+ALTER TABLE file
+    ADD COLUMN starred BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN metadata JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX file_tags_idx ON file USING GIN (tags);
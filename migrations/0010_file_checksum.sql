@@ -0,0 +1,2 @@
+/* hex sha256 of the content, used as ETag; files stored before are hashed on their next download or reindex */
+ALTER TABLE file ADD COLUMN sha256 VARCHAR(64);
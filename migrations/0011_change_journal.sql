@@ -0,0 +1,12 @@
+/* journal of file changes for sync clients, ids of an owner are committed in increasing order */
+CREATE TABLE file_change (
+    id BIGSERIAL PRIMARY KEY,
+    owner_uuid UUID NOT NULL REFERENCES "users" (uuid) ON DELETE CASCADE,
+    /* no foreign key, deletions are journaled too */
+    reference_uuid UUID NOT NULL,
+    kind VARCHAR NOT NULL,
+    filename VARCHAR,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX file_change_owner_idx ON file_change (owner_uuid, id);
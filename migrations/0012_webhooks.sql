@@ -0,0 +1,29 @@
+/* outgoing webhooks of users, the secret is kept in plain text as it signs every payload */
+CREATE TABLE webhook (
+    uuid UUID PRIMARY KEY,
+    owner_uuid UUID NOT NULL REFERENCES "users" (uuid) ON DELETE CASCADE,
+    url VARCHAR NOT NULL,
+    secret VARCHAR NOT NULL,
+    events TEXT[] NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX webhook_owner_idx ON webhook (owner_uuid);
+
+/* queue and log of deliveries, pending ones are retried until they succeed or run out of attempts */
+CREATE TABLE webhook_delivery (
+    id BIGSERIAL PRIMARY KEY,
+    webhook_uuid UUID NOT NULL REFERENCES webhook (uuid) ON DELETE CASCADE,
+    event VARCHAR NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    response_status INTEGER,
+    error VARCHAR,
+    next_attempt TIMESTAMPTZ NOT NULL DEFAULT now(),
+    lease_until TIMESTAMPTZ,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX webhook_delivery_due_idx ON webhook_delivery (next_attempt) WHERE status = 'pending';
+CREATE INDEX webhook_delivery_webhook_idx ON webhook_delivery (webhook_uuid, id);
@@ -0,0 +1,28 @@
+/* durable queue of background jobs, claimed with FOR UPDATE SKIP LOCKED by any number of workers */
+CREATE TABLE job (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    payload JSONB NOT NULL,
+    /* queued, running, succeeded or failed */
+    status VARCHAR NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    /* set while a worker runs the job, running jobs with an expired lease are picked up again */
+    lease_until TIMESTAMPTZ,
+    last_error VARCHAR,
+    /* at most one queued job per key */
+    unique_key VARCHAR,
+    finished_at TIMESTAMPTZ,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX job_due_idx ON job (run_at) WHERE status IN ('queued', 'running');
+CREATE UNIQUE INDEX job_unique_key_idx ON job (unique_key) WHERE status = 'queued';
+CREATE INDEX job_finished_idx ON job (finished_at) WHERE finished_at IS NOT NULL;
+
+/* next run of every recurring job, shared so only one instance enqueues it */
+CREATE TABLE job_schedule (
+    name VARCHAR PRIMARY KEY,
+    next_run TIMESTAMPTZ NOT NULL
+);
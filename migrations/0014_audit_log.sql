@@ -0,0 +1,28 @@
+/* append-only log of security relevant actions, entries are only ever removed by the retention */
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    /* no foreign keys, entries outlive deleted accounts and files */
+    actor_uuid UUID,
+    /* username at the time, or the one tried by a failed login */
+    actor VARCHAR,
+    action VARCHAR NOT NULL,
+    target_uuid UUID,
+    ip VARCHAR,
+    user_agent VARCHAR,
+    outcome VARCHAR NOT NULL,
+    detail VARCHAR,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX audit_log_actor_idx ON audit_log (actor_uuid, id);
+CREATE INDEX audit_log_target_idx ON audit_log (target_uuid, id);
+CREATE INDEX audit_log_timestamp_idx ON audit_log (timestamp);
+
+CREATE FUNCTION audit_log_immutable() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit log entries can not be changed';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_immutable BEFORE UPDATE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION audit_log_immutable();
@@ -0,0 +1,2 @@
+/* result of the virus scan: unscanned, pending, clean, infected or failed; files stored before are unscanned */
+ALTER TABLE file ADD COLUMN scan_status VARCHAR NOT NULL DEFAULT 'unscanned';
@@ -0,0 +1,11 @@
+/* progress of file batches run by the job queue, results are appended as the job works through the operations */
+CREATE TABLE batch (
+    uuid UUID PRIMARY KEY,
+    owner_uuid UUID NOT NULL REFERENCES "users" (uuid) ON DELETE CASCADE,
+    total INTEGER NOT NULL,
+    results JSONB NOT NULL DEFAULT '[]',
+    finished BOOLEAN NOT NULL DEFAULT false,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX batch_finished_idx ON batch (timestamp) WHERE finished;
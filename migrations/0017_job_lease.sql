@@ -0,0 +1,2 @@
+/* identifies the claim of the worker running a job, outcomes of a worker that lost its lease are ignored */
+ALTER TABLE job ADD COLUMN lease_token UUID;
@@ -0,0 +1,72 @@
+use crate::config::{Config, EventBusBackend, RateLimitBackend, RepositoryBackend};
+use crate::models::appstate::Appstate;
+use crate::repository::memory::{
+    MemoryAuditRepository, MemoryBatchRepository, MemoryDeletionRepository, MemoryEmailChangeRepository, MemoryFileRepository,
+    MemoryIdentityRepository, MemoryJobRepository, MemoryUserRepository, MemoryWebhookRepository,
+};
+use crate::router;
+use crate::util::events::postgres::PgEventBus;
+use crate::util::mail::log::LogMailer;
+use crate::util::mail::mailer::Mailer;
+use crate::util::mail::smtp::SmtpMailer;
+use crate::util::ratelimit::memory::MemoryRateLimitStore;
+use crate::util::ratelimit::postgres::PgRateLimitStore;
+use crate::util::ratelimit::store::RateLimitStore;
+use crate::util::scan::clamd::ClamdScanner;
+use axum::Router;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Pool, Postgres};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Appstate with the mailer, rate limit store and repositories selected by the config
+pub fn appstate(config: Arc<Config>, db_pool: Arc<Pool<Postgres>>) -> Result<Appstate, Box<dyn Error + Send + Sync>> {
+    let cookie_key = config.cookie_key()
+        .ok_or("auth.cookie_secret (COOKIE_SECRET) must be at least 64 bytes long")?;
+
+    // mails are only printed when no smtp server is configured
+    let mailer: Arc<dyn Mailer> = match &config.mail {
+        Some(mail) => Arc::new(SmtpMailer::new(&mail.smtp_url, &mail.from)?),
+        None => Arc::new(LogMailer),
+    };
+
+    // rate limit counters, postgres shares them between multiple instances
+    let rate_limiter: Arc<dyn RateLimitStore> = match config.rate_limit.store {
+        RateLimitBackend::Postgres => Arc::new(PgRateLimitStore::new(db_pool.clone())),
+        RateLimitBackend::Memory => Arc::new(MemoryRateLimitStore::new()),
+    };
+
+    let mut appstate = Appstate::new(db_pool.clone(), config.clone(), cookie_key, rate_limiter, mailer);
+    // the in-process bus is the default, postgres needs `listen` running to deliver anything
+    if config.events.bus == EventBusBackend::Postgres {
+        appstate = appstate.with_events(Arc::new(PgEventBus::new(db_pool)));
+    }
+    if let Some(scan) = &config.scan {
+        appstate = appstate.with_scanner(Arc::new(ClamdScanner::new(&scan.clamd_address, Duration::from_secs(scan.timeout))));
+    }
+
+    Ok(match config.database.repository {
+        RepositoryBackend::Postgres => appstate,
+        RepositoryBackend::Memory => appstate.with_repositories(
+            Arc::new(MemoryUserRepository::new()),
+            Arc::new(MemoryFileRepository::new()),
+            Arc::new(MemoryWebhookRepository::new()),
+            Arc::new(MemoryAuditRepository::new()),
+        ).with_account_repositories(
+            Arc::new(MemoryDeletionRepository::new()),
+            Arc::new(MemoryEmailChangeRepository::new()),
+            Arc::new(MemoryIdentityRepository::new()),
+        ).with_jobs(Arc::new(MemoryJobRepository::new()), Arc::new(MemoryBatchRepository::new())),
+    })
+}
+
+/// Validates the config and builds the whole application, the database is connected on first use \
+/// Single sign-on, ldap and the background jobs need the async setup of `serve` and aren't enabled here
+pub fn build_app(config: Config) -> Result<Router, Box<dyn Error + Send + Sync>> {
+    config.validate()?;
+    let pool = PgPoolOptions::new().connect_lazy(&config.database.url)?;
+
+    let appstate = appstate(Arc::new(config), Arc::new(pool))?;
+    Ok(router::app(Arc::new(appstate)))
+}
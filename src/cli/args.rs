@@ -0,0 +1,40 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Arguments of the `drive` binary, `serve` is run without a command
+#[derive(Parser, Debug)]
+#[command(name = "drive", version, about = "Self-hosted file storage")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Runs the server, the default without a command
+    Serve,
+    /// Applies pending database migrations
+    Migrate,
+    /// Creates an admin, the password is read from DRIVE_PASSWORD or stdin
+    CreateAdmin { username: String, email: String },
+    /// Sets a temporary password that has to be changed on the next login and ends all sessions
+    ResetPassword { username: String },
+    /// Lists all users with their storage usage
+    Users,
+    /// Compares the stored files with the database
+    VerifyStorage {
+        /// Removes orphaned files and rows of missing files and corrects wrong sizes
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Detects missing file types and extracts the searchable text of all files again
+    Reindex,
+    /// Scans all files that weren't scanned yet or whose scan failed, infected ones are quarantined
+    Scan,
+    /// Copies a directory tree into the drive of a user
+    Import { username: String, source: PathBuf },
+    /// Copies all files of a user and a manifest with their metadata into an empty directory
+    Export { username: String, target: PathBuf },
+    /// Runs the background jobs without serving requests, e.g. next to servers with `JOB_WORKERS=false`
+    Worker,
+}
@@ -0,0 +1,135 @@
+use crate::models::appstate::Appstate;
+use crate::models::change;
+use crate::models::file::{orphaned_files, ScanStatus};
+use crate::repository::file::FileUpdate;
+use crate::util::mime;
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Compares the `file` table with the files on disk \
+/// Reports rows without a file, wrong sizes and files without a row, `fix` repairs all of them
+pub async fn verify(fix: bool, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut problems = 0;
+
+    let files = appstate.files.all().await?;
+
+    let mut known = HashSet::with_capacity(files.len());
+    for file in files {
+        known.insert(file.relative_path.clone());
+
+        match tokio::fs::metadata(&file.absolute_path).await {
+            Ok(metadata) if metadata.len() as usize == file.size => {},
+            Ok(metadata) => {
+                problems += 1;
+                println!("size mismatch: {} ({}) has {} bytes, expected {}",
+                    file.relative_path, file.filename, metadata.len(), file.size);
+                if fix {
+                    // the content differs from what was hashed
+                    let update = FileUpdate::Content {
+                        reference_uuid: file.reference_uuid,
+                        size: metadata.len() as usize,
+                        sha256: file.compute_sha256().await?,
+                    };
+                    change::apply(appstate, file.owner_uuid, &[update]).await?;
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                problems += 1;
+                println!("missing: {} ({})", file.relative_path, file.filename);
+                if fix {
+                    change::apply(appstate, file.owner_uuid, &[FileUpdate::Delete { reference_uuid: file.reference_uuid }]).await?;
+                }
+            },
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // everything on disk should be referenced by a row
+    for (path, relative_path) in orphaned_files(Path::new(&appstate.file_location), &known).await? {
+        problems += 1;
+        println!("orphaned: {}", relative_path);
+        if fix {
+            tokio::fs::remove_file(&path).await?;
+        }
+    }
+
+    match (problems, fix) {
+        (0, _) => println!("Storage is consistent"),
+        (_, true) => println!("Fixed {} problems", problems),
+        (_, false) => return Err(format!("Found {} problems, run with --fix to repair them", problems).into()),
+    }
+    Ok(())
+}
+
+/// Detects the type of files stored without one and extracts the searchable text of all files again \
+/// Makes files uploaded before the search was added searchable and hashes files stored without a checksum
+pub async fn reindex(appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let files = appstate.files.all().await?;
+    let (mut indexed, mut failed) = (0, 0);
+
+    for mut file in files {
+        let guessed = mime::guess(&file.filename);
+        if file.mime_type == "application/octet-stream" && guessed != file.mime_type {
+            appstate.files.set_mime_type(file.reference_uuid, &guessed).await?;
+            file.mime_type = guessed;
+        }
+
+        // files stored before checksums were kept
+        if let Err(e) = file.ensure_sha256(appstate).await {
+            println!("failed to hash {} ({}): {}", file.relative_path, file.filename, e);
+        }
+
+        match file.index(appstate).await {
+            Ok(_) => indexed += 1,
+            Err(e) => {
+                failed += 1;
+                println!("failed to index {} ({}): {}", file.relative_path, file.filename, e);
+            },
+        }
+    }
+
+    println!("Reindexed {} files, {} failed", indexed, failed);
+    Ok(())
+}
+
+/// Scans every file that wasn't found clean or infected yet, e.g. after scanning was enabled \
+/// Files found clean are indexed, so the search finds them
+pub async fn scan(appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if appstate.scanner.is_none() {
+        return Err("Scanning is not configured, set scan.clamd_address (CLAMD_ADDRESS)".into())
+    }
+    let files = appstate.files.all().await?;
+    let (mut clean, mut infected, mut failed) = (0, 0, 0);
+
+    for mut file in files {
+        if matches!(file.scan_status, ScanStatus::Clean | ScanStatus::Infected) {
+            continue
+        }
+        match file.scan(appstate).await {
+            Ok(Some(change)) => change::publish(appstate, &change).await,
+            Ok(None) => {},
+            Err(e) => {
+                failed += 1;
+                println!("failed to scan {} ({}): {}", file.relative_path, file.filename, e);
+                continue
+            },
+        }
+        match file.scan_status {
+            ScanStatus::Infected => {
+                infected += 1;
+                println!("quarantined: {} ({})", file.relative_path, file.filename);
+            },
+            _ => {
+                clean += 1;
+                if let Err(e) = file.index(appstate).await {
+                    println!("failed to index {} ({}): {}", file.relative_path, file.filename, e);
+                }
+            },
+        }
+    }
+
+    println!("Scanned {} files, {} clean, {} infected, {} failed", clean + infected, clean, infected, failed);
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use crate::models::appstate::Appstate;
+use crate::models::change;
+use crate::models::file::{File, PartialFile, ScanStatus};
+use crate::models::user::Permission;
+use crate::repository::file::FileUpdate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+
+/// Written to `manifest.json` of an export
+#[derive(Serialize)]
+struct Manifest {
+    uuid: Uuid,
+    username: String,
+    email: String,
+    permission: Permission,
+    quota: Option<usize>,
+    timestamp: usize,
+    files: Vec<ExportedFile>,
+}
+
+#[derive(Serialize)]
+struct ExportedFile {
+    reference_uuid: Uuid,
+    filename: String,
+    /// Relative to the export directory, None if the file was missing on disk or is quarantined
+    path: Option<String>,
+    size: usize,
+    mime_type: String,
+    starred: bool,
+    tags: Vec<String>,
+    metadata: BTreeMap<String, String>,
+    /// Hex sha256 of the content, None for files that were never hashed
+    sha256: Option<String>,
+    timestamp: usize,
+}
+
+/// Copies every regular file below `source` into the drive of a user \
+/// Filenames keep their path relative to `source`, symlinks are skipped
+pub async fn import(username: &str, source: &Path, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let user = appstate.users.get_by_username(username).await?
+        .ok_or("User does not exist")?;
+
+    let mut usage = appstate.files.usage(user.uuid).await?.bytes;
+    let (mut imported, mut bytes, mut skipped) = (0, 0, 0);
+
+    let mut dirs = vec![source.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            // doesn't follow symlinks
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                dirs.push(path);
+                continue
+            }
+            if !file_type.is_file() {
+                println!("skipped {}: not a regular file", path.display());
+                skipped += 1;
+                continue
+            }
+
+            let size = entry.metadata().await?.len() as usize;
+            if size > appstate.config.storage.max_file_size {
+                println!("skipped {}: exceeds the maximum file size", path.display());
+                skipped += 1;
+                continue
+            }
+            if user.quota.is_some_and(|quota| usage + size > quota) {
+                println!("skipped {}: storage quota exceeded", path.display());
+                skipped += 1;
+                continue
+            }
+
+            let filename = path.strip_prefix(source)?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let mut file = File::construct(None, filename, &user, size, appstate).await
+                .ok_or("Failed to construct File")?;
+
+            // removes the copy unless it's referenced in the db
+            let partial = PartialFile::new(&file.absolute_path);
+            file.size = tokio::fs::copy(&path, &file.absolute_path).await? as usize;
+            file.sha256 = Some(file.compute_sha256().await?);
+            if appstate.scanner.is_some() {
+                file.scan_status = ScanStatus::Pending;
+            }
+            change::apply(appstate, user.uuid, &[FileUpdate::Insert(Box::new(file.clone()))]).await?;
+            partial.keep();
+            match file.scan(appstate).await {
+                Ok(Some(change)) => change::publish(appstate, &change).await,
+                Ok(None) => {},
+                Err(e) => println!("failed to scan {}: {}", path.display(), e),
+            }
+            if file.scan_status == ScanStatus::Infected {
+                println!("quarantined {}: infected", path.display());
+            } else if let Err(e) = file.index(appstate).await {
+                println!("failed to index {}: {}", path.display(), e);
+            }
+
+            usage += file.size;
+            bytes += file.size;
+            imported += 1;
+        }
+    }
+
+    println!("Imported {} files ({} bytes), skipped {}", imported, bytes, skipped);
+    Ok(())
+}
+
+/// Copies all files of a user below `target/files` and writes their metadata to `target/manifest.json` \
+/// `target` has to be empty or not exist yet
+pub async fn export(username: &str, target: &Path, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let user = appstate.users.get_by_username(username).await?
+        .ok_or("User does not exist")?;
+
+    if tokio::fs::try_exists(target).await? && tokio::fs::read_dir(target).await?.next_entry().await?.is_some() {
+        return Err("Target directory is not empty".into())
+    }
+    let files_dir = target.join("files");
+    tokio::fs::create_dir_all(&files_dir).await?;
+
+    let stored = appstate.files.list(user.uuid).await?;
+
+    let mut files = Vec::with_capacity(stored.len());
+    let mut bytes = 0;
+    for file in stored {
+        // filenames may repeat, later ones get their reference uuid as prefix
+        let mut relative_path = export_path(&file);
+        if tokio::fs::try_exists(files_dir.join(&relative_path)).await? {
+            let name = relative_path.file_name().unwrap_or_default().to_string_lossy();
+            relative_path = relative_path.with_file_name(format!("{}-{}", file.reference_uuid, name));
+        }
+        let path = files_dir.join(&relative_path);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // quarantined files are only listed in the manifest
+        let copied = match file.scan_status {
+            ScanStatus::Infected => {
+                println!("skipped quarantined: {} ({})", file.relative_path, file.filename);
+                None
+            },
+            _ => match tokio::fs::copy(&file.absolute_path, &path).await {
+                Ok(copied) => Some(copied),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    println!("missing on disk: {} ({})", file.relative_path, file.filename);
+                    None
+                },
+                Err(e) => return Err(e.into()),
+            },
+        };
+        let path = copied.map(|copied| {
+            bytes += copied as usize;
+            format!("files/{}", relative_path.to_string_lossy().replace('\\', "/"))
+        });
+
+        files.push(ExportedFile {
+            reference_uuid: file.reference_uuid,
+            filename: file.filename,
+            path,
+            size: file.size,
+            mime_type: file.mime_type,
+            starred: file.starred,
+            tags: file.tags,
+            metadata: file.metadata,
+            sha256: file.sha256,
+            timestamp: file.timestamp,
+        });
+    }
+
+    let count = files.len();
+    let manifest = Manifest {
+        uuid: user.uuid,
+        username: user.username,
+        email: user.email,
+        permission: user.permission,
+        quota: user.quota,
+        timestamp: user.timestamp,
+        files,
+    };
+    tokio::fs::write(target.join("manifest.json"), serde_json::to_vec_pretty(&manifest)?).await?;
+
+    println!("Exported {} files ({} bytes) to {}", count, bytes, target.display());
+    Ok(())
+}
+
+/// Turns a filename into a relative path that can't escape the export directory
+fn export_path(file: &File) -> PathBuf {
+    let path: PathBuf = Path::new(&file.filename.replace('\\', "/"))
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect();
+
+    if path.as_os_str().is_empty() {
+        PathBuf::from(file.reference_uuid.to_string())
+    } else {
+        path
+    }
+}
@@ -0,0 +1,86 @@
+use crate::models::appstate::Appstate;
+use crate::models::user::{Permission, User};
+use crate::repository::error::RepositoryError;
+use crate::util::{token, validation};
+use std::error::Error;
+use std::io::BufRead;
+use uuid::Uuid;
+
+/// Reads a password from `DRIVE_PASSWORD` or the first line of stdin \
+/// Keeps it out of the shell history and the process list
+pub fn read_password() -> Result<String, Box<dyn Error + Send + Sync>> {
+    if let Ok(password) = std::env::var("DRIVE_PASSWORD") {
+        return Ok(password)
+    }
+
+    eprint!("Password: ");
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Creates an admin, e.g. the first user of an instance without local sign up
+pub async fn create_admin(username: String, email: String, password: String, appstate: &Appstate)
+    -> Result<(), Box<dyn Error + Send + Sync>> {
+    let checks = [
+        ("username", validation::username(&username)),
+        ("password", validation::password(&password)),
+        ("email", validation::email(&email)),
+    ];
+    let errors: Vec<String> = checks.into_iter()
+        .filter(|(_, (valid, _))| !valid)
+        .map(|(field, (_, reason))| format!("{}: {}", field, reason))
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into())
+    }
+
+    let hashed_password = User::hash_password(&password).map_err(|e| e.to_string())?;
+    let user = User::new(username, hashed_password, email, Permission::ADMIN);
+
+    match appstate.users.insert(&user).await {
+        Ok(_) => {},
+        Err(RepositoryError::Conflict) => return Err("Username is already taken".into()),
+        Err(e) => return Err(e.into()),
+    }
+
+    println!("Created admin {} ({})", user.username, user.uuid);
+    Ok(())
+}
+
+/// Replaces the password of a user with a random one that has to be changed on the next login \
+/// Also ends all sessions and lifts a login lockout
+pub async fn reset_password(username: &str, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let user = appstate.users.get_by_username(username).await?
+        .ok_or("User does not exist")?;
+
+    let password = token::generate()[..20].to_string();
+    let hashed_password = User::hash_password(&password).map_err(|e| e.to_string())?;
+
+    appstate.users.set_password(user.uuid, &hashed_password, Uuid::new_v4(), true).await?;
+
+    let key = format!("login:user:{}", user.username.to_lowercase());
+    appstate.rate_limiter.reset_failures(&key).await?;
+
+    println!("Temporary password of {}: {}", user.username, password);
+    Ok(())
+}
+
+/// Prints every user with their storage usage
+pub async fn list(appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let users = appstate.users.list("", usize::MAX, 0).await?;
+    let uuids: Vec<Uuid> = users.iter().map(|user| user.uuid).collect();
+    let usage = appstate.files.usage_by_owner(&uuids).await?;
+
+    println!("{:<36}  {:<16}  {:<32}  {:<10}  {:>8}  {:>14}  {:>14}",
+        "UUID", "USERNAME", "EMAIL", "PERMISSION", "FILES", "USAGE", "QUOTA");
+    for user in users {
+        let usage = usage.get(&user.uuid).copied().unwrap_or_default();
+        let permission = if user.disabled { "DISABLED".to_string() } else { format!("{:?}", user.permission) };
+        let quota = user.quota.map(|q| q.to_string()).unwrap_or_else(|| "-".to_string());
+        println!("{:<36}  {:<16}  {:<32}  {:<10}  {:>8}  {:>14}  {:>14}",
+            user.uuid, user.username, user.email, permission, usage.files, usage.bytes, quota);
+    }
+
+    Ok(())
+}
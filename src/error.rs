@@ -0,0 +1,224 @@
+use crate::util::request_id;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use jsonwebtoken::errors::ErrorKind;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use utoipa::ToSchema;
+
+/// Error returned by every handler and middleware \
+/// Rendered as `{"code", "message", "details", "request_id"}` with the matching status
+#[derive(Debug)]
+pub enum ApiError {
+    /// Malformed or otherwise unusable request
+    BadRequest(&'static str),
+    /// Input didn't pass validation, every failed field is listed in the details
+    Validation(Vec<FieldError>),
+    /// Missing or invalid credentials
+    Unauthorized(&'static str),
+    /// Authenticated but not allowed to do this
+    Forbidden(&'static str),
+    NotFound(&'static str),
+    /// Collides with existing data, e.g. a taken username
+    Conflict(&'static str),
+    PayloadTooLarge(&'static str),
+    /// A conditional header like `If-Match` didn't match the current state
+    PreconditionFailed(&'static str),
+    /// Seconds until the client may retry are sent in `Retry-After`
+    TooManyRequests(&'static str, i64),
+    /// Temporarily can't handle the request, e.g. while shutting down
+    Unavailable(&'static str),
+    /// Unexpected failure, the message says what failed without leaking internals
+    Internal(&'static str),
+    Database(sqlx::Error),
+    Io(std::io::Error),
+    Jwt(jsonwebtoken::errors::Error),
+    /// Rejection produced by axum itself, e.g. an unparsable json body
+    Rejection(StatusCode, String),
+}
+
+/// Reason a single input field failed validation
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, reason: impl Into<String>) -> Self {
+        Self { field, reason: reason.into() }
+    }
+}
+
+/// Json body of every error response
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable machine readable code, e.g. `validation_failed`
+    code: &'static str,
+    message: String,
+    /// Failed fields of a `validation_failed` error
+    details: Vec<FieldError>,
+    /// Also sent in the `x-request-id` header, include it when reporting a problem
+    request_id: Option<String>,
+}
+
+impl ApiError {
+    /// Internal error with a message for the client, the cause is only logged
+    pub fn internal(message: &'static str, cause: impl Display) -> Self {
+        eprintln!("request {}: {}: {}", request_id::current().as_deref().unwrap_or("-"), message, cause);
+        ApiError::Internal(message)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::TooManyRequests(..) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Database(e) => match e {
+                sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+                sqlx::Error::Database(db_err) if db_err.is_unique_violation() => StatusCode::CONFLICT,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            ApiError::Io(e) => match e.kind() {
+                std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            ApiError::Jwt(e) => match e.kind() {
+                ErrorKind::InvalidToken
+                | ErrorKind::InvalidSignature
+                | ErrorKind::ExpiredSignature
+                | ErrorKind::ImmatureSignature
+                | ErrorKind::InvalidIssuer
+                | ErrorKind::InvalidAudience
+                | ErrorKind::InvalidSubject
+                | ErrorKind::InvalidAlgorithm
+                | ErrorKind::MissingRequiredClaim(_)
+                | ErrorKind::Base64(_)
+                | ErrorKind::Json(_)
+                | ErrorKind::Utf8(_) => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            ApiError::Rejection(status, _) => *status,
+        }
+    }
+
+    /// Stable machine readable code, clients should match on this instead of the message
+    pub fn code(&self) -> &'static str {
+        match self.status() {
+            StatusCode::BAD_REQUEST if matches!(self, ApiError::Validation(_)) => "validation_failed",
+            StatusCode::BAD_REQUEST => "bad_request",
+            StatusCode::UNAUTHORIZED => "unauthorized",
+            StatusCode::FORBIDDEN => "forbidden",
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+            StatusCode::CONFLICT => "conflict",
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            StatusCode::PRECONDITION_FAILED => "precondition_failed",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+            StatusCode::UNPROCESSABLE_ENTITY => "unprocessable_entity",
+            StatusCode::TOO_MANY_REQUESTS => "rate_limited",
+            StatusCode::SERVICE_UNAVAILABLE => "unavailable",
+            status if status.is_client_error() => "client_error",
+            _ => "internal_error",
+        }
+    }
+
+    /// Message shown to the client, internal errors don't expose their source
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::BadRequest(m)
+            | ApiError::Unauthorized(m)
+            | ApiError::Forbidden(m)
+            | ApiError::NotFound(m)
+            | ApiError::Conflict(m)
+            | ApiError::PayloadTooLarge(m)
+            | ApiError::PreconditionFailed(m)
+            | ApiError::TooManyRequests(m, _)
+            | ApiError::Unavailable(m)
+            | ApiError::Internal(m) => m.to_string(),
+            ApiError::Validation(_) => "Validation failed".to_string(),
+            ApiError::Rejection(_, m) => m.clone(),
+            ApiError::Database(_) | ApiError::Io(_) | ApiError::Jwt(_) => match self.status() {
+                StatusCode::NOT_FOUND => "Not found".to_string(),
+                StatusCode::CONFLICT => "Already exists".to_string(),
+                StatusCode::UNAUTHORIZED => "Invalid token".to_string(),
+                _ => "Internal server error".to_string(),
+            },
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Database(e) => write!(f, "database error: {}", e),
+            ApiError::Io(e) => write!(f, "io error: {}", e),
+            ApiError::Jwt(e) => write!(f, "jwt error: {}", e),
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let request_id = request_id::current();
+
+        // the client only sees a generic message, so keep the cause in the log
+        if status.is_server_error() && !matches!(self, ApiError::Internal(_) | ApiError::Unavailable(_)) {
+            eprintln!("request {}: {}", request_id.as_deref().unwrap_or("-"), self);
+        }
+
+        let retry_after = match &self {
+            ApiError::TooManyRequests(_, seconds) => Some(HeaderValue::from((*seconds).max(1))),
+            _ => None,
+        };
+
+        let details = match &self {
+            ApiError::Validation(errors) => errors.clone(),
+            _ => Vec::new(),
+        };
+
+        let body = ErrorBody {
+            code: self.code(),
+            message: self.message(),
+            details,
+            request_id,
+        };
+
+        let mut response = (status, Json(body)).into_response();
+        if let Some(retry_after) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after);
+        }
+        response
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::Database(e)
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        ApiError::Io(e)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for ApiError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        ApiError::Jwt(e)
+    }
+}
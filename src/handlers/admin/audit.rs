@@ -0,0 +1,103 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::handlers::audit::{self, ExportFormat, Response};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::audit::{AuditAction, AuditEntry, AuditFilter, AuditOutcome};
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    /// Uuid of the user who acted
+    actor: Option<Uuid>,
+    action: Option<AuditAction>,
+    outcome: Option<AuditOutcome>,
+    /// Reference of a file
+    target: Option<Uuid>,
+    /// Unix timestamps, both inclusive
+    from: Option<i64>,
+    to: Option<i64>,
+    /// `cursor` of the previous page
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    /// `csv` unless set
+    #[param(inline)]
+    format: Option<ExportFormat>,
+    actor: Option<Uuid>,
+    action: Option<AuditAction>,
+    outcome: Option<AuditOutcome>,
+    target: Option<Uuid>,
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+/// Audit log of all users, failed logins of unknown usernames included
+#[utoipa::path(
+    get, path = "/audit", tag = "admin",
+    params(Params),
+    responses(
+        (status = 200, description = "Matching entries", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn list_audit_log(
+    State(appstate): State<AppstateWrapper>,
+    Query(params): Query<Params>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+
+    let filter = AuditFilter {
+        actor_uuid: params.actor,
+        action: params.action,
+        target_uuid: params.target,
+        outcome: params.outcome,
+        from: params.from,
+        to: params.to,
+        before: params.before,
+    };
+    audit::page(&filter, params.limit, &appstate).await.map(Json)
+}
+
+/// Downloads the audit log of all users as attachment, at most 100000 entries
+#[utoipa::path(
+    get, path = "/audit/export", tag = "admin",
+    params(ExportParams),
+    responses(
+        (status = 200, description = "Matching entries, newest first", content(
+            (String = "text/csv"),
+            (Vec<AuditEntry> = "application/json"),
+        )),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn export_audit_log(
+    State(appstate): State<AppstateWrapper>,
+    Query(params): Query<ExportParams>,
+) -> Result<axum_core::response::Response, ApiError> {
+    let appstate = appstate.0;
+
+    let filter = AuditFilter {
+        actor_uuid: params.actor,
+        action: params.action,
+        target_uuid: params.target,
+        outcome: params.outcome,
+        from: params.from,
+        to: params.to,
+        before: None,
+    };
+    audit::export(&filter, params.format.unwrap_or_default(), &appstate).await
+}
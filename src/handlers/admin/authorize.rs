@@ -0,0 +1,21 @@
+use crate::error::ApiError;
+use crate::models::user::AuthUser;
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Extension;
+
+/// Only lets admins pass, has to be layered inside the `auth` middleware
+#[axum_macros::debug_middleware]
+pub async fn admin(
+    Extension(auth_user): Extension<AuthUser>,
+    req: Request,
+    next: Next
+) -> Result<Response, ApiError> {
+    if !auth_user.0.is_admin() {
+        return Err(ApiError::Forbidden("Admin permission required"))
+    }
+
+    let response = next.run(req).await;
+    Ok(response)
+}
@@ -0,0 +1,31 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::job::QueuedJob;
+use axum::extract::{Path, State};
+use axum::Json;
+
+/// Shows a single background job with its latest error
+#[utoipa::path(
+    get, path = "/jobs/{job_id}", tag = "admin",
+    params(("job_id" = i64, Path, description = "Id of the job")),
+    responses(
+        (status = 200, description = "The job", body = QueuedJob),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "Job does not exist", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn get_job(
+    State(appstate): State<AppstateWrapper>,
+    Path(job_id): Path<i64>,
+) -> Result<Json<QueuedJob>, ApiError> {
+    let appstate = appstate.0;
+
+    let job = appstate.jobs.get(job_id).await
+        .map_err(|e| ApiError::internal("Failed to fetch job from db", e))?
+        .ok_or(ApiError::NotFound("Job does not exist"))?;
+
+    Ok(Json(job))
+}
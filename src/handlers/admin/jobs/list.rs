@@ -0,0 +1,54 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::job::{JobCount, JobStatus, QueuedJob};
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    status: Option<JobStatus>,
+    kind: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Jobs)]
+pub struct Response {
+    /// Number of jobs per kind and status, regardless of the filters
+    counts: Vec<JobCount>,
+    /// Newest first
+    jobs: Vec<QueuedJob>,
+}
+
+/// Lists background jobs, optionally filtered by status and kind
+#[utoipa::path(
+    get, path = "/jobs", tag = "admin",
+    params(Params),
+    responses(
+        (status = 200, description = "Matching jobs", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn list_jobs(
+    State(appstate): State<AppstateWrapper>,
+    Query(params): Query<Params>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+
+    let limit = params.limit.unwrap_or(50).clamp(1, 500) as usize;
+    let offset = params.offset.unwrap_or(0).max(0) as usize;
+
+    let jobs = appstate.jobs.list(params.status, params.kind.as_deref(), limit, offset).await
+        .map_err(|e| ApiError::internal("Failed to fetch jobs from db", e))?;
+    let counts = appstate.jobs.counts().await
+        .map_err(|e| ApiError::internal("Failed to count jobs", e))?;
+
+    Ok(Json(Response { counts, jobs }))
+}
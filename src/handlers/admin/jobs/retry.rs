@@ -0,0 +1,44 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::job::{JobStatus, QueuedJob};
+use axum::extract::{Path, State};
+use axum::Json;
+
+/// Queues a failed job again with fresh attempts
+#[utoipa::path(
+    post, path = "/jobs/{job_id}/retry", tag = "admin",
+    params(("job_id" = i64, Path, description = "Id of the job")),
+    responses(
+        (status = 200, description = "Job queued again", body = QueuedJob),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "Job does not exist", body = ErrorBody),
+        (status = 409, description = "Job hasn't failed", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn retry_job(
+    State(appstate): State<AppstateWrapper>,
+    Path(job_id): Path<i64>,
+) -> Result<Json<QueuedJob>, ApiError> {
+    let appstate = appstate.0;
+
+    let job = appstate.jobs.get(job_id).await
+        .map_err(|e| ApiError::internal("Failed to fetch job from db", e))?
+        .ok_or(ApiError::NotFound("Job does not exist"))?;
+    if job.status != JobStatus::Failed {
+        return Err(ApiError::Conflict("Only failed jobs can be retried"))
+    }
+
+    let retried = appstate.jobs.retry(job_id).await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+    if !retried {
+        return Err(ApiError::Conflict("Only failed jobs can be retried"))
+    }
+
+    let job = appstate.jobs.get(job_id).await
+        .map_err(|e| ApiError::internal("Failed to fetch job from db", e))?
+        .ok_or(ApiError::NotFound("Job does not exist"))?;
+    Ok(Json(job))
+}
@@ -0,0 +1,62 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::user::AuthUser;
+use crate::util::ip::ClientInfo;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = SetDisabled)]
+pub struct Body {
+    disabled: bool,
+}
+
+/// Disables or enables an account \
+/// Disabled users can't log in and existing tokens are rejected by the auth middleware
+#[utoipa::path(
+    put, path = "/users/{user_id}/disabled", tag = "admin",
+    params(("user_id" = Uuid, Path, description = "Uuid of the user")),
+    request_body = Body,
+    responses(
+        (status = 200, description = "Account disabled or enabled"),
+        (status = 400, description = "Can't disable own account", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "User does not exist", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn set_disabled(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+    Path(user_id): Path<Uuid>,
+    client: ClientInfo,
+    Json(body): Json<Body>
+) -> Result<StatusCode, ApiError> {
+    let admin = auth_user.0.0;
+    let appstate = appstate.0;
+
+    if admin.uuid == user_id {
+        return Err(ApiError::BadRequest("Can't disable own account"))
+    }
+
+    let exists = appstate.users.set_disabled(user_id, body.disabled)
+        .await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+
+    if !exists {
+        return Err(ApiError::NotFound("User does not exist"))
+    }
+
+    let action = if body.disabled { AuditAction::AccountDisable } else { AuditAction::AccountEnable };
+    let entry = AuditEntry::new(action, AuditOutcome::Success).actor(&admin).target(user_id).client(&client);
+    audit::record(&appstate, entry).await;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,73 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::{Permission, User};
+use crate::repository::file::Usage;
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = AdminUser)]
+pub struct Response {
+    uuid: Uuid,
+    username: String,
+    email: String,
+    permission: Permission,
+    quota: Option<usize>,
+    disabled: bool,
+    password_reset: bool,
+    /// Bytes used by all files of the user
+    usage: usize,
+    files: usize,
+    timestamp: usize,
+}
+
+impl Response {
+    pub fn new(user: User, usage: Usage) -> Self {
+        Self {
+            uuid: user.uuid,
+            username: user.username,
+            email: user.email,
+            permission: user.permission,
+            quota: user.quota,
+            disabled: user.disabled,
+            password_reset: user.password_reset,
+            usage: usage.bytes,
+            files: usage.files,
+            timestamp: user.timestamp,
+        }
+    }
+}
+
+/// Returns a single user including their storage usage
+#[utoipa::path(
+    get, path = "/users/{user_id}", tag = "admin",
+    params(("user_id" = Uuid, Path, description = "Uuid of the user")),
+    responses(
+        (status = 200, description = "The user", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "User does not exist", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn get_user(
+    State(appstate): State<AppstateWrapper>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+
+    let user = match appstate.users.get(user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(ApiError::NotFound("User does not exist")),
+        Err(e) => return Err(ApiError::internal("Failed to fetch user from db", e))
+    };
+
+    let usage = appstate.files.usage(user_id).await
+        .map_err(|e| ApiError::internal("Failed to fetch storage usage", e))?;
+
+    Ok(Json(Response::new(user, usage)))
+}
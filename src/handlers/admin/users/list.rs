@@ -0,0 +1,56 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::handlers::admin::users::get::Response;
+use crate::models::appstate::AppstateWrapper;
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    /// matches username or email
+    search: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Lists users ordered by username, optionally filtered by a search term
+#[utoipa::path(
+    get, path = "/users", tag = "admin",
+    params(Params),
+    responses(
+        (status = 200, description = "Matching users", body = Vec<Response>),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn list_users(
+    State(appstate): State<AppstateWrapper>,
+    Query(params): Query<Params>,
+) -> Result<Json<Vec<Response>>, ApiError> {
+    let appstate = appstate.0;
+
+    let search = params.search.unwrap_or_default();
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let users = appstate.users.list(&search, limit as usize, offset as usize).await
+        .map_err(|e| ApiError::internal("Failed to fetch users from db", e))?;
+
+    let uuids: Vec<Uuid> = users.iter().map(|user| user.uuid).collect();
+    let usage = appstate.files.usage_by_owner(&uuids).await
+        .map_err(|e| ApiError::internal("Failed to fetch storage usage", e))?;
+
+    let response = users.into_iter()
+        .map(|user| {
+            let usage = usage.get(&user.uuid).copied().unwrap_or_default();
+            Response::new(user, usage)
+        })
+        .collect();
+
+    Ok(Json(response))
+}
@@ -0,0 +1,45 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::user::AuthUser;
+use crate::util::ip::ClientInfo;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Extension;
+use uuid::Uuid;
+
+/// Logs a user out everywhere by rotating their token-id, which invalidates all issued tokens
+#[utoipa::path(
+    put, path = "/users/{user_id}/logout", tag = "admin",
+    params(("user_id" = Uuid, Path, description = "Uuid of the user")),
+    responses(
+        (status = 200, description = "User logged out everywhere"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "User does not exist", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn force_logout(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+    Path(user_id): Path<Uuid>,
+    client: ClientInfo,
+) -> Result<StatusCode, ApiError> {
+    let admin = auth_user.0.0;
+    let appstate = appstate.0;
+
+    let exists = appstate.users.set_tokenid(user_id, Uuid::new_v4())
+        .await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+
+    if !exists {
+        return Err(ApiError::NotFound("User does not exist"))
+    }
+
+    let entry = AuditEntry::new(AuditAction::ForcedLogout, AuditOutcome::Success).actor(&admin).target(user_id).client(&client);
+    audit::record(&appstate, entry).await;
+
+    Ok(StatusCode::OK)
+}
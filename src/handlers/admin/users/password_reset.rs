@@ -0,0 +1,46 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::user::AuthUser;
+use crate::util::ip::ClientInfo;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Extension;
+use uuid::Uuid;
+
+/// Forces a user to change their password \
+/// Until then every protected route except the password change is rejected
+#[utoipa::path(
+    put, path = "/users/{user_id}/password_reset", tag = "admin",
+    params(("user_id" = Uuid, Path, description = "Uuid of the user")),
+    responses(
+        (status = 200, description = "Password change forced"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "User does not exist", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn force_password_reset(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+    Path(user_id): Path<Uuid>,
+    client: ClientInfo,
+) -> Result<StatusCode, ApiError> {
+    let admin = auth_user.0.0;
+    let appstate = appstate.0;
+
+    let exists = appstate.users.require_password_reset(user_id)
+        .await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+
+    if !exists {
+        return Err(ApiError::NotFound("User does not exist"))
+    }
+
+    let entry = AuditEntry::new(AuditAction::PasswordReset, AuditOutcome::Success).actor(&admin).target(user_id).client(&client);
+    audit::record(&appstate, entry).await;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,60 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::user::{AuthUser, Permission};
+use crate::util::ip::ClientInfo;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = SetPermission)]
+pub struct Body {
+    permission: Permission,
+}
+
+/// Changes the permission of a user, admins can't change their own permission
+#[utoipa::path(
+    put, path = "/users/{user_id}/permission", tag = "admin",
+    params(("user_id" = Uuid, Path, description = "Uuid of the user")),
+    request_body = Body,
+    responses(
+        (status = 200, description = "Permission changed"),
+        (status = 400, description = "Can't change own permission", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "User does not exist", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn change_permission(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+    Path(user_id): Path<Uuid>,
+    client: ClientInfo,
+    Json(body): Json<Body>
+) -> Result<StatusCode, ApiError> {
+    let admin = auth_user.0.0;
+    let appstate = appstate.0;
+
+    if admin.uuid == user_id {
+        return Err(ApiError::BadRequest("Can't change own permission"))
+    }
+
+    let exists = appstate.users.set_permission(user_id, body.permission.clone())
+        .await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+
+    if !exists {
+        return Err(ApiError::NotFound("User does not exist"))
+    }
+
+    let entry = AuditEntry::new(AuditAction::PermissionChange, AuditOutcome::Success).actor(&admin).target(user_id).client(&client);
+    audit::record(&appstate, entry.detail(format!("{:?}", body.permission))).await;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,58 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::user::AuthUser;
+use crate::util::ip::ClientInfo;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = SetQuota)]
+pub struct Body {
+    /// Quota in bytes, null removes the quota
+    quota: Option<usize>,
+}
+
+/// Sets the storage quota of a user \
+/// Existing files are kept even if they exceed the new quota
+#[utoipa::path(
+    put, path = "/users/{user_id}/quota", tag = "admin",
+    params(("user_id" = Uuid, Path, description = "Uuid of the user")),
+    request_body = Body,
+    responses(
+        (status = 200, description = "Quota set"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "User does not exist", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn set_quota(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+    Path(user_id): Path<Uuid>,
+    client: ClientInfo,
+    Json(body): Json<Body>
+) -> Result<StatusCode, ApiError> {
+    let admin = auth_user.0.0;
+    let appstate = appstate.0;
+
+    let exists = appstate.users.set_quota(user_id, body.quota)
+        .await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+
+    if !exists {
+        return Err(ApiError::NotFound("User does not exist"))
+    }
+
+    let entry = AuditEntry::new(AuditAction::QuotaChange, AuditOutcome::Success).actor(&admin).target(user_id).client(&client);
+    let quota = body.quota.map(|quota| quota.to_string()).unwrap_or("unlimited".to_string());
+    audit::record(&appstate, entry.detail(quota)).await;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,158 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::{Appstate, AppstateWrapper};
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditFilter, AuditOutcome, MAX_EXPORT};
+use crate::models::user::AuthUser;
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    action: Option<AuditAction>,
+    outcome: Option<AuditOutcome>,
+    /// Reference of a file
+    target: Option<Uuid>,
+    /// Unix timestamps, both inclusive
+    from: Option<i64>,
+    to: Option<i64>,
+    /// `cursor` of the previous page
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = AuditLog)]
+pub struct Response {
+    /// Newest first
+    pub entries: Vec<AuditEntry>,
+    /// Pass as `before` for the next page, unset on the last one
+    pub cursor: Option<i64>,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    /// `csv` unless set
+    #[param(inline)]
+    format: Option<ExportFormat>,
+    action: Option<AuditAction>,
+    outcome: Option<AuditOutcome>,
+    target: Option<Uuid>,
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+/// Audit log of the logged-in user: logins, account changes and file actions
+#[utoipa::path(
+    get, path = "/", tag = "users",
+    params(Params),
+    responses(
+        (status = 200, description = "Entries of the own account", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn list_audit(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Query(params): Query<Params>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let filter = AuditFilter {
+        actor_uuid: Some(user.uuid),
+        action: params.action,
+        target_uuid: params.target,
+        outcome: params.outcome,
+        from: params.from,
+        to: params.to,
+        before: params.before,
+    };
+    page(&filter, params.limit, &appstate).await.map(Json)
+}
+
+/// Downloads the audit log of the logged-in user as attachment, at most 100000 entries
+#[utoipa::path(
+    get, path = "/export", tag = "users",
+    params(ExportParams),
+    responses(
+        (status = 200, description = "Entries of the own account, newest first", content(
+            (String = "text/csv"),
+            (Vec<AuditEntry> = "application/json"),
+        )),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn export_audit(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Query(params): Query<ExportParams>,
+) -> Result<axum_core::response::Response, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let filter = AuditFilter {
+        actor_uuid: Some(user.uuid),
+        action: params.action,
+        target_uuid: params.target,
+        outcome: params.outcome,
+        from: params.from,
+        to: params.to,
+        before: None,
+    };
+    export(&filter, params.format.unwrap_or_default(), &appstate).await
+}
+
+/// Page of at most `limit` entries, one more is fetched to know if there's another page
+pub async fn page(filter: &AuditFilter, limit: Option<i64>, appstate: &Appstate) -> Result<Response, ApiError> {
+    let limit = limit.unwrap_or(100).clamp(1, 1000) as usize;
+    let mut entries = appstate.audit.query(filter, limit + 1).await
+        .map_err(|e| ApiError::internal("Failed to fetch audit log from db", e))?;
+    let cursor = match entries.len() > limit {
+        true => {
+            entries.truncate(limit);
+            entries.last().map(|entry| entry.id)
+        },
+        false => None,
+    };
+    Ok(Response { entries, cursor })
+}
+
+/// Renders the matching entries as attachment
+pub async fn export(filter: &AuditFilter, format: ExportFormat, appstate: &Appstate) -> Result<axum_core::response::Response, ApiError> {
+    let entries = appstate.audit.query(filter, MAX_EXPORT).await
+        .map_err(|e| ApiError::internal("Failed to fetch audit log from db", e))?;
+
+    let (content_type, disposition, body) = match format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "attachment; filename=\"audit-log.csv\"", audit::to_csv(&entries)),
+        ExportFormat::Json => {
+            let json = serde_json::to_string(&entries)
+                .map_err(|e| ApiError::internal("Failed to serialize audit log", e))?;
+            ("application/json", "attachment; filename=\"audit-log.json\"", json)
+        },
+    };
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, content_type), (header::CONTENT_DISPOSITION, disposition)],
+        body,
+    ).into_response())
+}
@@ -0,0 +1,188 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::{Appstate, AppstateWrapper};
+use crate::models::change::Change;
+use crate::models::user::AuthUser;
+use crate::util::events::bus::BusEvent;
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::{Extension, Json};
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Changes read from the journal at once while catching up
+const REPLAY_BATCH: usize = 500;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    /// `cursor` of the previous response, the whole journal is returned without it
+    cursor: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Changes)]
+pub struct Response {
+    /// Oldest first
+    changes: Vec<Change>,
+    /// Pass as `cursor` on the next poll, unchanged if nothing happened
+    cursor: i64,
+    /// More changes are waiting, poll again right away
+    has_more: bool,
+}
+
+/// Changes of the files of the logged-in user since `cursor`, for incremental sync
+#[utoipa::path(
+    get, path = "/", tag = "files",
+    params(Params),
+    responses(
+        (status = 200, description = "Changes after the cursor", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn list_changes(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Query(params): Query<Params>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let cursor = params.cursor.unwrap_or(0).max(0);
+    let limit = params.limit.unwrap_or(500).clamp(1, 1000) as usize;
+
+    // one more than requested tells whether there are more
+    let mut changes = appstate.files.changes(user.uuid, cursor, limit + 1).await
+        .map_err(|e| ApiError::internal("Failed to fetch changes from db", e))?;
+    let has_more = changes.len() > limit;
+    changes.truncate(limit);
+
+    Ok(Json(Response {
+        cursor: changes.last().map_or(cursor, |change| change.id),
+        changes,
+        has_more,
+    }))
+}
+
+/// Pushes the changes of the logged-in user as server-sent events, the event id is the change id \
+/// Reconnecting with `Last-Event-ID` first replays everything missed in between
+#[utoipa::path(
+    get, path = "/events", tag = "files",
+    params(("Last-Event-ID" = Option<i64>, Header, description = "Id of the last received event")),
+    responses(
+        (status = 200, description = "Stream of changes, every event carries a change as json", content_type = "text/event-stream", body = Change),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn stream_changes(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let last_event_id = match headers.get("last-event-id") {
+        Some(value) => Some(value.to_str().ok().and_then(|v| v.parse::<i64>().ok())
+            .ok_or(ApiError::BadRequest("Last-Event-ID is not a change id"))?),
+        None => None,
+    };
+
+    // subscribe before replaying, so nothing falls between the two
+    let receiver = appstate.events.subscribe();
+    let head = appstate.files.last_change_id(user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch changes from db", e))?;
+    let subscription = Subscription {
+        receiver,
+        appstate,
+        user_uuid: user.uuid,
+        head,
+        cursor: last_event_id.unwrap_or(head),
+        pending: VecDeque::new(),
+        replaying: last_event_id.is_some(),
+        replayed: HashSet::new(),
+    };
+
+    let stream = futures_util::stream::unfold(subscription, |mut subscription| async move {
+        let change = subscription.next().await?;
+        let event = Event::default().id(change.id.to_string()).json_data(&change)
+            .unwrap_or_else(|_| Event::default().comment("unserializable change"));
+        Some((Ok(event), subscription))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Changes of a single user, from the journal while catching up and from the bus afterwards
+struct Subscription {
+    appstate: Arc<Appstate>,
+    user_uuid: Uuid,
+    receiver: broadcast::Receiver<BusEvent>,
+    /// Latest change when subscribing, older ones only come from the journal
+    head: i64,
+    /// Highest id sent so far
+    cursor: i64,
+    /// Read from the journal but not sent yet
+    pending: VecDeque<Change>,
+    replaying: bool,
+    /// Sent from the journal after `head`, they may still arrive from the bus
+    replayed: HashSet<i64>,
+}
+
+impl Subscription {
+    /// None ends the stream, on shutdown or if the journal can't be read
+    async fn next(&mut self) -> Option<Change> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                self.cursor = self.cursor.max(change.id);
+                if change.id > self.head {
+                    self.replayed.insert(change.id);
+                }
+                return Some(change)
+            }
+
+            if self.replaying {
+                match self.appstate.files.changes(self.user_uuid, self.cursor, REPLAY_BATCH).await {
+                    Ok(changes) if !changes.is_empty() => self.pending.extend(changes),
+                    Ok(_) => self.replaying = false,
+                    Err(e) => {
+                        eprintln!("Failed to replay changes of {}: {}", self.user_uuid, e);
+                        return None
+                    },
+                }
+                continue
+            }
+
+            let received = tokio::select! {
+                received = self.receiver.recv() => received,
+                _ = self.appstate.shutdown.drained() => return None,
+            };
+            match received {
+                // published late but journaled before subscribing, the replay has them
+                Ok(BusEvent::Change(change)) if change.id <= self.head => {},
+                Ok(BusEvent::Change(change)) if change.owner_uuid == self.user_uuid && !self.replayed.remove(&change.id) => {
+                    self.cursor = self.cursor.max(change.id);
+                    return Some(change)
+                },
+                Ok(BusEvent::Change(_)) => {},
+                // the bus dropped changes, the journal has all of them
+                Ok(BusEvent::Gap) | Err(RecvError::Lagged(_)) => self.replaying = true,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use axum::http::header;
+use axum::response::{Html, IntoResponse};
+use axum::{Extension, Json};
+use std::sync::Arc;
+use utoipa::openapi::OpenApi;
+
+/// Returns the OpenAPI document of all routes
+#[axum_macros::debug_handler]
+pub async fn openapi_json(
+    Extension(spec): Extension<Arc<OpenApi>>,
+) -> Json<OpenApi> {
+    Json(spec.as_ref().clone())
+}
+
+/// Interactive docs rendering `/v1/openapi.json`
+#[axum_macros::debug_handler]
+pub async fn docs_ui() -> Html<&'static str> {
+    Html(include_str!("docs.html"))
+}
+
+/// Swagger UI is bundled with the binary, so the docs work offline and don't trust a cdn \
+/// A new version comes with a new binary, so the assets are only cached for a day
+const ASSET_CACHE_CONTROL: &str = "public, max-age=86400";
+
+/// Stylesheet of swagger-ui-dist 5.17.14
+#[axum_macros::debug_handler]
+pub async fn docs_css() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/css; charset=utf-8"), (header::CACHE_CONTROL, ASSET_CACHE_CONTROL)],
+        include_str!("swagger-ui/swagger-ui.css"),
+    )
+}
+
+/// Script of swagger-ui-dist 5.17.14
+#[axum_macros::debug_handler]
+pub async fn docs_js() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/javascript; charset=utf-8"), (header::CACHE_CONTROL, ASSET_CACHE_CONTROL)],
+        include_str!("swagger-ui/swagger-ui-bundle.js"),
+    )
+}
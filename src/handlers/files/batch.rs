@@ -0,0 +1,108 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::batch::{self, ItemResult, Operation, BACKGROUND_THRESHOLD, MAX_OPERATIONS};
+use crate::models::user::AuthUser;
+use crate::util::ip::ClientInfo;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Batch)]
+pub struct Body {
+    /// Applied in order, each one on its own
+    operations: Vec<Operation>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = BatchStatus)]
+pub struct Response {
+    /// Set if the batch runs in the background
+    batch_id: Option<Uuid>,
+    total: usize,
+    completed: usize,
+    finished: bool,
+    /// Results of the completed operations, in order
+    results: Vec<ItemResult>,
+}
+
+/// Applies operations to many files, a failed operation doesn't stop the others \
+/// Consecutive renames, moves and tag changes are written together, deletes and copies on their own \
+/// Batches of more than 100 operations run on the job queue and are polled with the returned `batch_id`
+#[utoipa::path(
+    post, path = "/batch", tag = "files",
+    request_body = Body,
+    responses(
+        (status = 200, description = "Result of every operation", body = Response),
+        (status = 202, description = "Batch started in the background", body = Response),
+        (status = 400, description = "No or too many operations", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn run_batch(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    client: ClientInfo,
+    Json(body): Json<Body>,
+) -> Result<(StatusCode, Json<Response>), ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let total = body.operations.len();
+    if total == 0 {
+        return Err(ApiError::BadRequest("Batch has no operations"))
+    }
+    if total > MAX_OPERATIONS {
+        return Err(ApiError::BadRequest("Batch has more than 10000 operations"))
+    }
+
+    if total <= BACKGROUND_THRESHOLD {
+        let results = batch::execute(0, body.operations, &user, &client, &appstate).await;
+        return Ok((StatusCode::OK, Json(Response { batch_id: None, total, completed: total, finished: true, results })))
+    }
+
+    let batch_id = batch::start(body.operations, &user, &client, &appstate).await
+        .map_err(|e| ApiError::internal("Failed to start batch", e))?;
+
+    Ok((StatusCode::ACCEPTED, Json(Response { batch_id: Some(batch_id), total, completed: 0, finished: false, results: vec![] })))
+}
+
+/// Progress of a background batch, kept for an hour after it finished
+#[utoipa::path(
+    get, path = "/batch/{batch_id}", tag = "files",
+    params(("batch_id" = Uuid, Path, description = "Id returned when starting the batch")),
+    responses(
+        (status = 200, description = "Progress and results so far", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "Batch does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn batch_status(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path(batch_id): Path<Uuid>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let batch = appstate.batches.get(batch_id, user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to get batch", e))?
+        .ok_or(ApiError::NotFound("Batch does not exist"))?;
+
+    Ok(Json(Response {
+        batch_id: Some(batch_id),
+        total: batch.total,
+        completed: batch.results.len(),
+        finished: batch.finished,
+        results: batch.results,
+    }))
+}
@@ -1,37 +1,72 @@
+use crate::error::{ApiError, ErrorBody};
 use crate::models::appstate::AppstateWrapper;
-use crate::models::file::File;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::change;
 use crate::models::user::AuthUser;
+use crate::repository::file::FileUpdate;
+use crate::util::conditional::{Precondition, Validators};
+use crate::util::ip::ClientInfo;
 use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::Extension;
 use uuid::Uuid;
 
+/// Deletes the file, `If-Match` with the ETag of the download prevents deleting a file another client replaced
+#[utoipa::path(
+    delete, path = "/delete/{ref_id}", tag = "files",
+    params(("ref_id" = Uuid, Path, description = "Reference of the file")),
+    responses(
+        (status = 204, description = "File deleted"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "File does not exist", body = ErrorBody),
+        (status = 412, description = "`If-Match` or `If-Unmodified-Since` didn't match", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
 pub async fn delete_file(
     State(appstate): State<AppstateWrapper>,
     auth_user: Extension<AuthUser>,
     Path(ref_id): Path<Uuid>,
-) -> Result<StatusCode, (StatusCode, &'static str)> {
+    client: ClientInfo,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
     let appstate = appstate.0;
     let user = auth_user.0.0;
     // get file data from db
-    let file = match File::get_from_db(ref_id.clone(), user.uuid.clone(), &appstate).await {
-        Ok(o) => o,
-        Err(_) => return Err((StatusCode::NOT_FOUND, "Failed to find file in db")),
+    let mut file = match appstate.files.get(ref_id, user.uuid).await {
+        Ok(Some(file)) => file,
+        Ok(None) => {
+            let entry = AuditEntry::new(AuditAction::Delete, AuditOutcome::Failure).actor(&user).target(ref_id).client(&client);
+            audit::record(&appstate, entry.detail("file not found")).await;
+            return Err(ApiError::NotFound("Failed to find file in db"))
+        },
+        Err(e) => return Err(ApiError::internal("Failed to fetch file from db", e)),
     };
 
+    if Validators::requested(&headers) {
+        let sha256 = file.ensure_sha256(&appstate).await
+            .map_err(|e| ApiError::internal("Failed to hash file", e))?;
+        if Validators::new(&sha256, file.timestamp).evaluate(&headers, false) != Precondition::Proceed {
+            return Err(ApiError::PreconditionFailed("File does not match the precondition"))
+        }
+    }
+
     // delete file from disk and db
     match file.delete_from_disk().await {
         Ok(_) => {},
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete from disk")),
+        Err(e) => return Err(ApiError::internal("Failed to delete from disk", e)),
     }
 
-    match file.delete_from_db(&appstate).await {
+    match change::apply(&appstate, user.uuid, &[FileUpdate::Delete { reference_uuid: file.reference_uuid }]).await {
         Ok(_) => {},
         Err(_) => {
             eprintln!("FATAL: DANGLING ENTRY IN `file`, file: {:?}", file);
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete from db"))
+            return Err(ApiError::Internal("Failed to delete from db"))
         }
     }
+    let entry = AuditEntry::new(AuditAction::Delete, AuditOutcome::Success).actor(&user).target(file.reference_uuid).client(&client);
+    audit::record(&appstate, entry.detail(file.filename)).await;
 
 
     Ok(StatusCode::NO_CONTENT)
@@ -1,6 +1,10 @@
+use crate::error::{ApiError, ErrorBody};
 use crate::models::appstate::AppstateWrapper;
-use crate::models::file::File;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::file::ScanStatus;
 use crate::models::user::AuthUser;
+use crate::util::conditional::{Precondition, Validators};
+use crate::util::ip::ClientInfo;
 use axum::extract::{Path, Request, State};
 use axum::http::{header, HeaderValue, StatusCode};
 use axum::Extension;
@@ -10,39 +14,119 @@ use tower::ServiceExt;
 use tower_http::services::ServeFile;
 use uuid::Uuid;
 
+/// Sends the file with its sha256 as strong ETag, infected files are never sent and unscanned ones depending on `scan.serve_unscanned` \
+/// Supports `If-None-Match`, `If-Modified-Since`, `If-Match`, `If-Unmodified-Since`, `Range` and `If-Range`
+#[utoipa::path(
+    get, path = "/download/{ref_id}", tag = "files",
+    params(("ref_id" = Uuid, Path, description = "Reference of the file")),
+    responses(
+        (status = 200, description = "File content as attachment", content_type = "application/octet-stream"),
+        (status = 206, description = "Requested range of the file", content_type = "application/octet-stream"),
+        (status = 304, description = "The cached copy is current"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 403, description = "File is infected and quarantined", body = ErrorBody),
+        (status = 404, description = "File does not exist", body = ErrorBody),
+        (status = 409, description = "File wasn't found clean yet", body = ErrorBody),
+        (status = 412, description = "`If-Match` or `If-Unmodified-Since` didn't match", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
 #[axum_macros::debug_handler]
 pub async fn serve_file(
     State(appstate): State<AppstateWrapper>,
     auth_user: Extension<AuthUser>,
     Path(ref_id): Path<Uuid>,
+    client: ClientInfo,
     req: Request,
-) -> Result<axum_core::response::Response, (StatusCode, &'static str)> {
+) -> Result<axum_core::response::Response, ApiError> {
     let user = auth_user.0.0;
     let appstate = appstate.0;
 
     // check that user owns file
-    let file = match File::get_from_db(ref_id, user.uuid.clone(), &appstate).await {
-        Ok(row) => row,
-        Err(_) => return Err((StatusCode::NOT_FOUND, "Failed to find in db"))
+    let mut file = match appstate.files.get(ref_id, user.uuid).await {
+        Ok(Some(file)) => file,
+        Ok(None) => {
+            let entry = AuditEntry::new(AuditAction::Download, AuditOutcome::Failure).actor(&user).target(ref_id).client(&client);
+            audit::record(&appstate, entry.detail("file not found")).await;
+            return Err(ApiError::NotFound("Failed to find in db"))
+        },
+        Err(e) => return Err(ApiError::internal("Failed to fetch file from db", e))
     };
 
+    match file.scan_status {
+        ScanStatus::Clean => {},
+        ScanStatus::Infected => return Err(ApiError::Forbidden("File is quarantined")),
+        _ if appstate.config.scan.as_ref().is_some_and(|scan| !scan.serve_unscanned) => {
+            return Err(ApiError::Conflict("File has not been scanned yet"))
+        },
+        _ => {},
+    }
+
     // check again that the file exists
     let path = StdPath::new(&file.absolute_path);
     if !path.exists() {
-        return Err((StatusCode::NOT_FOUND, "Failed to find file on disk"))
+        return Err(ApiError::NotFound("Failed to find file on disk"))
+    }
+
+    let sha256 = file.ensure_sha256(&appstate).await
+        .map_err(|e| ApiError::internal("Failed to hash file", e))?;
+    let validators = Validators::new(&sha256, file.timestamp);
+    let cache_control = HeaderValue::from_str(&appstate.config.cache.download)
+        .map_err(|e| ApiError::internal("Invalid cache.download", e))?;
+
+    match validators.evaluate(req.headers(), true) {
+        Precondition::Proceed => {},
+        Precondition::NotModified => return Ok((
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, validators.etag()),
+                (header::LAST_MODIFIED, validators.last_modified()),
+                (header::CACHE_CONTROL, cache_control),
+            ],
+        ).into_response()),
+        Precondition::Failed => return Err(ApiError::PreconditionFailed("File does not match the precondition")),
+    }
+
+    // ServeFile only knows the modification time on disk, so it must not evaluate the preconditions again
+    let (mut parts, body) = req.into_parts();
+    if !validators.range_applies(&parts.headers) {
+        parts.headers.remove(header::RANGE);
+    }
+    for name in [header::IF_MATCH, header::IF_NONE_MATCH, header::IF_MODIFIED_SINCE, header::IF_UNMODIFIED_SINCE, header::IF_RANGE] {
+        parts.headers.remove(name);
     }
+    let req = Request::from_parts(parts, body);
 
     // construct ServeFile and response
     let service = ServeFile::new(&file.absolute_path);
 
-    let mut response = service.oneshot(req).await.map_err(|_| (StatusCode::NOT_FOUND, "Failed to construct file service"))?;
+    let mut response = service.oneshot(req).await.map_err(|_| ApiError::NotFound("Failed to construct file service"))?;
+
+    // ranges only send a part, so count what's actually served
+    if response.status().is_success() {
+        let length = response.headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0);
+        appstate.metrics.bytes_downloaded.inc_by(length);
+
+        let entry = AuditEntry::new(AuditAction::Download, AuditOutcome::Success).actor(&user).target(file.reference_uuid).client(&client);
+        audit::record(&appstate, entry.detail(file.filename.clone())).await;
+    }
 
     // set custom headers for original filename
     let header_value = format!("attachment; filename=\"{}\"", &file.filename);
     match HeaderValue::from_str(&header_value) {
         Ok(o) => response.headers_mut().insert(header::CONTENT_DISPOSITION, o),
-        _ => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to construct response headers")),
+        _ => return Err(ApiError::Internal("Failed to construct response headers")),
     };
 
+    let headers = response.headers_mut();
+    headers.insert(header::ETAG, validators.etag());
+    headers.insert(header::LAST_MODIFIED, validators.last_modified());
+    headers.insert(header::CACHE_CONTROL, cache_control);
+
     Ok(response.into_response())
 }
\ No newline at end of file
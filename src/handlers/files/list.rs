@@ -0,0 +1,91 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::file::{File, ScanStatus};
+use crate::models::user::AuthUser;
+use crate::repository::file::FileFilter;
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    /// only files with this tag
+    tag: Option<String>,
+    /// only starred or only not starred files
+    starred: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = FileInfo)]
+pub struct Response {
+    reference_uuid: Uuid,
+    filename: String,
+    mime_type: String,
+    /// Size in bytes
+    size: usize,
+    starred: bool,
+    tags: Vec<String>,
+    metadata: BTreeMap<String, String>,
+    /// Hex sha256 of the content, also sent as ETag by the download
+    sha256: Option<String>,
+    /// Infected files are quarantined and can only be deleted
+    scan_status: ScanStatus,
+    /// Unix timestamp of the upload
+    timestamp: usize,
+}
+
+impl From<File> for Response {
+    fn from(file: File) -> Self {
+        Self {
+            reference_uuid: file.reference_uuid,
+            filename: file.filename,
+            mime_type: file.mime_type,
+            size: file.size,
+            starred: file.starred,
+            tags: file.tags,
+            metadata: file.metadata,
+            sha256: file.sha256,
+            scan_status: file.scan_status,
+            timestamp: file.timestamp,
+        }
+    }
+}
+
+/// Lists the files of the logged-in user, newest first
+#[utoipa::path(
+    get, path = "/list", tag = "files",
+    params(Params),
+    responses(
+        (status = 200, description = "Matching files", body = Vec<Response>),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn list_files(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Query(params): Query<Params>,
+) -> Result<Json<Vec<Response>>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let filter = FileFilter {
+        tag: params.tag.map(|tag| tag.trim().to_lowercase()),
+        starred: params.starred,
+    };
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let files = appstate.files.list_filtered(user.uuid, &filter, limit as usize, offset as usize).await
+        .map_err(|e| ApiError::internal("Failed to fetch files from db", e))?;
+
+    Ok(Json(files.into_iter().map(Response::from).collect()))
+}
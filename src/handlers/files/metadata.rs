@@ -0,0 +1,109 @@
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::change;
+use crate::models::file::MAX_METADATA;
+use crate::models::user::AuthUser;
+use crate::repository::file::FileUpdate;
+use crate::util::validation;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = SetMetadata)]
+pub struct Body {
+    value: String,
+}
+
+/// Sets a metadata entry of a file, replacing the value of an existing key
+#[utoipa::path(
+    put, path = "/metadata/{ref_id}/{key}", tag = "files",
+    params(
+        ("ref_id" = Uuid, Path, description = "Reference of the file"),
+        ("key" = String, Path, description = "1-64 chars of a-z, A-Z, 0-9, ., _, -"),
+    ),
+    request_body = Body,
+    responses(
+        (status = 200, description = "Entry set"),
+        (status = 400, description = "Validation failed or too many entries", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "File does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn set_metadata(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path((ref_id, key)): Path<(Uuid, String)>,
+    Json(body): Json<Body>,
+) -> Result<StatusCode, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let mut errors = Vec::new();
+    if let (false, reason) = validation::metadata_key(&key) {
+        errors.push(FieldError::new("key", reason));
+    }
+    if let (false, reason) = validation::metadata_value(&body.value) {
+        errors.push(FieldError::new("value", reason));
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors))
+    }
+
+    let file = match appstate.files.get(ref_id, user.uuid).await {
+        Ok(Some(file)) => file,
+        Ok(None) => return Err(ApiError::NotFound("File does not exist")),
+        Err(e) => return Err(ApiError::internal("Failed to fetch file from db", e)),
+    };
+    if !file.metadata.contains_key(&key) && file.metadata.len() >= MAX_METADATA {
+        return Err(ApiError::BadRequest("File has the maximum number of metadata entries"))
+    }
+
+    let update = FileUpdate::Metadata { reference_uuid: ref_id, key, value: Some(body.value) };
+    let changes = change::apply(&appstate, user.uuid, &[update]).await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+    if changes[0].is_none() {
+        return Err(ApiError::NotFound("File does not exist"))
+    }
+
+    Ok(StatusCode::OK)
+}
+
+/// Removes a metadata entry of a file, removing a missing key is fine
+#[utoipa::path(
+    delete, path = "/metadata/{ref_id}/{key}", tag = "files",
+    params(
+        ("ref_id" = Uuid, Path, description = "Reference of the file"),
+        ("key" = String, Path, description = "Key of the entry"),
+    ),
+    responses(
+        (status = 204, description = "Entry removed"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "File does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn remove_metadata(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path((ref_id, key)): Path<(Uuid, String)>,
+) -> Result<StatusCode, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let changes = change::apply(&appstate, user.uuid, &[FileUpdate::Metadata { reference_uuid: ref_id, key, value: None }]).await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+    if changes[0].is_none() {
+        return Err(ApiError::NotFound("File does not exist"))
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,51 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::handlers::files::list::Response;
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::AuthUser;
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    /// matches filenames, tags, types like `image` or `application/pdf` and the text of text and pdf files
+    q: String,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Searches the files of the logged-in user, best matches first
+#[utoipa::path(
+    get, path = "/search", tag = "files",
+    params(Params),
+    responses(
+        (status = 200, description = "Matching files", body = Vec<Response>),
+        (status = 400, description = "Empty query", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn search_files(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Query(params): Query<Params>,
+) -> Result<Json<Vec<Response>>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let query = params.q.trim();
+    if query.is_empty() {
+        return Err(ApiError::BadRequest("Search query is empty"))
+    }
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let files = appstate.files.search(user.uuid, query, limit as usize, offset as usize).await
+        .map_err(|e| ApiError::internal("Failed to search files", e))?;
+
+    Ok(Json(files.into_iter().map(Response::from).collect()))
+}
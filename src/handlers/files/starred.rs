@@ -0,0 +1,49 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::change;
+use crate::models::user::AuthUser;
+use crate::repository::file::FileUpdate;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = SetStarred)]
+pub struct Body {
+    starred: bool,
+}
+
+/// Stars or unstars a file
+#[utoipa::path(
+    put, path = "/starred/{ref_id}", tag = "files",
+    params(("ref_id" = Uuid, Path, description = "Reference of the file")),
+    request_body = Body,
+    responses(
+        (status = 200, description = "File starred or unstarred"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "File does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn set_starred(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path(ref_id): Path<Uuid>,
+    Json(body): Json<Body>,
+) -> Result<StatusCode, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let changes = change::apply(&appstate, user.uuid, &[FileUpdate::Starred { reference_uuid: ref_id, starred: body.starred }]).await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+    if changes[0].is_none() {
+        return Err(ApiError::NotFound("File does not exist"))
+    }
+
+    Ok(StatusCode::OK)
+}
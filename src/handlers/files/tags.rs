@@ -0,0 +1,185 @@
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::change;
+use crate::models::file::{normalize_tags, MAX_TAGS};
+use crate::models::user::AuthUser;
+use crate::repository::file::FileUpdate;
+use crate::util::validation;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Most files a single bulk request can tag
+const MAX_BULK_FILES: usize = 1000;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = SetTags)]
+pub struct Body {
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Tags)]
+pub struct Response {
+    /// Lowercase and sorted, as they're stored
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = BulkTags)]
+pub struct BulkBody {
+    reference_uuids: Vec<Uuid>,
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = BulkTagsResult)]
+pub struct BulkResponse {
+    updated: Vec<Uuid>,
+    /// Files that don't exist or would end up with too many tags
+    skipped: Vec<Uuid>,
+}
+
+/// Normalizes tags and reports every invalid one at once
+fn validate_tags(tags: &[String], field: &'static str) -> Result<Vec<String>, ApiError> {
+    let tags = normalize_tags(tags);
+    let errors: Vec<FieldError> = tags.iter()
+        .filter_map(|tag| match validation::tag(tag) {
+            (false, reason) => Some(FieldError::new(field, format!("{}: {}", tag, reason))),
+            _ => None,
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors))
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(ApiError::Validation(vec![FieldError::new(field, format!("More than {} tags", MAX_TAGS))]))
+    }
+    Ok(tags)
+}
+
+/// Replaces all tags of a file
+#[utoipa::path(
+    put, path = "/tags/{ref_id}", tag = "files",
+    params(("ref_id" = Uuid, Path, description = "Reference of the file")),
+    request_body = Body,
+    responses(
+        (status = 200, description = "Tags replaced", body = Response),
+        (status = 400, description = "Validation failed", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "File does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn set_tags(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path(ref_id): Path<Uuid>,
+    Json(body): Json<Body>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let tags = validate_tags(&body.tags, "tags")?;
+
+    let changes = change::apply(&appstate, user.uuid, &[FileUpdate::ReplaceTags { reference_uuid: ref_id, tags: tags.clone() }]).await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+    if changes[0].is_none() {
+        return Err(ApiError::NotFound("File does not exist"))
+    }
+
+    Ok(Json(Response { tags }))
+}
+
+/// Removes a single tag of a file, removing a tag the file doesn't have is fine
+#[utoipa::path(
+    delete, path = "/tags/{ref_id}/{tag}", tag = "files",
+    params(
+        ("ref_id" = Uuid, Path, description = "Reference of the file"),
+        ("tag" = String, Path, description = "Tag to remove"),
+    ),
+    responses(
+        (status = 204, description = "Tag removed"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "File does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn remove_tag(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path((ref_id, tag)): Path<(Uuid, String)>,
+) -> Result<StatusCode, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    // removing never exceeds the limit
+    let update = FileUpdate::Tags { reference_uuid: ref_id, add: vec![], remove: vec![tag.trim().to_lowercase()], max_tags: usize::MAX };
+    let changes = change::apply(&appstate, user.uuid, &[update]).await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+    if changes[0].is_none() {
+        return Err(ApiError::NotFound("File does not exist"))
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Adds and removes tags of many files in one request, removals win over additions
+#[utoipa::path(
+    post, path = "/tags", tag = "files",
+    request_body = BulkBody,
+    responses(
+        (status = 200, description = "Tags updated", body = BulkResponse),
+        (status = 400, description = "Validation failed", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn bulk_tag(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Json(body): Json<BulkBody>,
+) -> Result<Json<BulkResponse>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    if body.reference_uuids.is_empty() || body.reference_uuids.len() > MAX_BULK_FILES {
+        return Err(ApiError::BadRequest("Between 1 and 1000 files can be tagged at once"))
+    }
+    let add = validate_tags(&body.add, "add")?;
+    let remove = normalize_tags(&body.remove);
+
+    let mut reference_uuids = Vec::with_capacity(body.reference_uuids.len());
+    for uuid in body.reference_uuids {
+        if !reference_uuids.contains(&uuid) {
+            reference_uuids.push(uuid);
+        }
+    }
+    let updates: Vec<FileUpdate> = reference_uuids.iter()
+        .map(|uuid| FileUpdate::Tags { reference_uuid: *uuid, add: add.clone(), remove: remove.clone(), max_tags: MAX_TAGS })
+        .collect();
+    let changes = change::apply(&appstate, user.uuid, &updates).await
+        .map_err(|e| ApiError::internal("Failed to write change to db", e))?;
+
+    let (mut updated, mut skipped) = (Vec::new(), Vec::new());
+    for (uuid, change) in reference_uuids.into_iter().zip(changes) {
+        match change {
+            Some(_) => updated.push(uuid),
+            None => skipped.push(uuid),
+        }
+    }
+
+    Ok(Json(BulkResponse { updated, skipped }))
+}
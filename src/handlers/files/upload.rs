@@ -1,41 +1,81 @@
-use crate::models::appstate::AppstateWrapper;
-use crate::models::file::File;
+use crate::config::ScanMode;
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::jobs::scan::ScanFile;
+use crate::models::appstate::{Appstate, AppstateWrapper};
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::change::{self, Change};
+use crate::models::file::{File, PartialFile, ScanStatus};
 use crate::models::user::AuthUser;
+use crate::repository::file::FileUpdate;
+use crate::util::ip::ClientInfo;
+use crate::util::metrics::ActiveUpload;
+use crate::util::mime;
+use crate::util::validation;
 use axum::extract::{Multipart, State};
 use axum::http::StatusCode;
 use axum::{Extension, Json};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = UploadedFile)]
 pub struct Response {
     reference_uuid: Uuid,
-    filename: String
+    filename: String,
+    /// `pending` until the background scan finished in async mode
+    scan_status: ScanStatus,
 }
 
+#[utoipa::path(
+    post, path = "/upload", tag = "files",
+    request_body(content_type = "multipart/form-data", description = "Every field named `file` is stored as a file"),
+    responses(
+        (status = 201, description = "Files stored", body = Vec<Response>),
+        (status = 400, description = "Malformed multipart body or invalid filename", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 413, description = "Quota or maximum file size exceeded", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+        (status = 503, description = "Aborted by a shutdown", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
 #[axum_macros::debug_handler]
 pub async fn stream_upload(
     State(appstate): State<AppstateWrapper>,
     auth_user: Extension<AuthUser>,
+    client: ClientInfo,
     mut multipart: Multipart
-) -> Result<(StatusCode, Json<Vec<Response>>), (StatusCode, &'static str)> {
+) -> Result<(StatusCode, Json<Vec<Response>>), ApiError> {
     let appstate = appstate.0;
     let user = auth_user.0.0;
 
     let mut response: Vec<Response> = Vec::new();
+    let _active = ActiveUpload::start(&appstate.metrics.active_uploads);
 
-    while let Ok(Some(mut field)) = multipart.next_field().await {
+    // current storage usage to enforce the quota
+    let mut usage = appstate.files.usage(user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch storage usage", e))?
+        .bytes;
+
+    while let Some(mut field) = multipart.next_field().await
+        .map_err(|e| ApiError::Rejection(e.status(), e.body_text()))?
+    {
         let field_name = match &field.name() {
             Some(x) => x.to_string(),
-            _ => { return Err((StatusCode::BAD_REQUEST, "Failed to get field name"))}
+            _ => { return Err(ApiError::BadRequest("Failed to get field name"))}
         };
         // continue on everything not marked as a file
         if &field_name.to_lowercase() != "file" { continue; }
 
         let filename = match &field.file_name() {
             Some(x) => x.to_string(),
-            _ => { return Err((StatusCode::BAD_REQUEST, "Failed to get filename"))}
+            _ => { return Err(ApiError::BadRequest("Failed to get filename"))}
         };
+        if let (false, reason) = validation::filename(&filename) {
+            return Err(ApiError::Validation(vec![FieldError::new("file", reason)]))
+        }
 
         // mutable to later update file size
         let mut file = File::construct(
@@ -44,39 +84,88 @@ pub async fn stream_upload(
             &user,
             0,
             &appstate
-        ).await.ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Failed to construct File"))?;
+        ).await.ok_or(ApiError::Internal("Failed to construct File"))?;
+        file.mime_type = mime::detect(&file.filename, field.content_type());
+
+        // removes the partial file unless the upload completes
+        let partial = PartialFile::new(&file.absolute_path);
+        let mut hasher = Sha256::new();
 
         // write to file in chunks
-        while let Some(chunk) = field
-            .chunk()
-            .await
-            .map_err(|_| (StatusCode::BAD_REQUEST, "Failed to get next chunk"))?
-        {
-            match file.write_chunk(chunk.as_ref()).await {
-                Ok(_) => { file.size += chunk.len() },
-                Err(_) => {
-                    match file.delete_from_disk().await {
-                        Ok(_) => {},
-                        Err(e) => {
-                            eprintln!("FATAL: DANGLING FILE: {:?}; ERROR: {}", &file, e);
-                        }
-                    }
-                    return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to write file to disk"))
-                }
-            }// end match write_chunk
-        }// end while let chunk
-
-        // write file to db
-        match file.write_to_db(&appstate).await {
-            Ok(_) => {},
-            Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to write to db")),
+        loop {
+            let chunk = tokio::select! {
+                chunk = field.chunk() => chunk.map_err(|e| ApiError::Rejection(e.status(), e.body_text()))?,
+                _ = appstate.shutdown.aborted() => return Err(ApiError::Unavailable("Server is shutting down")),
+            };
+            let Some(chunk) = chunk else { break };
+
+            // check quota before writing
+            if user.quota.is_some_and(|quota| usage + file.size + chunk.len() > quota) {
+                return Err(ApiError::PayloadTooLarge("Storage quota exceeded"))
+            }
+
+            if file.size + chunk.len() > appstate.config.storage.max_file_size {
+                return Err(ApiError::PayloadTooLarge("File exceeds maximum size"))
+            }
+
+            if let Err(e) = file.write_chunk(chunk.as_ref()).await {
+                return Err(ApiError::internal("Failed to write file to disk", e))
+            }
+            hasher.update(&chunk);
+            file.size += chunk.len();
+            appstate.metrics.bytes_uploaded.inc_by(chunk.len() as u64);
+        }// end loop chunk
+        file.sha256 = Some(hex::encode(hasher.finalize()));
+        if appstate.scanner.is_some() {
+            file.scan_status = ScanStatus::Pending;
         }
 
+        // write file to db, the change is published after a sync scan
+        let created = match appstate.files.apply(user.uuid, &[FileUpdate::Insert(Box::new(file.clone()))]).await {
+            Ok(o) => o,
+            Err(e) => return Err(ApiError::internal("Failed to write to db", e)),
+        };
+        partial.keep();
+        let entry = AuditEntry::new(AuditAction::Upload, AuditOutcome::Success).actor(&user).target(file.reference_uuid).client(&client);
+        audit::record(&appstate, entry.detail(file.filename.clone())).await;
+
+        let scanned = match appstate.scanner {
+            Some(_) => scan(&mut file, &appstate).await,
+            None => None,
+        };
+        // after a sync scan, so a quarantined file isn't announced as a normal upload without its verdict
+        for change in created.into_iter().chain([scanned]).flatten() {
+            change::publish(&appstate, &change).await;
+        }
+
+        // the file is stored even if it can't be searched by its content, it's indexed once it's found clean
+        if matches!(file.scan_status, ScanStatus::Unscanned | ScanStatus::Clean) {
+            if let Err(e) = file.index(&appstate).await {
+                eprintln!("Failed to index {}: {}", file.reference_uuid, e);
+            }
+        }
+
+        usage += file.size;
 
         // add to response
-        response.push( Response { reference_uuid: file.reference_uuid, filename: file.filename });
+        response.push( Response { reference_uuid: file.reference_uuid, filename: file.filename, scan_status: file.scan_status });
 
     }// end while let field
 
     Ok((StatusCode::CREATED, Json(response)))
-}
\ No newline at end of file
+}
+
+/// Scans right away or queues the scan depending on `scan.mode`, failed scans are retried in the background \
+/// Returns the change of a sync verdict
+async fn scan(file: &mut File, appstate: &Appstate) -> Option<Change> {
+    if appstate.config.scan.as_ref().is_some_and(|scan| scan.mode == ScanMode::Sync) {
+        match file.scan(appstate).await {
+            Ok(o) => return o,
+            Err(e) => eprintln!("Failed to scan {}: {}", file.reference_uuid, e),
+        }
+    }
+    if let Err(e) = ScanFile::enqueue(file.reference_uuid, file.owner_uuid, appstate).await {
+        eprintln!("Failed to queue the scan of {}: {}", file.reference_uuid, e);
+    }
+    None
+}
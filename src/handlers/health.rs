@@ -0,0 +1,51 @@
+use crate::models::appstate::AppstateWrapper;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Max time a single readiness check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+pub struct Response {
+    ready: bool,
+    /// Set once a shutdown signal was received
+    draining: bool,
+    database: bool,
+    storage: bool,
+}
+
+/// Liveness, only tells that the process is able to answer
+#[axum_macros::debug_handler]
+pub async fn healthz() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Readiness, checks the database and that files can be stored \
+/// Fails while draining so load balancers stop sending new requests
+#[axum_macros::debug_handler]
+pub async fn readyz(
+    State(appstate): State<AppstateWrapper>,
+) -> (StatusCode, Json<Response>) {
+    let appstate = appstate.0;
+
+    let database = tokio::time::timeout(CHECK_TIMEOUT, appstate.users.ping())
+        .await
+        .is_ok_and(|result| result.is_ok());
+
+    // write and remove a probe file
+    let probe = format!("{}/.readyz-{}", appstate.file_location, Uuid::new_v4());
+    let storage = tokio::time::timeout(CHECK_TIMEOUT, async {
+        tokio::fs::write(&probe, b"ok").await?;
+        tokio::fs::remove_file(&probe).await
+    }).await.is_ok_and(|result| result.is_ok());
+
+    let draining = appstate.shutdown.is_draining();
+    let ready = database && storage && !draining;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (status, Json(Response { ready, draining, database, storage }))
+}
@@ -0,0 +1,62 @@
+use crate::error::ApiError;
+use crate::models::appstate::AppstateWrapper;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{header, HeaderMap};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use std::time::Instant;
+
+/// Counts requests and their latency per route template and status \
+/// Unmatched paths share one label so scanners can't blow up the cardinality
+#[axum_macros::debug_middleware]
+pub async fn track_metrics(
+    Extension(appstate): Extension<AppstateWrapper>,
+    req: Request,
+    next: Next
+) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let route = req.extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    let metrics = &appstate.metrics;
+    metrics.http_requests.with_label_values(&labels).inc();
+    metrics.http_request_duration.with_label_values(&labels).observe(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Metrics in the prometheus text format, only for requests with the bearer token \
+/// Refused for everyone if no token is configured
+#[axum_macros::debug_handler]
+pub async fn metrics(
+    State(appstate): State<AppstateWrapper>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let appstate = appstate.0;
+
+    let provided = headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let authorized = match (&appstate.config.metrics.bearer_token, provided) {
+        (Some(token), Some(provided)) => !token.is_empty() && provided == token,
+        _ => false,
+    };
+    if !authorized {
+        return Err(ApiError::Unauthorized("Missing or wrong metrics token"))
+    }
+
+    appstate.metrics.observe_pool(&appstate.db_pool);
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        appstate.metrics.render()
+    ).into_response())
+}
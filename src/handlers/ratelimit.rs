@@ -0,0 +1,41 @@
+use crate::error::ApiError;
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::AuthUser;
+use crate::util::ip::ClientIp;
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Extension;
+use chrono::Utc;
+
+/// Limits requests per user (or per ip for anonymous requests) in fixed windows \
+/// Layer inside the `auth` middleware to count per user
+#[axum_macros::debug_middleware]
+pub async fn rate_limit(
+    Extension(appstate): Extension<AppstateWrapper>,
+    ClientIp(ip): ClientIp,
+    req: Request,
+    next: Next
+) -> Result<Response, ApiError> {
+    let appstate = appstate.0;
+
+    let key = match req.extensions().get::<AuthUser>() {
+        Some(auth_user) => format!("request:user:{}", auth_user.0.uuid),
+        None => format!("request:ip:{}", ip),
+    };
+
+    let limits = &appstate.config.rate_limit;
+    let now = Utc::now().timestamp();
+    let window_start = now - now % limits.request_window;
+
+    let hits = appstate.rate_limiter.hit(&key, window_start).await
+        .map_err(|e| ApiError::internal("Failed to count request", e))?;
+
+    if hits > limits.request_limit {
+        let retry_after = window_start + limits.request_window - now;
+        return Err(ApiError::TooManyRequests("Too many requests", retry_after))
+    }
+
+    let response = next.run(req).await;
+    Ok(response)
+}
@@ -0,0 +1,61 @@
+use crate::error::ApiError;
+use crate::util::request_id::{self, REQUEST_ID_HEADER};
+use axum::body::to_bytes;
+use axum::extract::Request;
+use axum::http::{header, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+/// Max size of a plain text rejection that gets wrapped into json
+const REJECTION_LIMIT: usize = 64 * 1024;
+
+/// Assigns every request an id that is echoed in the `x-request-id` header and error bodies \
+/// Also turns axum's plain text rejections (bad json, body too large, ...) into `ApiError`s \
+/// Has to be the outermost layer so every error is covered
+#[axum_macros::debug_middleware]
+pub async fn request_id(req: Request, next: Next) -> Response {
+    let id = request_id::from_header(
+        req.headers().get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok())
+    );
+
+    request_id::scope(id.clone(), async move {
+        let response = next.run(req).await;
+        let mut response = wrap_rejection(response).await;
+
+        if let Ok(value) = HeaderValue::from_str(&id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        response
+    }).await
+}
+
+/// Error responses that aren't json yet weren't produced by an `ApiError`
+async fn wrap_rejection(response: Response) -> Response {
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response
+    }
+
+    let is_json = response.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if is_json {
+        return response
+    }
+
+    let (parts, body) = response.into_parts();
+    let message = match to_bytes(body, REJECTION_LIMIT).await {
+        Ok(bytes) if !bytes.is_empty() => String::from_utf8_lossy(&bytes).into_owned(),
+        _ => parts.status.canonical_reason().unwrap_or("Error").to_string(),
+    };
+
+    let mut wrapped = ApiError::Rejection(status, message).into_response();
+    // keep headers like `Allow` or `Retry-After`
+    for (name, value) in parts.headers.iter() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            wrapped.headers_mut().insert(name, value.clone());
+        }
+    }
+    wrapped
+}
@@ -1,8 +1,9 @@
-use crate::models::appstate::AppstateWrapper;
-use crate::models::user::AuthUser;
+use crate::error::ApiError;
+use crate::models::appstate::{Appstate, AppstateWrapper};
+use crate::models::user::{AuthUser, Session, User};
 use crate::util::jwt::claims::Claims;
 use axum::extract::Request;
-use axum::http::StatusCode;
+use axum::http::HeaderMap;
 use axum::middleware::Next;
 use axum::response::Response;
 use axum::Extension;
@@ -15,39 +16,76 @@ pub async fn auth(
     Extension(appstate): Extension<AppstateWrapper>,
     mut req: Request,
     next: Next
-) -> Result<Response, StatusCode> {
-    let appstate = appstate.0;
+) -> Result<Response, ApiError> {
+    let (user, session) = authenticate(&appstate.0, req.headers()).await?;
+
+    // only routes layered with `auth_pending_reset` are open while a reset is forced
+    if user.password_reset {
+        return Err(ApiError::Forbidden("Password has to be changed"))
+    }
+
+    // pass user to next handler
+    req.extensions_mut().insert(AuthUser(user));
+    req.extensions_mut().insert(session);
+    let response = next.run(req).await;
+    Ok(response)
+}
+
+/// Same as `auth`, but lets users through whose password reset has been forced \
+/// Only meant for the route that changes the password
+#[axum_macros::debug_middleware]
+pub async fn auth_pending_reset(
+    Extension(appstate): Extension<AppstateWrapper>,
+    mut req: Request,
+    next: Next
+) -> Result<Response, ApiError> {
+    let (user, session) = authenticate(&appstate.0, req.headers()).await?;
+
+    req.extensions_mut().insert(AuthUser(user));
+    req.extensions_mut().insert(session);
+    let response = next.run(req).await;
+    Ok(response)
+}
+
+/// Resolves the user of the token cookie, disabled accounts are rejected
+async fn authenticate(appstate: &Appstate, headers: &HeaderMap) -> Result<(User, Session), ApiError> {
     // get private cookies
-    let headers = req.headers();
     let jar = PrivateCookieJar::from_headers(headers, appstate.cookie_secret.clone());
 
     let token = jar.get("token")
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or(ApiError::Unauthorized("Not logged in"))?;
 
 
     // decode token
-    let secret = &appstate.jwt_secret;
+    let secret = &appstate.config.auth.jwt_secret;
     let token_data = decode::<Claims>(
         token.value(),
         &DecodingKey::from_secret(secret.as_ref()),
         &Validation::default()
-    ).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    ).map_err(|e| {
+        appstate.metrics.auth_failure("token");
+        ApiError::from(e)
+    })?;
 
     // validate claims and get user model
     let claims = token_data.claims;
-    let user = match claims.validate_claims(&appstate.db_pool).await {
+    let user = match claims.validate_claims(appstate.users.as_ref()).await {
         Ok(o) => {
             match o {
                 Some(u) => u,
-                None => return Err(StatusCode::UNAUTHORIZED)
+                None => {
+                    appstate.metrics.auth_failure("token");
+                    return Err(ApiError::Unauthorized("Token is no longer valid"))
+                }
             }
         },
-        Err(_) => return Err( StatusCode::INTERNAL_SERVER_ERROR )
+        Err(e) => return Err(ApiError::internal("Failed to validate token", e))
     };
 
+    // disabled accounts can't do anything
+    if user.disabled {
+        return Err(ApiError::Forbidden("Account is disabled"))
+    }
 
-    // pass user to next handler
-    req.extensions_mut().insert(AuthUser(user));
-    let response = next.run(req).await;
-    Ok(response)
+    Ok((user, Session { auth_time: claims.auth_time }))
 }
\ No newline at end of file
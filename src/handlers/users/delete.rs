@@ -0,0 +1,100 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::jobs::account_deletion::PurgeAccount;
+use crate::models::appstate::AppstateWrapper;
+use crate::models::deletion::AccountDeletion;
+use crate::models::user::{AuthUser, Session};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::PrivateCookieJar;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = DeleteAccount)]
+pub struct Body {
+    /// Not needed right after a login, e.g. for single sign-on users without a password
+    password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = ScheduledDeletion)]
+pub struct Response {
+    /// Unix timestamp after which the account gets purged, until then it can be cancelled
+    scheduled_for: i64,
+}
+
+/// Schedules the deletion of the account with all of its files, dependent on password confirmation \
+/// Without a password the session has to be fresh from a login, the purge runs once the grace period is over
+#[utoipa::path(
+    delete, path = "/", tag = "users",
+    request_body = Body,
+    responses(
+        (status = 202, description = "Deletion scheduled, the token cookie is removed", body = Response),
+        (status = 401, description = "Wrong password or the login is too old", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn delete_account(
+    auth_user: Extension<AuthUser>,
+    Extension(session): Extension<Session>,
+    State(appstate): State<AppstateWrapper>,
+    jar: PrivateCookieJar,
+    Json(body): Json<Body>
+) -> Result<(StatusCode, PrivateCookieJar, Json<Response>), ApiError> {
+    let user = auth_user.0.0;
+    let appstate = appstate.0;
+
+    // confirm password, or a recent login for users that never got one
+    match body.password {
+        Some(password) => match user.compare_passwords(password) {
+            Ok(true) => {},
+            Ok(false) => return Err(ApiError::Unauthorized("Wrong Password")),
+            Err(e) => return Err(ApiError::internal("Failed to compare passwords", e))
+        },
+        None => if !session.is_recent(appstate.config.account.reauthentication_window) {
+            return Err(ApiError::Unauthorized("Log in again to confirm the deletion"))
+        },
+    }
+
+    let deletion = match AccountDeletion::schedule(&user, appstate.config.account.deletion_grace_period, &appstate).await {
+        Ok(o) => o,
+        Err(e) => return Err(ApiError::internal("Failed to schedule deletion", e))
+    };
+    // the hourly sweep queues it if this fails
+    if let Err(e) = PurgeAccount::enqueue(&deletion, &appstate).await {
+        eprintln!("Failed to queue purge of {}: {}", user.uuid, e);
+    }
+
+    // only drop the token of this client, others can still cancel during the grace period
+    let jar = jar.remove(Cookie::build("token").path("/"));
+
+    Ok((StatusCode::ACCEPTED, jar, Json(Response { scheduled_for: deletion.scheduled_for })))
+}
+
+/// Cancels a scheduled account deletion while its grace period isn't over
+#[utoipa::path(
+    delete, path = "/deletion", tag = "users",
+    responses(
+        (status = 204, description = "Deletion cancelled"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "No cancellable deletion scheduled", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn cancel_deletion(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth_user.0.0;
+    let appstate = appstate.0;
+
+    match appstate.deletions.cancel(user.uuid).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(ApiError::NotFound("No cancellable deletion scheduled")),
+        Err(e) => Err(ApiError::internal("Failed to cancel deletion", e))
+    }
+}
@@ -1,64 +1,121 @@
-use crate::models::appstate::AppstateWrapper;
-use crate::models::user::User;
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::{Appstate, AppstateWrapper};
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::util::ip::ClientInfo;
 use crate::util::jwt::claims::Claims;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::Json;
-use axum_extra::extract::cookie::{Cookie, SameSite};
 use axum_extra::extract::PrivateCookieJar;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Login)]
 pub struct Body {
     pub username: String,
     pub password: String,
 }
 
+/// Logs a user in, failed attempts lock the account and the ip with an exponential backoff
+#[utoipa::path(
+    post, path = "/login", tag = "users",
+    request_body = Body,
+    responses(
+        (status = 200, description = "Logged in, the token cookie is set"),
+        (status = 401, description = "Wrong username or password, or the account is disabled", body = ErrorBody),
+        (status = 403, description = "Password login is disabled", body = ErrorBody),
+        (status = 429, description = "Too many failed login attempts", body = ErrorBody),
+    )
+)]
 pub async fn login(
     State(appstate): State<AppstateWrapper>,
+    client: ClientInfo,
     jar: PrivateCookieJar,
     Json(body): Json<Body>
-) -> Result<(StatusCode, PrivateCookieJar), (StatusCode, &'static str)> {
+) -> Result<(StatusCode, PrivateCookieJar), ApiError> {
     let appstate = appstate.0;
 
-    // get user from db
-    let conn = &appstate.db_pool;
-    let query_result = sqlx::query("SELECT * FROM users WHERE username = $1")
-        .bind(body.username)
-        .fetch_optional(conn.as_ref())
-        .await;
+    if appstate.auth_providers.is_empty() {
+        return Err(ApiError::Forbidden("Password login is disabled"))
+    }
 
-    let row = match query_result {
-        Ok(Some(row)) => row,
-        Ok(None) => return Err((StatusCode::BAD_REQUEST, "User does not exist")),
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user from db"))
-    };
+    let limiter = &appstate.rate_limiter;
+    let now = Utc::now().timestamp();
 
-    // compare passwords
-    let user= User::from_pg_row(row)
-        .ok().ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Failed to parse user"))?;
+    // reject locked accounts and ips before doing any work
+    let account_key = format!("login:user:{}", body.username.to_lowercase());
+    let ip_key = format!("login:ip:{}", client.ip);
+    for key in [&account_key, &ip_key] {
+        match limiter.locked_until(key, now).await {
+            Ok(None) => {},
+            Ok(Some(until)) => {
+                appstate.metrics.auth_failure("lockout");
+                record_failure(&appstate, &body.username, &client, "locked out").await;
+                return Err(ApiError::TooManyRequests("Too many failed login attempts", until - now))
+            },
+            Err(e) => return Err(ApiError::internal("Failed to check login attempts", e))
+        }
+    }
+
+    // ask every provider until one accepts the credentials
+    let mut user = None;
+    for provider in &appstate.auth_providers {
+        match provider.authenticate(&body.username, body.password.clone(), &appstate).await {
+            Ok(Some(o)) => {
+                user = Some(o);
+                break
+            },
+            Ok(None) => {},
+            // an unreachable directory mustn't keep the other providers from answering
+            Err(e) => eprintln!("Auth provider failed, trying the next one: {}", e),
+        }
+    }
 
-    match user.compare_passwords(body.password) {
-        Ok(o) => {
-            if !o {
-                return Err((StatusCode::UNAUTHORIZED, "Wrong password"))
+    // disabled accounts get the same answer as wrong credentials, so they can't be told apart
+    let user = match user {
+        Some(user) if !user.disabled => user,
+        found => {
+            let detail = match found {
+                Some(_) => "account is disabled",
+                None => "wrong username or password",
+            };
+            appstate.metrics.auth_failure("password");
+            record_failure(&appstate, &body.username, &client, detail).await;
+            let account_lock = limiter.record_failure(&account_key, &appstate.config.rate_limit.login_account, now).await;
+            let ip_lock = limiter.record_failure(&ip_key, &appstate.config.rate_limit.login_ip, now).await;
+            if account_lock.is_err() || ip_lock.is_err() {
+                return Err(ApiError::Internal("Failed to record login attempt"))
             }
-        },
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to compare passwords"))
+            return Err(ApiError::Unauthorized("Wrong username or password"))
+        }
+    };
+
+    if limiter.reset_failures(&account_key).await.is_err() {
+        return Err(ApiError::Internal("Failed to reset login attempts"))
     }
 
     // generate token
-    let token = match Claims::generate_jwt(&appstate.jwt_secret, &user) {
+    let token = match Claims::generate_jwt(&appstate.config.auth, &user) {
         Ok(o) => o,
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate jwt"))
+        Err(e) => return Err(ApiError::internal("Failed to generate jwt", e))
     };
 
     // set cookies
-    let mut cookie = Cookie::new("token", token);
-    cookie.set_http_only(true);
-    cookie.set_same_site(SameSite::Strict);
+    let jar = jar.add(Claims::cookie(token));
 
-    let jar = jar.add(cookie);
+    let entry = AuditEntry::new(AuditAction::Login, AuditOutcome::Success).actor(&user).client(&client);
+    audit::record(&appstate, entry).await;
 
     Ok((StatusCode::OK, jar))
+}
+
+/// Attributes a failed login to the account if the username exists, so its owner sees it too
+async fn record_failure(appstate: &Appstate, username: &str, client: &ClientInfo, detail: &str) {
+    let entry = match appstate.users.get_by_username(username).await {
+        Ok(Some(user)) => AuditEntry::new(AuditAction::Login, AuditOutcome::Failure).actor(&user),
+        _ => AuditEntry { actor: Some(username.to_string()), ..AuditEntry::new(AuditAction::Login, AuditOutcome::Failure) },
+    };
+    audit::record(appstate, entry.client(client).detail(detail)).await;
 }
\ No newline at end of file
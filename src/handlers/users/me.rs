@@ -0,0 +1,72 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::{AuthUser, Permission};
+use axum::extract::State;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Profile)]
+pub struct Response {
+    uuid: Uuid,
+    username: String,
+    email: String,
+    /// New email waiting for confirmation
+    pending_email: Option<String>,
+    permission: Permission,
+    /// Bytes used by all files of the user
+    usage: usize,
+    /// Quota in bytes, null means unlimited
+    quota: Option<usize>,
+    /// Two-factor authentication isn't supported yet, always false
+    two_factor: bool,
+    /// Unix timestamp of a scheduled account deletion
+    deletion_scheduled_for: Option<i64>,
+    /// Unix timestamp of the account creation
+    timestamp: usize,
+}
+
+/// Returns the profile of the logged-in user
+#[utoipa::path(
+    get, path = "/me", tag = "users",
+    responses(
+        (status = 200, description = "Profile of the logged-in user", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn me(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+) -> Result<Json<Response>, ApiError> {
+    let user = auth_user.0.0;
+    let appstate = appstate.0;
+
+    let usage = appstate.files.usage(user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch storage usage", e))?
+        .bytes;
+
+    let pending_email = appstate.email_changes.get(user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch email change", e))?
+        .map(|change| change.new_email);
+
+    let deletion_scheduled_for = appstate.deletions.get(user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch account deletion", e))?
+        .map(|deletion| deletion.scheduled_for);
+
+    Ok(Json(Response {
+        uuid: user.uuid,
+        username: user.username,
+        email: user.email,
+        pending_email,
+        permission: user.permission,
+        usage,
+        quota: user.quota,
+        two_factor: false,
+        deletion_scheduled_for,
+        timestamp: user.timestamp,
+    }))
+}
@@ -1,4 +1,6 @@
+use crate::error::{ApiError, ErrorBody, FieldError};
 use crate::models::appstate::AppstateWrapper;
+use crate::repository::error::RepositoryError;
 use crate::util::jwt::claims::Claims;
 use crate::{
     models::user::*,
@@ -8,39 +10,53 @@ use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, PasswordHasher};
 use axum::{extract::State, http::StatusCode, Json};
-use axum_extra::extract::cookie::{Cookie, SameSite};
 use axum_extra::extract::PrivateCookieJar;
 use serde::{Deserialize, Serialize};
-use sqlx::Error;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = NewUser)]
 pub struct Body {
     username: String,
     email: String,
     password: String,
 }
 
+#[utoipa::path(
+    post, path = "/new", tag = "users",
+    request_body = Body,
+    responses(
+        (status = 201, description = "User created, the token cookie is set"),
+        (status = 400, description = "Validation failed", body = ErrorBody),
+        (status = 403, description = "Local sign up is disabled", body = ErrorBody),
+        (status = 409, description = "Username is already taken", body = ErrorBody),
+    )
+)]
 #[axum_macros::debug_handler]
 pub async fn new(
     State(appstate): State<AppstateWrapper>,
     jar: PrivateCookieJar,
     Json(body): Json<Body>,
-) -> Result<(StatusCode, PrivateCookieJar), (StatusCode, &'static str)> {
+) -> Result<(StatusCode, PrivateCookieJar), ApiError> {
     let appstate = appstate.0;
 
-    // validate username & password
-    match validation::username(&body.username) {
-        (true, _) => {},
-        (false, _) => {
-                return Err((StatusCode::BAD_REQUEST, "Username is not valid"))
-        }
+    if !appstate.config.auth.local_login {
+        return Err(ApiError::Forbidden("Local sign up is disabled"))
     }
 
-    match validation::password(&body.password) {
-        (true, _) => {}
-        (false, _) => {
-            return Err((StatusCode::BAD_REQUEST, "Password is not valid"))
-        }
+    // validate username, password & email, reporting every failed field at once
+    let mut errors = Vec::new();
+    if let (false, reason) = validation::username(&body.username) {
+        errors.push(FieldError::new("username", reason));
+    }
+    if let (false, reason) = validation::password(&body.password) {
+        errors.push(FieldError::new("password", reason));
+    }
+    if let (false, reason) = validation::email(&body.email) {
+        errors.push(FieldError::new("email", reason));
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors))
     }
 
     // hash password
@@ -49,7 +65,7 @@ pub async fn new(
     let argon = Argon2::default();
     let hashed_password = match argon.hash_password(body.password.as_ref(), &salt) {
         Ok(o) => o.to_string(),
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash password"))
+        Err(e) => return Err(ApiError::internal("Failed to hash password", e))
     };
 
     // construct user model
@@ -57,50 +73,29 @@ pub async fn new(
         body.username,
         hashed_password,
         body.email,
-        Permission::USER /* Hard coded user permission, admin rights yet to be implemented */
+        Permission::USER
     );
 
     // TODO! send user email to validate
 
     // generate jwt for user
-    let token = Claims::generate_jwt(&appstate.jwt_secret, &user);
+    let token = Claims::generate_jwt(&appstate.config.auth, &user);
     let Ok(token) = token else {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate jwt"))
+        return Err(ApiError::Internal("Failed to generate jwt"))
     };
 
     // write user to db
-    let conn = &appstate.db_pool;
-    let query =
-        r"INSERT INTO users (uuid, username, email, password, permission, tokenid) VALUES ($1, $2, $3, $4, $5, $6)";
-
-    let query_result = sqlx::query(query)
-        .bind(&user.uuid.to_string())
-        .bind(&user.username)
-        .bind(&user.email)
-        .bind(&user.password)
-        .bind(&user.permission)
-        .bind(&user.tokenid.to_string())
-        .execute(conn.as_ref()).await;
+    let query_result = appstate.users.insert(&user).await;
 
     if let Err(e) = query_result {
         return match e {
-            Error::Database(db_err) => {
-                if db_err.is_unique_violation() {
-                    Err((StatusCode::BAD_REQUEST, "Username is already taken"))
-                } else {
-                    Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to write to database"))
-                }
-            }
-            _ => Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to write to database"))
+            RepositoryError::Conflict => Err(ApiError::Conflict("Username is already taken")),
+            e => Err(ApiError::internal("Failed to write to database", e))
         }
     }
 
     // set cookie
-    let mut cookie = Cookie::new("token", token);
-    cookie.set_http_only(true);
-    cookie.set_same_site(SameSite::Strict);
-
-    let jar = jar.add(cookie);
+    let jar = jar.add(Claims::cookie(token));
 
     Ok((StatusCode::CREATED, jar))
 }
\ No newline at end of file
@@ -0,0 +1,123 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::handlers::users::oidc::login::{PendingLogin, PENDING_COOKIE, PENDING_COOKIE_PATH};
+use crate::models::appstate::{Appstate, AppstateWrapper};
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::identity::ExternalIdentity;
+use crate::models::user::User;
+use crate::repository::error::RepositoryError;
+use crate::util::ip::ClientInfo;
+use crate::util::jwt::claims::Claims;
+use axum::extract::{Query, State};
+use axum::response::Redirect;
+use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::PrivateCookieJar;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    code: String,
+    state: String,
+}
+
+/// Finishes the login at the identity provider \
+/// Logs in the linked user, provisions a new one on first login or links the identity to the user that started the flow
+#[utoipa::path(
+    get, path = "/oidc/callback", tag = "users",
+    params(Params),
+    responses(
+        (status = 303, description = "Logged in or identity linked, redirect to the app"),
+        (status = 400, description = "No login in progress or state does not match", body = ErrorBody),
+        (status = 401, description = "Failed to verify login at identity provider", body = ErrorBody),
+        (status = 403, description = "Account is disabled", body = ErrorBody),
+        (status = 409, description = "Identity is already linked to an account", body = ErrorBody),
+    )
+)]
+#[axum_macros::debug_handler]
+pub async fn oidc_callback(
+    State(appstate): State<AppstateWrapper>,
+    client: ClientInfo,
+    jar: PrivateCookieJar,
+    Query(params): Query<Params>,
+) -> Result<(PrivateCookieJar, Redirect), ApiError> {
+    let appstate = appstate.0;
+    let provider = appstate.oidc.as_ref()
+        .ok_or(ApiError::NotFound("Single sign-on is not configured"))?;
+
+    // restore and consume the pending login
+    let pending: PendingLogin = jar.get(PENDING_COOKIE)
+        .and_then(|cookie| serde_json::from_str(cookie.value()).ok())
+        .ok_or(ApiError::BadRequest("No login in progress"))?;
+    let jar = jar.remove(Cookie::build(PENDING_COOKIE).path(PENDING_COOKIE_PATH));
+
+    if pending.request.state != params.state {
+        return Err(ApiError::BadRequest("State does not match"))
+    }
+
+    let claims = provider.exchange_code(&params.code, &pending.request).await
+        .map_err(|_| {
+            appstate.metrics.auth_failure("oidc");
+            ApiError::Unauthorized("Failed to verify login at identity provider")
+        })?;
+    let identity = ExternalIdentity::new(claims.iss.clone(), claims.sub.clone(), uuid::Uuid::nil());
+
+    // link to the user that started the flow
+    if let Some(user_uuid) = pending.link_user {
+        let identity = ExternalIdentity { user_uuid, ..identity };
+        return match appstate.identities.link(&identity).await {
+            Ok(_) => Ok((jar, Redirect::to(provider.post_login_redirect()))),
+            Err(RepositoryError::Conflict) => {
+                Err(ApiError::Conflict("Identity is already linked to an account"))
+            },
+            Err(e) => Err(ApiError::internal("Failed to write to database", e))
+        }
+    }
+
+    // log in the linked user or provision one
+    let user = match identity.get_user(&appstate).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            // only trust the email if the provider verified it
+            let email = claims.email.filter(|_| claims.email_verified.unwrap_or(false));
+            ExternalIdentity::provision_user(
+                claims.iss,
+                claims.sub,
+                claims.preferred_username.as_deref(),
+                email,
+                &appstate,
+            ).await.map_err(|e| ApiError::internal("Failed to provision user", e))?
+        },
+        Err(e) => return Err(ApiError::internal("Failed to fetch user from db", e))
+    };
+
+    let result = login(&user, jar, &appstate, provider.post_login_redirect());
+    let outcome = match &result {
+        Ok(_) => Some((AuditOutcome::Success, "oidc")),
+        Err(ApiError::Forbidden(_)) => Some((AuditOutcome::Failure, "oidc, account is disabled")),
+        Err(_) => None,
+    };
+    if let Some((outcome, detail)) = outcome {
+        let entry = AuditEntry::new(AuditAction::Login, outcome).actor(&user).client(&client).detail(detail);
+        audit::record(&appstate, entry).await;
+    }
+    result
+}
+
+fn login(user: &User, jar: PrivateCookieJar, appstate: &Appstate, redirect: &str)
+    -> Result<(PrivateCookieJar, Redirect), ApiError> {
+    if user.disabled {
+        return Err(ApiError::Forbidden("Account is disabled"))
+    }
+
+    // generate token
+    let token = match Claims::generate_jwt(&appstate.config.auth, user) {
+        Ok(o) => o,
+        Err(e) => return Err(ApiError::internal("Failed to generate jwt", e))
+    };
+
+    // set cookies
+    let jar = jar.add(Claims::cookie(token));
+
+    Ok((jar, Redirect::to(redirect)))
+}
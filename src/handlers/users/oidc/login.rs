@@ -0,0 +1,85 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::AuthUser;
+use crate::util::oidc::AuthorizationRequest;
+use axum::extract::State;
+use axum::response::Redirect;
+use axum::Extension;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::PrivateCookieJar;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Name of the private cookie holding the pending login until the callback
+pub const PENDING_COOKIE: &str = "oidc";
+/// Only sent to the routes below it, the callback has to remove it with the same path
+pub const PENDING_COOKIE_PATH: &str = "/v1/user/oidc";
+
+/// Login in progress at the identity provider
+#[derive(Serialize, Deserialize)]
+pub struct PendingLogin {
+    pub request: AuthorizationRequest,
+    /// Set when an already logged-in user links the identity to their account
+    pub link_user: Option<Uuid>,
+}
+
+/// Redirects to the identity provider to log in
+#[utoipa::path(
+    get, path = "/oidc/login", tag = "users",
+    responses(
+        (status = 303, description = "Redirect to the identity provider"),
+        (status = 404, description = "Single sign-on is not configured", body = ErrorBody),
+    )
+)]
+#[axum_macros::debug_handler]
+pub async fn oidc_login(
+    State(appstate): State<AppstateWrapper>,
+    jar: PrivateCookieJar,
+) -> Result<(PrivateCookieJar, Redirect), ApiError> {
+    start(appstate, jar, None)
+}
+
+/// Redirects to the identity provider to link the identity to the logged-in user
+#[utoipa::path(
+    get, path = "/oidc/link", tag = "users",
+    responses(
+        (status = 303, description = "Redirect to the identity provider"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "Single sign-on is not configured", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn oidc_link(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+    jar: PrivateCookieJar,
+) -> Result<(PrivateCookieJar, Redirect), ApiError> {
+    let user = auth_user.0.0;
+    start(appstate, jar, Some(user.uuid))
+}
+
+fn start(
+    appstate: AppstateWrapper,
+    jar: PrivateCookieJar,
+    link_user: Option<Uuid>,
+) -> Result<(PrivateCookieJar, Redirect), ApiError> {
+    let provider = appstate.oidc.as_ref()
+        .ok_or(ApiError::NotFound("Single sign-on is not configured"))?;
+
+    let pending = PendingLogin { request: AuthorizationRequest::new(), link_user };
+    let url = provider.authorization_url(&pending.request)
+        .map_err(|e| ApiError::internal("Failed to construct authorization url", e))?;
+    let value = serde_json::to_string(&pending)
+        .map_err(|e| ApiError::internal("Failed to serialize login", e))?;
+
+    // lax, as the callback is a cross-site navigation from the provider
+    let mut cookie = Cookie::new(PENDING_COOKIE, value);
+    cookie.set_http_only(true);
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_path(PENDING_COOKIE_PATH);
+
+    let jar = jar.add(cookie);
+
+    Ok((jar, Redirect::to(&url)))
+}
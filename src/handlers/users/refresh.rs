@@ -1,33 +1,38 @@
+use crate::error::{ApiError, ErrorBody};
 use crate::models::appstate::AppstateWrapper;
-use crate::models::user::AuthUser;
+use crate::models::user::{AuthUser, Session};
 use crate::util::jwt::claims::Claims;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::Extension;
-use axum_extra::extract::cookie::{Cookie, SameSite};
 use axum_extra::extract::PrivateCookieJar;
 
+#[utoipa::path(
+    get, path = "/refresh_token", tag = "users",
+    responses(
+        (status = 200, description = "New token cookie is set"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
 #[axum_macros::debug_handler]
 pub async fn refresh_token(
     auth_user: Extension<AuthUser>,
+    Extension(session): Extension<Session>,
     jar: PrivateCookieJar,
     State(appstate): State<AppstateWrapper>,
-) -> Result<(StatusCode, PrivateCookieJar), (StatusCode, &'static str)> {
+) -> Result<(StatusCode, PrivateCookieJar), ApiError> {
     let appstate = appstate.0;
     let user = auth_user.0.0;
 
-    // generate new token
-    let new_token = match Claims::generate_jwt(&appstate.jwt_secret, &user) {
+    // generate new token, refreshing isn't a login
+    let new_token = match Claims::refresh_jwt(&appstate.config.auth, &user, session.auth_time) {
         Ok(o) => o,
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate new token"))
+        Err(e) => return Err(ApiError::internal("Failed to generate new token", e))
     };
 
     // set new token in cookies
-    let mut cookie = Cookie::new("token", new_token);
-    cookie.set_http_only(true);
-    cookie.set_same_site(SameSite::Strict);
-
-    let jar = jar.add(cookie);
+    let jar = jar.add(Claims::cookie(new_token));
 
     Ok((StatusCode::OK, jar))
 }
\ No newline at end of file
@@ -0,0 +1,70 @@
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::email_change::EmailChange;
+use crate::models::user::AuthUser;
+use crate::util::validation;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = ChangeEmail)]
+pub struct Body {
+    new_email: String,
+    password: String,
+}
+
+/// Requests a change of the email, dependent on password confirmation \
+/// The new email only gets applied once the token sent to it is confirmed
+#[utoipa::path(
+    put, path = "/email/change", tag = "users",
+    request_body = Body,
+    responses(
+        (status = 202, description = "Confirmation token sent to the new email"),
+        (status = 400, description = "Validation failed", body = ErrorBody),
+        (status = 401, description = "Wrong password", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn change_email(
+    auth_user: Extension<AuthUser>,
+    State(appstate): State<AppstateWrapper>,
+    Json(body): Json<Body>
+) -> Result<StatusCode, ApiError> {
+    let user = auth_user.0.0;
+    let appstate = appstate.0;
+
+    if let (false, reason) = validation::email(&body.new_email) {
+        return Err(ApiError::Validation(vec![FieldError::new("new_email", reason)]))
+    }
+
+    // confirm password
+    match user.compare_passwords(body.password) {
+        Ok(o) => {
+            if !o {
+                return Err(ApiError::Unauthorized("Wrong Password"))
+            }
+        },
+        Err(e) => return Err(ApiError::internal("Failed to compare passwords", e))
+    };
+
+    let token = match EmailChange::create(&user, body.new_email.clone(), &appstate).await {
+        Ok(o) => o,
+        Err(e) => return Err(ApiError::internal("Failed to write change to db", e))
+    };
+
+    // send token to the new address to prove ownership
+    let mail = format!(
+        "Hello {},\n\nconfirm this address for your drive account with the following token:\n\n{}\n\n\
+         The token is valid for 24 hours. If you didn't request this, you can ignore this mail.",
+        user.username, token
+    );
+    if appstate.mailer.send(&body.new_email, "Confirm your new email", &mail).await.is_err() {
+        return Err(ApiError::Internal("Failed to send confirmation mail"))
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
@@ -0,0 +1,53 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::email_change::EmailChange;
+use crate::repository::error::RepositoryError;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = ConfirmEmail)]
+pub struct Body {
+    token: String,
+}
+
+/// Applies a pending email change with the token sent to the new address \
+/// Doesn't require a login as the token may be opened on another device
+#[utoipa::path(
+    post, path = "/email/confirm", tag = "users",
+    request_body = Body,
+    responses(
+        (status = 200, description = "Email changed"),
+        (status = 400, description = "Token is invalid or expired", body = ErrorBody),
+        (status = 409, description = "Email is already taken", body = ErrorBody),
+    )
+)]
+#[axum_macros::debug_handler]
+pub async fn confirm_email(
+    State(appstate): State<AppstateWrapper>,
+    Json(body): Json<Body>
+) -> Result<StatusCode, ApiError> {
+    let appstate = appstate.0;
+
+    let (change, old_email) = match EmailChange::confirm(&body.token, &appstate).await {
+        Ok(Some(o)) => o,
+        Ok(None) => return Err(ApiError::BadRequest("Token is invalid or expired")),
+        Err(RepositoryError::Conflict) => return Err(ApiError::Conflict("Email is already taken")),
+        Err(e) => return Err(ApiError::internal("Failed to write change to db", e))
+    };
+
+    // let the old address know, the change is applied either way
+    let mail = format!(
+        "Hello,\n\nthe email of your drive account has been changed to {}.\n\n\
+         If you didn't do this, contact your administrator.",
+        change.new_email
+    );
+    if let Err(e) = appstate.mailer.send(&old_email, "Your email has been changed", &mail).await {
+        eprintln!("Failed to notify {} about email change: {}", old_email, e);
+    }
+
+    Ok(StatusCode::OK)
+}
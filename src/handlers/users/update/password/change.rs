@@ -1,18 +1,23 @@
+use crate::error::{ApiError, ErrorBody, FieldError};
 use crate::models::appstate::AppstateWrapper;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
 use crate::models::user::AuthUser;
+use crate::util::ip::ClientInfo;
 use crate::util::jwt::claims::Claims;
+use crate::util::validation;
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, PasswordHasher};
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::{Extension, Json};
-use axum_extra::extract::cookie::{Cookie, SameSite};
 use axum_extra::extract::PrivateCookieJar;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = ChangePassword)]
 pub struct Body {
     old_password: String,
     new_password: String,
@@ -20,13 +25,24 @@ pub struct Body {
 
 /// Changes Password to new one, dependent on old password confirmation
 /// Generates new token for user
+#[utoipa::path(
+    put, path = "/password/change", tag = "users",
+    request_body = Body,
+    responses(
+        (status = 200, description = "Password changed, the new token cookie is set"),
+        (status = 400, description = "Validation failed", body = ErrorBody),
+        (status = 401, description = "Wrong password", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
 #[axum_macros::debug_handler]
 pub async fn change_password(
     auth_user: Extension<AuthUser>,
     State(appstate): State<AppstateWrapper>,
+    client: ClientInfo,
     jar: PrivateCookieJar,
     Json(body): Json<Body>
-) -> Result<(StatusCode, PrivateCookieJar), (StatusCode, &'static str)> {
+) -> Result<(StatusCode, PrivateCookieJar), ApiError> {
     let mut user = auth_user.0.0;
     let appstate = appstate.0;
 
@@ -34,19 +50,25 @@ pub async fn change_password(
     match user.compare_passwords(body.old_password) {
         Ok(o) => {
             if !o {
-                return Err((StatusCode::UNAUTHORIZED, "Wrong Password"))
+                let entry = AuditEntry::new(AuditAction::PasswordChange, AuditOutcome::Failure).actor(&user).client(&client);
+                audit::record(&appstate, entry.detail("wrong password")).await;
+                return Err(ApiError::Unauthorized("Wrong Password"))
             }
         },
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to compare passwords"))
+        Err(e) => return Err(ApiError::internal("Failed to compare passwords", e))
     };
 
+    if let (false, reason) = validation::password(&body.new_password) {
+        return Err(ApiError::Validation(vec![FieldError::new("new_password", reason)]))
+    }
+
     // hash new password
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
 
     let new_hashed = match argon2.hash_password(body.new_password.as_ref(), &salt) {
         Ok(o) => o.to_string(),
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash new password"))
+        Err(e) => return Err(ApiError::internal("Failed to hash new password", e))
     };
 
     // generate new token-id
@@ -54,31 +76,23 @@ pub async fn change_password(
     user.tokenid = new_tokenid;
 
     // update new password in db
-    let conn = &appstate.db_pool;
-    let query = r"UPDATE users SET password = $1, tokenid = $2 WHERE uuid = $3";
-    let query_result = sqlx::query(query)
-        .bind(new_hashed)
-        .bind(new_tokenid.to_string())
-        .bind(user.uuid.to_string())
-        .execute(conn.as_ref())
-        .await;
+    let query_result = appstate.users.set_password(user.uuid, &new_hashed, new_tokenid, false).await;
 
-    if !query_result.is_ok() {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to write change to db"))
+    if query_result.is_err() {
+        return Err(ApiError::Internal("Failed to write change to db"))
     }
 
     // generate new token
-    let token = match Claims::generate_jwt(&appstate.jwt_secret, &user) {
+    let token = match Claims::generate_jwt(&appstate.config.auth, &user) {
         Ok(o) => o,
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate new token"))
+        Err(e) => return Err(ApiError::internal("Failed to generate new token", e))
     };
 
     // add new token to cookies
-    let mut cookie = Cookie::new("token", token);
-    cookie.set_http_only(true);
-    cookie.set_same_site(SameSite::Strict);
+    let jar = jar.add(Claims::cookie(token));
 
-    let jar = jar.add(cookie);
+    let entry = AuditEntry::new(AuditAction::PasswordChange, AuditOutcome::Success).actor(&user).client(&client);
+    audit::record(&appstate, entry).await;
 
     Ok((StatusCode::OK, jar))
 }
\ No newline at end of file
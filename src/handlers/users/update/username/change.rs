@@ -1,39 +1,61 @@
+use crate::error::{ApiError, ErrorBody, FieldError};
 use crate::models::appstate::AppstateWrapper;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
 use crate::models::user::AuthUser;
+use crate::repository::error::RepositoryError;
+use crate::util::ip::ClientInfo;
+use crate::util::validation;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::{Extension, Json};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = ChangeUsername)]
 pub struct Body {
     new_username: String,
 }
 
 /// Changes Username to new one, dependent on password confirmation
+#[utoipa::path(
+    put, path = "/username/change", tag = "users",
+    request_body = Body,
+    responses(
+        (status = 200, description = "Username changed"),
+        (status = 400, description = "Validation failed", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 409, description = "Username is already taken", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
 #[axum_macros::debug_handler]
 pub async fn change_username(
     auth_user: Extension<AuthUser>,
     State(appstate): State<AppstateWrapper>,
+    client: ClientInfo,
     Json(body): Json<Body>
-) -> Result<StatusCode, (StatusCode, &'static str)> {
+) -> Result<StatusCode, ApiError> {
     let user = auth_user.0.0;
     let appstate = appstate.0;
 
-    // update new username in db
-    let conn = &appstate.db_pool;
-    let query = r"UPDATE users SET username = $1 WHERE uuid = $2";
-    let query_result = sqlx::query(query)
-        .bind(body.new_username)
-        .bind(user.uuid.to_string())
-        .execute(conn.as_ref())
-        .await;
+    if let (false, reason) = validation::username(&body.new_username) {
+        return Err(ApiError::Validation(vec![FieldError::new("new_username", reason)]))
+    }
 
+    // update new username in db
+    let query_result = appstate.users.set_username(user.uuid, &body.new_username).await;
 
-    if !query_result.is_ok() {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to write change to db"))
+    if let Err(e) = query_result {
+        return match e {
+            RepositoryError::Conflict => Err(ApiError::Conflict("Username is already taken")),
+            e => Err(ApiError::internal("Failed to write change to db", e))
+        }
     }
 
+    // recorded under the old username, the detail holds the new one
+    let entry = AuditEntry::new(AuditAction::UsernameChange, AuditOutcome::Success).actor(&user).client(&client);
+    audit::record(&appstate, entry.detail(body.new_username)).await;
 
     Ok(StatusCode::OK)
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::handlers::webhooks::list::Info;
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::AuthUser;
+use crate::models::webhook::{Webhook, EVENTS, MAX_WEBHOOKS};
+use crate::util::outbound;
+use crate::util::token;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = NewWebhook)]
+pub struct Body {
+    /// http or https url receiving a POST per event, must not resolve to an internal address
+    url: String,
+    /// e.g. `file.uploaded`, `file.renamed`, `file.moved`, `file.updated` or `file.deleted`
+    events: Vec<String>,
+    /// Key of the signatures, generated if missing
+    secret: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = CreatedWebhook)]
+pub struct Response {
+    #[serde(flatten)]
+    webhook: Info,
+    /// Only returned here, payloads are signed with it in `X-Drive-Signature`
+    secret: String,
+}
+
+/// Subscribes a url to events of the files of the logged-in user \
+/// Every delivery is signed with `X-Drive-Signature: sha256=<hex hmac of "{X-Drive-Timestamp}.{body}">`
+#[utoipa::path(
+    post, path = "/", tag = "webhooks",
+    request_body = Body,
+    responses(
+        (status = 201, description = "Webhook created", body = Response),
+        (status = 400, description = "Validation failed, e.g. the url is internal", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 409, description = "Too many webhooks", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn create_webhook(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Json(body): Json<Body>,
+) -> Result<(StatusCode, Json<Response>), ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let mut errors = Vec::new();
+    match reqwest::Url::parse(&body.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {
+            if let Err(e) = outbound::check_url(&url, &appstate.config.webhooks.allowed_hosts).await {
+                errors.push(FieldError::new("url", e));
+            }
+        },
+        _ => errors.push(FieldError::new("url", "Not an http or https url")),
+    }
+    let mut events = body.events;
+    events.sort();
+    events.dedup();
+    if events.is_empty() {
+        errors.push(FieldError::new("events", "No events"));
+    }
+    for event in events.iter().filter(|event| !EVENTS.contains(&event.as_str())) {
+        errors.push(FieldError::new("events", format!("{}: unknown event", event)));
+    }
+    if body.secret.as_ref().is_some_and(|secret| secret.len() < 16 || secret.len() > 256) {
+        errors.push(FieldError::new("secret", "Length of secret not in bounds of 16-256"));
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors))
+    }
+
+    let existing = appstate.webhooks.list(user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch webhooks from db", e))?;
+    if existing.len() >= MAX_WEBHOOKS {
+        return Err(ApiError::Conflict("Webhook limit reached"))
+    }
+
+    let secret = body.secret.unwrap_or_else(token::generate);
+    let webhook = Webhook::new(user.uuid, body.url, secret.clone(), events);
+    appstate.webhooks.insert(&webhook).await
+        .map_err(|e| ApiError::internal("Failed to write webhook to db", e))?;
+
+    Ok((StatusCode::CREATED, Json(Response { webhook: Info::from(webhook), secret })))
+}
@@ -0,0 +1,37 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::AuthUser;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Extension;
+use uuid::Uuid;
+
+/// Deletes a webhook, pending deliveries are dropped
+#[utoipa::path(
+    delete, path = "/{webhook_id}", tag = "webhooks",
+    params(("webhook_id" = Uuid, Path, description = "Uuid of the webhook")),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "Webhook does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn delete_webhook(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let exists = appstate.webhooks.delete(webhook_id, user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to delete webhook from db", e))?;
+    if !exists {
+        return Err(ApiError::NotFound("Webhook does not exist"))
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,55 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::AuthUser;
+use crate::models::webhook::Delivery;
+use axum::extract::{Path, Query, State};
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Deliveries)]
+pub struct Response {
+    /// Newest first
+    deliveries: Vec<Delivery>,
+}
+
+/// Delivery log of a webhook, finished deliveries are kept for 30 days
+#[utoipa::path(
+    get, path = "/{webhook_id}/deliveries", tag = "webhooks",
+    params(("webhook_id" = Uuid, Path, description = "Uuid of the webhook"), Params),
+    responses(
+        (status = 200, description = "Latest deliveries", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "Webhook does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn list_deliveries(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path(webhook_id): Path<Uuid>,
+    Query(params): Query<Params>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    appstate.webhooks.get(webhook_id, user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch webhook from db", e))?
+        .ok_or(ApiError::NotFound("Webhook does not exist"))?;
+
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000) as usize;
+    let deliveries = appstate.webhooks.deliveries(webhook_id, limit).await
+        .map_err(|e| ApiError::internal("Failed to fetch deliveries from db", e))?;
+
+    Ok(Json(Response { deliveries }))
+}
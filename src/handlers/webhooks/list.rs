@@ -0,0 +1,61 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::AuthUser;
+use crate::models::webhook::Webhook;
+use axum::extract::State;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Webhook as shown to its owner, the secret is only returned on creation
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = WebhookInfo)]
+pub struct Info {
+    pub uuid: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub timestamp: usize,
+}
+
+impl From<Webhook> for Info {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            uuid: webhook.uuid,
+            url: webhook.url,
+            events: webhook.events,
+            timestamp: webhook.timestamp,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Webhooks)]
+pub struct Response {
+    /// Oldest first
+    webhooks: Vec<Info>,
+}
+
+/// Webhooks of the logged-in user
+#[utoipa::path(
+    get, path = "/", tag = "webhooks",
+    responses(
+        (status = 200, description = "All webhooks", body = Response),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn list_webhooks(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+) -> Result<Json<Response>, ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let webhooks = appstate.webhooks.list(user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch webhooks from db", e))?;
+
+    Ok(Json(Response { webhooks: webhooks.into_iter().map(Info::from).collect() }))
+}
@@ -0,0 +1,41 @@
+use crate::error::{ApiError, ErrorBody};
+use crate::models::appstate::AppstateWrapper;
+use crate::models::user::AuthUser;
+use crate::models::webhook::{self, Delivery, PING};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde_json::json;
+use uuid::Uuid;
+
+/// Sends a `ping` event to test the receiver, its outcome shows up in the deliveries \
+/// Failed pings are retried like any other delivery
+#[utoipa::path(
+    post, path = "/{webhook_id}/ping", tag = "webhooks",
+    params(("webhook_id" = Uuid, Path, description = "Uuid of the webhook")),
+    responses(
+        (status = 202, description = "Ping queued", body = Delivery),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "Webhook does not exist", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody),
+    ),
+    security(("cookie" = []))
+)]
+#[axum_macros::debug_handler]
+pub async fn ping_webhook(
+    State(appstate): State<AppstateWrapper>,
+    auth_user: Extension<AuthUser>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Delivery>), ApiError> {
+    let appstate = appstate.0;
+    let user = auth_user.0.0;
+
+    let webhook = appstate.webhooks.get(webhook_id, user.uuid).await
+        .map_err(|e| ApiError::internal("Failed to fetch webhook from db", e))?
+        .ok_or(ApiError::NotFound("Webhook does not exist"))?;
+
+    let delivery = webhook::enqueue(&appstate, &webhook, PING, json!({ "webhook_uuid": webhook.uuid })).await
+        .map_err(|e| ApiError::internal("Failed to queue ping", e))?;
+
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
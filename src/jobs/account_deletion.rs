@@ -0,0 +1,65 @@
+use crate::models::appstate::Appstate;
+use crate::models::deletion::AccountDeletion;
+use crate::models::job::{Job, QueuedJob};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use uuid::Uuid;
+
+/// Seconds between sweeps for due deletions without a queued purge
+pub const SWEEP_INTERVAL: i64 = 3600;
+
+/// Purges an account once its grace period is over, queued when the deletion is scheduled
+#[derive(Serialize, Deserialize)]
+pub struct PurgeAccount {
+    pub user_uuid: Uuid,
+}
+
+impl PurgeAccount {
+    /// Queues the purge for when the deletion is due, at most one per account is queued
+    pub async fn enqueue(deletion: &AccountDeletion, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let job = Self { user_uuid: deletion.user_uuid };
+        let run_at = DateTime::from_timestamp(deletion.scheduled_for, 0);
+        let key = format!("{}:{}", Self::KIND, deletion.user_uuid);
+        QueuedJob::enqueue(&job, run_at, Some(&key), appstate).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Job for PurgeAccount {
+    const KIND: &'static str = "purge_account";
+
+    /// Interrupted purges are resumed by the retry once the lease of the deletion expired
+    async fn run(self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // cancelled or already purged
+        let Some(deletion) = appstate.deletions.get(self.user_uuid).await? else {
+            return Ok(())
+        };
+        // cancelled and scheduled again in the meantime
+        if deletion.scheduled_for > Utc::now().timestamp() {
+            return Self::enqueue(&deletion, appstate).await
+        }
+
+        let deletion = AccountDeletion::claim(self.user_uuid, appstate).await?
+            .ok_or("deletion is being purged by another worker")?;
+        deletion.purge(appstate).await
+    }
+}
+
+/// Queues purges of due deletions that lost theirs, e.g. ones scheduled before the job queue existed
+#[derive(Serialize, Deserialize)]
+pub struct SweepAccountDeletions;
+
+#[async_trait]
+impl Job for SweepAccountDeletions {
+    const KIND: &'static str = "sweep_account_deletions";
+
+    async fn run(self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for deletion in appstate.deletions.due().await? {
+            PurgeAccount::enqueue(&deletion, appstate).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use crate::models::appstate::Appstate;
+use crate::models::job::Job;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// Prunes the audit log at the start of every hour
+pub const PRUNE_SCHEDULE: &str = "0 0 * * * *";
+
+/// Deletes audit log entries older than `audit.retention`
+#[derive(Serialize, Deserialize)]
+pub struct PruneAuditLog;
+
+#[async_trait]
+impl Job for PruneAuditLog {
+    const KIND: &'static str = "prune_audit_log";
+
+    async fn run(self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let retention = appstate.config.audit.retention;
+        if retention == 0 {
+            return Ok(())
+        }
+        appstate.audit.prune(Utc::now().timestamp() - retention).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use crate::models::appstate::Appstate;
+use crate::models::batch::{self, Operation, PROGRESS_INTERVAL};
+use crate::models::job::Job;
+use crate::util::ip::ClientInfo;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use uuid::Uuid;
+
+/// Works through a batch too large for a request, queued by `batch::start`
+#[derive(Serialize, Deserialize)]
+pub struct RunBatch {
+    pub batch_uuid: Uuid,
+    pub owner_uuid: Uuid,
+    pub operations: Vec<Operation>,
+    /// Of the request that started the batch, for the audit log
+    pub client: ClientInfo,
+}
+
+#[async_trait]
+impl Job for RunBatch {
+    const KIND: &'static str = "run_batch";
+
+    /// Saves the progress every `PROGRESS_INTERVAL` operations, a retry continues after the saved results
+    async fn run(self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // the owner was deleted in the meantime
+        let Some(progress) = appstate.batches.get(self.batch_uuid, self.owner_uuid).await? else {
+            return Ok(())
+        };
+        let Some(user) = appstate.users.get(self.owner_uuid).await? else {
+            return Ok(())
+        };
+
+        let mut index = progress.results.len();
+        for operations in self.operations[index.min(self.operations.len())..].chunks(PROGRESS_INTERVAL) {
+            let results = batch::execute(index, operations.to_vec(), &user, &self.client, appstate).await;
+            appstate.batches.record(self.batch_uuid, &results).await?;
+            index += operations.len();
+        }
+        appstate.batches.finish(self.batch_uuid).await?;
+        Ok(())
+    }
+}
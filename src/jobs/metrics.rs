@@ -0,0 +1,25 @@
+use crate::models::appstate::Appstate;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Runs forever, refreshing the storage gauges \
+/// Kept out of the scrape as summing up all files gets expensive on large instances
+pub async fn run(appstate: Arc<Appstate>) {
+    let interval = Duration::from_secs(appstate.config.metrics.storage_interval);
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+
+        match appstate.files.total_usage().await {
+            Ok(usage) => {
+                appstate.metrics.storage_used_bytes.set(usage.bytes as i64);
+                appstate.metrics.storage_files.set(usage.files as i64);
+            },
+            Err(e) => eprintln!("Failed to refresh storage metrics: {}", e),
+        }
+        match appstate.users.count().await {
+            Ok(users) => appstate.metrics.users.set(users as i64),
+            Err(e) => eprintln!("Failed to refresh user metrics: {}", e),
+        }
+    }
+}
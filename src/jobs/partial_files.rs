@@ -0,0 +1,35 @@
+use crate::models::appstate::Appstate;
+use crate::models::file::{orphaned_files, RECENT_WRITE};
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Runs forever, removing partial files left behind by a crash on startup and once per `RECENT_WRITE` \
+/// Files of uploads another instance may still be writing are only removed once they weren't written for that long
+pub async fn run(appstate: Arc<Appstate>) {
+    let mut interval = tokio::time::interval(RECENT_WRITE);
+    loop {
+        interval.tick().await;
+
+        match remove(&appstate).await {
+            Ok(0) => {},
+            Ok(removed) => println!("Removed {} partial files", removed),
+            Err(e) => eprintln!("Failed to remove partial files: {}", e),
+        }
+    }
+}
+
+/// Removes the files on disk without a row, returns how many
+pub async fn remove(appstate: &Appstate) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let known: HashSet<String> = appstate.files.all().await?.into_iter().map(|file| file.relative_path).collect();
+    let orphans = orphaned_files(Path::new(&appstate.file_location), &known).await?;
+    for (path, _) in &orphans {
+        match tokio::fs::remove_file(path).await {
+            Ok(_) => {},
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(orphans.len())
+}
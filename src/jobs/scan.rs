@@ -0,0 +1,50 @@
+use crate::models::appstate::Appstate;
+use crate::models::change;
+use crate::models::file::ScanStatus;
+use crate::models::job::{Job, QueuedJob};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use uuid::Uuid;
+
+/// Scans an uploaded or copied file, queued by uploads in async mode, copies of unscanned files and after failed scans
+#[derive(Serialize, Deserialize)]
+pub struct ScanFile {
+    pub reference_uuid: Uuid,
+    pub owner_uuid: Uuid,
+}
+
+impl ScanFile {
+    /// At most one scan per file is queued
+    pub async fn enqueue(reference_uuid: Uuid, owner_uuid: Uuid, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let key = format!("{}:{}", Self::KIND, reference_uuid);
+        QueuedJob::enqueue(&Self { reference_uuid, owner_uuid }, None, Some(&key), appstate).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Job for ScanFile {
+    const KIND: &'static str = "scan_file";
+
+    /// Failed scans are retried, the file stays `failed` once the attempts are used up
+    async fn run(self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // deleted in the meantime
+        let Some(mut file) = appstate.files.get(self.reference_uuid, self.owner_uuid).await? else {
+            return Ok(())
+        };
+        if matches!(file.scan_status, ScanStatus::Clean | ScanStatus::Infected) {
+            return Ok(())
+        }
+        // the upload was already announced as pending
+        if let Some(change) = file.scan(appstate).await? {
+            change::publish(appstate, &change).await;
+        }
+
+        // the search only looks into files that were found clean
+        if file.scan_status == ScanStatus::Clean {
+            file.index(appstate).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use crate::models::appstate::Appstate;
+use crate::models::webhook;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Seconds between checks for due deliveries
+const POLL_INTERVAL: u64 = 5;
+/// Days finished deliveries are kept in the log
+const RETENTION_DAYS: i64 = 30;
+
+/// Runs forever, retrying deliveries that are due and pruning old ones from the log \
+/// New deliveries are attempted right away, this only picks up retries and interrupted attempts
+pub async fn run(appstate: Arc<Appstate>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(POLL_INTERVAL));
+    loop {
+        interval.tick().await;
+
+        // work through every due delivery before sleeping again
+        loop {
+            match webhook::attempt(&appstate, None).await {
+                Ok(true) => {},
+                Ok(false) => break,
+                Err(e) => {
+                    eprintln!("Failed to attempt webhook delivery: {}", e);
+                    break
+                }
+            }
+        }
+
+        let before = Utc::now().timestamp() - RETENTION_DAYS * 24 * 3600;
+        if let Err(e) = appstate.webhooks.prune(before).await {
+            eprintln!("Failed to prune webhook deliveries: {}", e);
+        }
+    }
+}
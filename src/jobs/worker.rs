@@ -0,0 +1,126 @@
+use crate::jobs::account_deletion::{PurgeAccount, SweepAccountDeletions, SWEEP_INTERVAL};
+use crate::jobs::batch::RunBatch;
+use crate::jobs::audit::{PruneAuditLog, PRUNE_SCHEDULE};
+use crate::jobs::scan::ScanFile;
+use crate::models::appstate::Appstate;
+use crate::models::job::{QueuedJob, Registry, LEASE_DURATION};
+use chrono::Utc;
+use futures_util::FutureExt;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// Seconds between checks for due recurring jobs
+const SCHEDULE_INTERVAL: u64 = 10;
+
+/// Every job this release knows how to run
+pub fn registry() -> Registry {
+    Registry::new()
+        .register::<PurgeAccount>()
+        .register::<RunBatch>()
+        .register::<ScanFile>()
+        .every(SWEEP_INTERVAL, SweepAccountDeletions)
+        .cron(PRUNE_SCHEDULE, PruneAuditLog)
+}
+
+/// Runs `jobs.concurrency` workers and enqueues recurring jobs until the shutdown begins \
+/// Running jobs are finished first, jobs cut off by the process exiting are retried once their lease expired
+pub async fn run(appstate: Arc<Appstate>) {
+    run_with(appstate, registry()).await
+}
+
+/// Same as `run` with the jobs of `registry`
+pub async fn run_with(appstate: Arc<Appstate>, registry: Registry) {
+    let registry = Arc::new(registry);
+    let mut tasks = JoinSet::new();
+    for _ in 0..appstate.config.jobs.concurrency {
+        tasks.spawn(work(appstate.clone(), registry.clone()));
+    }
+    tasks.spawn(schedule(appstate, registry));
+    while tasks.join_next().await.is_some() {}
+}
+
+/// Claims and runs jobs one after another
+async fn work(appstate: Arc<Appstate>, registry: Arc<Registry>) {
+    let poll_interval = Duration::from_secs(appstate.config.jobs.poll_interval);
+    let kinds = registry.kinds();
+
+    while !appstate.shutdown.is_draining() {
+        let job = match QueuedJob::claim(&kinds, &appstate).await {
+            Ok(Some(o)) => o,
+            Ok(None) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(poll_interval) => {},
+                    _ = appstate.shutdown.drained() => {},
+                }
+                continue
+            },
+            Err(e) => {
+                eprintln!("Failed to claim job: {}", e);
+                tokio::time::sleep(poll_interval).await;
+                continue
+            }
+        };
+
+        // keep the lease while the job runs, so no other worker picks it up
+        // a panic is a failed attempt, it mustn't take the worker down with it
+        let result = {
+            let run = AssertUnwindSafe(registry.run(&job, appstate.clone())).catch_unwind();
+            tokio::pin!(run);
+            let mut renew = tokio::time::interval(Duration::from_secs(LEASE_DURATION as u64 / 3));
+            renew.tick().await;
+            loop {
+                tokio::select! {
+                    result = &mut run => break result.unwrap_or_else(|panic| Err(panic_message(panic).into())),
+                    _ = renew.tick() => match job.renew_lease(&appstate).await {
+                        Ok(true) => {},
+                        Ok(false) => eprintln!("Job {} ({}) lost its lease, another worker may run it too", job.id, job.kind),
+                        Err(e) => eprintln!("Failed to renew lease of job {}: {}", job.id, e),
+                    },
+                }
+            }
+        };
+
+        let finished = match result {
+            Ok(_) => job.succeed(&appstate).await,
+            Err(e) => {
+                eprintln!("Job {} ({}) failed on attempt {}: {}", job.id, job.kind, job.attempts, e);
+                job.fail(&e.to_string(), &appstate).await
+            }
+        };
+        match finished {
+            Ok(true) => {},
+            // the worker that claimed it since records the outcome
+            Ok(false) => eprintln!("Dropped the outcome of job {} ({}), it lost its lease", job.id, job.kind),
+            Err(e) => eprintln!("Failed to record the outcome of job {}: {}", job.id, e),
+        }
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    let message = panic.downcast_ref::<&str>().map(|m| m.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    format!("panicked: {}", message)
+}
+
+/// Enqueues due recurring jobs and prunes finished ones
+async fn schedule(appstate: Arc<Appstate>, registry: Arc<Registry>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SCHEDULE_INTERVAL));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = appstate.shutdown.drained() => return,
+        }
+
+        if let Err(e) = registry.enqueue_due(&appstate).await {
+            eprintln!("Failed to enqueue recurring jobs: {}", e);
+        }
+        let before = Utc::now().timestamp() - appstate.config.jobs.retention;
+        if let Err(e) = appstate.jobs.prune(before).await {
+            eprintln!("Failed to prune finished jobs: {}", e);
+        }
+    }
+}
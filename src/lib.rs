@@ -1,4 +1,9 @@
-
+pub mod app;
+pub mod config;
+pub mod error;
+pub mod migrate;
+pub mod openapi;
+pub mod router;
 
 pub mod handlers {
     pub mod users {
@@ -9,16 +14,66 @@ pub mod handlers {
             pub mod password {
                 pub mod change;
             }
+            pub mod email {
+                pub mod change;
+                pub mod confirm;
+            }
         }
         pub mod authenticate;
         pub mod login;
         pub mod refresh;
         pub mod new;
+        pub mod delete;
+        pub mod me;
+        pub mod oidc {
+            pub mod login;
+            pub mod callback;
+        }
     }
     pub mod files {
         pub mod download;
         pub mod delete;
         pub mod upload;
+        pub mod search;
+        pub mod list;
+        pub mod tags;
+        pub mod starred;
+        pub mod metadata;
+        pub mod batch;
+    }
+    pub mod admin {
+        pub mod users {
+            pub mod list;
+            pub mod get;
+            pub mod permission;
+            pub mod quota;
+            pub mod disable;
+            pub mod password_reset;
+            pub mod logout;
+        }
+        pub mod jobs {
+            pub mod list;
+            pub mod get;
+            pub mod retry;
+        }
+        pub mod audit;
+        pub mod authorize;
+    }
+    pub mod docs {
+        pub mod spec;
+    }
+    pub mod ratelimit;
+    pub mod request_id;
+    pub mod metrics;
+    pub mod health;
+    pub mod changes;
+    pub mod audit;
+    pub mod webhooks {
+        pub mod create;
+        pub mod list;
+        pub mod delete;
+        pub mod ping;
+        pub mod deliveries;
     }
 }
 
@@ -26,11 +81,86 @@ pub mod models {
     pub mod user;
     pub mod appstate;
     pub mod file;
+    pub mod batch;
+    pub mod change;
+    pub mod deletion;
+    pub mod email_change;
+    pub mod identity;
+    pub mod webhook;
+    pub mod job;
+    pub mod audit;
+}
+
+pub mod repository {
+    pub mod error;
+    pub mod user;
+    pub mod file;
+    pub mod webhook;
+    pub mod audit;
+    pub mod deletion;
+    pub mod email_change;
+    pub mod identity;
+    pub mod job;
+    pub mod batch;
+    pub mod memory;
+    pub mod postgres;
+}
+
+pub mod cli {
+    pub mod args;
+    pub mod users;
+    pub mod storage;
+    pub mod transfer;
+}
+
+pub mod jobs {
+    pub mod account_deletion;
+    pub mod audit;
+    pub mod batch;
+    pub mod metrics;
+    pub mod partial_files;
+    pub mod scan;
+    pub mod webhooks;
+    pub mod worker;
 }
 
 pub mod util {
     pub mod jwt {
         pub mod claims;
     }
+    pub mod ratelimit {
+        pub mod store;
+        pub mod memory;
+        pub mod postgres;
+    }
+    pub mod events {
+        pub mod bus;
+        pub mod memory;
+        pub mod postgres;
+    }
+    pub mod mail {
+        pub mod mailer;
+        pub mod log;
+        pub mod smtp;
+    }
+    pub mod scan {
+        pub mod scanner;
+        pub mod clamd;
+    }
     pub mod validation;
+    pub mod mime;
+    pub mod extract;
+    pub mod ip;
+    pub mod token;
+    pub mod metrics;
+    pub mod request_id;
+    pub mod conditional;
+    pub mod shutdown;
+    pub mod oidc;
+    pub mod outbound;
+    pub mod auth {
+        pub mod provider;
+        pub mod local;
+        pub mod ldap;
+    }
 }
\ No newline at end of file
@@ -1,97 +1,192 @@
-use axum::http::Method;
-use axum::routing::{delete, get, post, put};
-use axum::{middleware, Extension, Router};
-use axum_extra::extract::cookie::Key;
+use chrono::Utc;
+use clap::Parser;
 use dotenv::dotenv;
-use drive_lib::handlers::files::upload::stream_upload;
-use drive_lib::handlers::users::authenticate::auth;
-use drive_lib::handlers::users::login::login;
-use drive_lib::handlers::users::new::new;
-use drive_lib::handlers::users::refresh::refresh_token;
-use drive_lib::handlers::users::update;
-use drive_lib::models::appstate::{Appstate, AppstateWrapper};
+use drive_lib::app;
+use drive_lib::cli::args::{Cli, Command};
+use drive_lib::cli::{storage, transfer, users};
+use drive_lib::config::Config;
+use drive_lib::jobs::{metrics, partial_files, webhooks, worker};
+use drive_lib::migrate;
+use drive_lib::models::appstate::Appstate;
+use drive_lib::router;
+use drive_lib::util::auth::ldap::LdapAuthProvider;
+use drive_lib::util::oidc::OidcProvider;
+use drive_lib::util::shutdown;
 use sqlx::PgPool;
-use std::env;
+use std::future::IntoFuture;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use axum::extract::DefaultBodyLimit;
-use tower::ServiceBuilder;
-use tower_http::cors::{Any, CorsLayer};
-use tower_http::trace::TraceLayer;
-use drive_lib::handlers::files::delete::delete_file;
-use drive_lib::handlers::files::download::serve_file;
+use std::time::Duration;
+
+/// Time aborted requests get to clean up before the server is dropped
+const ABORT_GRACE_PERIOD: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() {
     // load env
     dotenv().ok();
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+
+    let config = match Config::load() {
+        Ok(o) => Arc::new(o),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let jwt_secret = env::var("JWT_SECRET").unwrap();
-    let cookie_secret = env::var("COOKIE_SECRET").unwrap();
-    let file_location = env::var("FILE_LOCATION").unwrap();
-    let psql_url = env::var("DATABASE_URL").unwrap();
+    // read before connecting, so a prompt isn't interrupted by connection errors
+    let password = match &command {
+        Command::CreateAdmin { .. } => match users::read_password() {
+            Ok(o) => o,
+            Err(e) => {
+                eprintln!("Failed to read password: {}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => String::new(),
+    };
 
     // db connection
-    let pool = PgPool::connect(&psql_url).await.unwrap();
-    let shared_pool = Arc::new(pool);
+    let pool = PgPool::connect(&config.database.url).await.unwrap();
 
+    if matches!(command, Command::Migrate) || config.database.migrate_on_startup {
+        if let Err(e) = migrate::run(&pool).await {
+            eprintln!("Failed to migrate database: {}", e);
+            std::process::exit(1);
+        }
+        // keep the output of the other commands clean
+        if matches!(command, Command::Migrate | Command::Serve) {
+            println!("Database is up to date");
+        }
+    }
 
     // appstate
-    let appstate = Arc::new(Appstate::new(
-        shared_pool,
-        jwt_secret,
-        Key::try_from(cookie_secret.as_bytes()).unwrap(),
-        file_location
-    ));
-    let wrapped_appstate = AppstateWrapper(appstate);
-
-    // set up http server
-    let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_origin(Any);
-
-    // axum
-    let protected_file_routes = Router::new()
-        .route("/upload", post(stream_upload))
-        .route("/download/{ref_id}", get(serve_file))
-        .route("/delete/{ref_id}", delete(delete_file))
-        .layer(
-            ServiceBuilder::new()
-                .layer(DefaultBodyLimit::max(2000000000))
-                .layer(middleware::from_fn(auth))
-                .layer(Extension(wrapped_appstate.clone()))
-        );
-
-
-
-    let protected_user_routes = Router::new()
-        .route("/password/change", put(update::password::change::change_password))
-        .route("/username/change", put(update::username::change::change_username))
-        .route("/refresh_token", get(refresh_token))
-        .layer(
-            ServiceBuilder::new()
-                .layer(middleware::from_fn(auth))
-                .layer(Extension(wrapped_appstate.clone()))
-        );
-
-    let public_user_routes = Router::new()
-        .route("/new", post(new))
-        .route("/login", post(login));
+    let appstate = match app::appstate(config.clone(), Arc::new(pool)) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let result = match command {
+        Command::Serve => return serve(appstate).await,
+        Command::Worker => return work(appstate).await,
+        Command::Migrate => Ok(()),
+        Command::CreateAdmin { username, email } => users::create_admin(username, email, password, &appstate).await,
+        Command::ResetPassword { username } => users::reset_password(&username, &appstate).await,
+        Command::Users => users::list(&appstate).await,
+        Command::VerifyStorage { fix } => storage::verify(fix, &appstate).await,
+        Command::Reindex => storage::reindex(&appstate).await,
+        Command::Scan => storage::scan(&appstate).await,
+        Command::Import { username, source } => transfer::import(&username, &source, &appstate).await,
+        Command::Export { username, target } => transfer::export(&username, &target, &appstate).await,
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Runs the queued jobs until a signal, running jobs get the drain timeout to finish
+async fn work(appstate: Appstate) {
+    let appstate = Arc::new(appstate);
+    let shutdown = appstate.shutdown.clone();
+    let timeout = Duration::from_secs(appstate.config.server.shutdown_timeout);
+    let mut worker = tokio::spawn(worker::run(appstate));
+    println!("Worker started");
+
+    tokio::select! {
+        _ = &mut worker => return,
+        _ = shutdown::signal() => shutdown.begin_drain(),
+    }
+
+    println!("Shutting down, waiting up to {}s for jobs to finish", timeout.as_secs());
+    if tokio::time::timeout(timeout, worker).await.is_err() {
+        eprintln!("Drain timeout reached, unfinished jobs are retried once their lease expired");
+    }
+}
+
+/// Runs the server with its background jobs until it's shut down
+async fn serve(mut appstate: Appstate) {
+    let config = appstate.config.clone();
+    let rate_limiter = appstate.rate_limiter.clone();
 
+    // periodically remove expired counters
+    let limits = config.rate_limit.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(600));
+        loop {
+            interval.tick().await;
+            let now = Utc::now().timestamp();
+            let failure_reset = limits.login_account.reset.max(limits.login_ip.reset);
+            if let Err(e) = rate_limiter.prune(now - limits.request_window, now - failure_reset).await {
+                eprintln!("Failed to prune rate limit counters: {}", e);
+            }
+        }
+    });
 
+    if let Some(oidc) = &config.oidc {
+        let provider = OidcProvider::discover(oidc.clone()).await.unwrap();
+        appstate = appstate.with_oidc(Arc::new(provider));
+    }
+    if let Some(ldap) = &config.ldap {
+        appstate = appstate.with_auth_provider(Arc::new(LdapAuthProvider::new(ldap.clone())));
+    }
+    let appstate = Arc::new(appstate);
+
+    // queued jobs like account purges, unless they run in a separate `drive worker`
+    if config.jobs.workers {
+        tokio::spawn(worker::run(appstate.clone()));
+    }
+
+    // retry failed webhook deliveries
+    tokio::spawn(webhooks::run(appstate.clone()));
+
+    // changes made on other instances, only needed by the postgres event bus
+    let events = appstate.events.clone();
+    tokio::spawn(async move { events.listen().await });
+
+    // uploads cut off by a crash leave their partial files behind
+    tokio::spawn(partial_files::run(appstate.clone()));
+
+    // storage gauges are expensive, so they're refreshed in the background
+    if config.metrics.enabled {
+        tokio::spawn(metrics::run(appstate.clone()));
+    }
 
     // set up axum
-    let app = Router::new()
-        .nest("/v1/file", protected_file_routes)
-        .nest("/v1/user", protected_user_routes)
-        .nest("/v1/user", public_user_routes)
-        .layer(
-            ServiceBuilder::new()
-                .layer(Extension(wrapped_appstate.clone()))
-                .layer(TraceLayer::new_for_http())
-                .layer(cors)
-        )
-        .with_state(wrapped_appstate.clone());
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let shutdown = appstate.shutdown.clone();
+    let app = router::app(appstate);
+
+    let listener = tokio::net::TcpListener::bind(&config.server.bind_address).await.unwrap();
+    let drain = shutdown.clone();
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            shutdown::signal().await;
+            drain.begin_drain();
+        });
+    let mut server = tokio::spawn(server.into_future());
+
+    tokio::select! {
+        result = &mut server => {
+            result.unwrap().unwrap();
+            return
+        },
+        _ = shutdown.drained() => {},
+    }
+
+    // new connections are refused, in-flight requests get the drain timeout to finish
+    let timeout = Duration::from_secs(config.server.shutdown_timeout);
+    println!("Shutting down, waiting up to {}s for requests to finish", timeout.as_secs());
+    if tokio::time::timeout(timeout, &mut server).await.is_err() {
+        eprintln!("Drain timeout reached, aborting remaining requests");
+        // aborted uploads remove their partial files
+        shutdown.abort();
+        if tokio::time::timeout(ABORT_GRACE_PERIOD, &mut server).await.is_err() {
+            server.abort();
+        }
+    }
 }
@@ -0,0 +1,47 @@
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::{Pool, Postgres};
+
+/// Migrations of `migrations/`, embedded at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Applies all pending migrations, already applied ones are skipped \
+/// Instances starting at the same time wait on a lock, so only one of them migrates
+pub async fn run(pool: &Pool<Postgres>) -> Result<(), MigrateError> {
+    adopt_untracked(pool).await?;
+    let mut connection = pool.acquire().await?;
+    let result = MIGRATOR.run(&mut *connection).await;
+    if result.is_err() {
+        // a failed run keeps holding the lock, closing the connection releases it
+        let _ = connection.close().await;
+    }
+    result
+}
+
+/// Databases set up by hand before migrations were tracked already have the schema of the first migration, \
+/// it's recorded as applied instead of running it again
+async fn adopt_untracked(pool: &Pool<Postgres>) -> Result<(), MigrateError> {
+    let (tracked, existing): (bool, bool) = sqlx::query_as(
+        "SELECT to_regclass('_sqlx_migrations') IS NOT NULL, to_regclass('\"users\"') IS NOT NULL",
+    )
+    .fetch_one(pool)
+    .await?;
+    if tracked || !existing {
+        return Ok(())
+    }
+    let Some(first) = MIGRATOR.iter().next() else {
+        return Ok(())
+    };
+
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+         VALUES ($1, $2, TRUE, $3, 0) ON CONFLICT DO NOTHING",
+    )
+    .bind(first.version)
+    .bind(&*first.description)
+    .bind(&*first.checksum)
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
@@ -1,4 +1,29 @@
+use crate::config::Config;
+use crate::repository::file::FileRepository;
+use crate::repository::audit::AuditRepository;
+use crate::repository::batch::BatchRepository;
+use crate::repository::deletion::DeletionRepository;
+use crate::repository::email_change::EmailChangeRepository;
+use crate::repository::identity::IdentityRepository;
+use crate::repository::job::JobRepository;
+use crate::repository::postgres::{
+    PgAuditRepository, PgBatchRepository, PgDeletionRepository, PgEmailChangeRepository, PgFileRepository, PgIdentityRepository,
+    PgJobRepository, PgUserRepository, PgWebhookRepository,
+};
+use crate::repository::user::UserRepository;
+use crate::repository::webhook::WebhookRepository;
 use axum::extract::FromRef;
+use crate::util::auth::local::LocalAuthProvider;
+use crate::util::auth::provider::AuthProvider;
+use crate::util::events::bus::EventBus;
+use crate::util::events::memory::MemoryEventBus;
+use crate::util::mail::mailer::Mailer;
+use crate::util::metrics::Metrics;
+use crate::util::oidc::OidcProvider;
+use crate::util::outbound::PublicResolver;
+use crate::util::shutdown::Shutdown;
+use crate::util::ratelimit::store::RateLimitStore;
+use crate::util::scan::scanner::Scanner;
 use axum_extra::extract::cookie::Key;
 use sqlx::{Pool, Postgres};
 use std::ops::Deref;
@@ -7,9 +32,33 @@ use std::sync::Arc;
 #[derive(Clone)]
 pub struct Appstate {
     pub(crate) db_pool: Arc<Pool<Postgres>>,
-    pub(crate) jwt_secret: String,
+    pub(crate) users: Arc<dyn UserRepository>,
+    pub(crate) files: Arc<dyn FileRepository>,
+    pub(crate) webhooks: Arc<dyn WebhookRepository>,
+    pub(crate) audit: Arc<dyn AuditRepository>,
+    pub(crate) deletions: Arc<dyn DeletionRepository>,
+    pub(crate) email_changes: Arc<dyn EmailChangeRepository>,
+    pub(crate) identities: Arc<dyn IdentityRepository>,
+    pub jobs: Arc<dyn JobRepository>,
+    pub config: Arc<Config>,
     pub(crate) cookie_secret: Key,
     pub file_location: String,
+    pub rate_limiter: Arc<dyn RateLimitStore>,
+    pub(crate) mailer: Arc<dyn Mailer>,
+    pub metrics: Arc<Metrics>,
+    pub shutdown: Arc<Shutdown>,
+    /// Single sign-on provider, None when not configured
+    pub(crate) oidc: Option<Arc<OidcProvider>>,
+    /// Providers asked in order when logging in with username and password
+    pub(crate) auth_providers: Vec<Arc<dyn AuthProvider>>,
+    /// Progress of file batches running in the background
+    pub(crate) batches: Arc<dyn BatchRepository>,
+    /// Journaled changes for the clients listening for events
+    pub events: Arc<dyn EventBus>,
+    /// Client of outgoing requests like webhook deliveries, doesn't follow redirects
+    pub(crate) http: reqwest::Client,
+    /// Malware scanner of uploads, None when not configured
+    pub(crate) scanner: Option<Arc<dyn Scanner>>,
 }
 
 /// This wrapper is used because the trait `axum_core::extract::from_ref` cannot be implemented for
@@ -18,14 +67,114 @@ pub struct Appstate {
 pub struct AppstateWrapper(pub Arc<Appstate>);
 
 impl Appstate {
-    pub fn new(db_pool: Arc<Pool<Postgres>>, jwt_secret: String, cookie_secret: Key, file_location: String) -> Self {
+    /// The local password provider is only added if `auth.local_login` is enabled \
+    /// Everything is kept in postgres unless other repositories are set
+    pub fn new(
+        db_pool: Arc<Pool<Postgres>>,
+        config: Arc<Config>,
+        cookie_secret: Key,
+        rate_limiter: Arc<dyn RateLimitStore>,
+        mailer: Arc<dyn Mailer>,
+    ) -> Self {
+        let mut auth_providers: Vec<Arc<dyn AuthProvider>> = Vec::new();
+        if config.auth.local_login {
+            auth_providers.push(Arc::new(LocalAuthProvider));
+        }
+
         Self {
+            users: Arc::new(PgUserRepository::new(db_pool.clone())),
+            files: Arc::new(PgFileRepository::new(db_pool.clone())),
+            webhooks: Arc::new(PgWebhookRepository::new(db_pool.clone())),
+            audit: Arc::new(PgAuditRepository::new(db_pool.clone())),
+            deletions: Arc::new(PgDeletionRepository::new(db_pool.clone())),
+            email_changes: Arc::new(PgEmailChangeRepository::new(db_pool.clone())),
+            identities: Arc::new(PgIdentityRepository::new(db_pool.clone())),
+            jobs: Arc::new(PgJobRepository::new(db_pool.clone())),
+            batches: Arc::new(PgBatchRepository::new(db_pool.clone())),
             db_pool,
-            jwt_secret,
+            file_location: config.storage.file_location.clone(),
+            // webhooks are the only outgoing requests
+            http: reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .dns_resolver(Arc::new(PublicResolver::new(config.webhooks.allowed_hosts.clone())))
+                .build()
+                .unwrap_or_default(),
+            config,
             cookie_secret,
-            file_location
+            rate_limiter,
+            mailer,
+            metrics: Arc::new(Metrics::new()),
+            shutdown: Arc::new(Shutdown::new()),
+            oidc: None,
+            auth_providers,
+            events: Arc::new(MemoryEventBus::new()),
+            scanner: None,
         }
     }
+
+    /// Replaces the repositories of users, files, webhooks and the audit log, e.g. with in-memory ones in tests
+    pub fn with_repositories(
+        mut self,
+        users: Arc<dyn UserRepository>,
+        files: Arc<dyn FileRepository>,
+        webhooks: Arc<dyn WebhookRepository>,
+        audit: Arc<dyn AuditRepository>,
+    ) -> Self {
+        self.users = users;
+        self.files = files;
+        self.webhooks = webhooks;
+        self.audit = audit;
+        self
+    }
+
+    /// Replaces the repositories of account deletions, email changes and linked identities
+    pub fn with_account_repositories(
+        mut self,
+        deletions: Arc<dyn DeletionRepository>,
+        email_changes: Arc<dyn EmailChangeRepository>,
+        identities: Arc<dyn IdentityRepository>,
+    ) -> Self {
+        self.deletions = deletions;
+        self.email_changes = email_changes;
+        self.identities = identities;
+        self
+    }
+
+    /// Replaces the job queue and the progress of background batches, the worker has to share them with the server
+    pub fn with_jobs(mut self, jobs: Arc<dyn JobRepository>, batches: Arc<dyn BatchRepository>) -> Self {
+        self.jobs = jobs;
+        self.batches = batches;
+        self
+    }
+
+    pub fn with_scanner(mut self, scanner: Arc<dyn Scanner>) -> Self {
+        self.scanner = Some(scanner);
+        self
+    }
+
+    /// Replaces the in-process event bus, e.g. with one shared between instances
+    pub fn with_events(mut self, events: Arc<dyn EventBus>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Replaces the mailer selected by the config
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
+
+    /// Enables single sign-on
+    pub fn with_oidc(mut self, provider: Arc<OidcProvider>) -> Self {
+        self.oidc = Some(provider);
+        self
+    }
+
+    /// Adds a provider that is asked after the already configured ones
+    pub fn with_auth_provider(mut self, provider: Arc<dyn AuthProvider>) -> Self {
+        self.auth_providers.push(provider);
+        self
+    }
 }
 
 impl Deref for AppstateWrapper {
@@ -0,0 +1,249 @@
+use crate::models::appstate::Appstate;
+use crate::models::user::User;
+use crate::util::ip::ClientInfo;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Most entries a single export contains, narrow down the time range for more
+pub const MAX_EXPORT: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Password and single sign-on logins, failed ones included
+    Login,
+    PasswordChange,
+    UsernameChange,
+    Upload,
+    Download,
+    Delete,
+    /// Admin actions, the target is the user they concern
+    AccountDisable,
+    AccountEnable,
+    PermissionChange,
+    PasswordReset,
+    QuotaChange,
+    ForcedLogout,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// Entry of the audit log, never changed once written
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Unset for failed logins of unknown usernames
+    pub actor_uuid: Option<Uuid>,
+    /// Username at the time, or the one a failed login tried
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    /// Reference of the file or uuid of the user the action concerns
+    pub target_uuid: Option<Uuid>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub outcome: AuditOutcome,
+    /// e.g. the filename or why the action failed
+    pub detail: Option<String>,
+    pub timestamp: usize,
+}
+
+/// Narrows down the audit log, unset fields match everything
+#[derive(Clone, Debug, Default)]
+pub struct AuditFilter {
+    pub actor_uuid: Option<Uuid>,
+    pub action: Option<AuditAction>,
+    pub target_uuid: Option<Uuid>,
+    pub outcome: Option<AuditOutcome>,
+    /// Unix timestamps, both inclusive
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    /// Only entries with a smaller id, for paging backwards
+    pub before: Option<i64>,
+}
+
+impl AuditEntry {
+    /// Entry without an actor, target or client, the id and timestamp are set when it's appended
+    pub fn new(action: AuditAction, outcome: AuditOutcome) -> Self {
+        Self {
+            id: 0,
+            actor_uuid: None,
+            actor: None,
+            action,
+            target_uuid: None,
+            ip: None,
+            user_agent: None,
+            outcome,
+            detail: None,
+            timestamp: Utc::now().timestamp() as usize,
+        }
+    }
+
+    pub fn actor(mut self, user: &User) -> Self {
+        self.actor_uuid = Some(user.uuid);
+        self.actor = Some(user.username.clone());
+        self
+    }
+
+    pub fn target(mut self, reference_uuid: Uuid) -> Self {
+        self.target_uuid = Some(reference_uuid);
+        self
+    }
+
+    pub fn client(mut self, client: &ClientInfo) -> Self {
+        self.ip = Some(client.ip.to_string());
+        self.user_agent = client.user_agent.clone();
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Maps PgRow to AuditEntry
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
+        let parse_error = |e: String| sqlx::Error::Decode(e.into());
+        Ok(Self {
+            id: row.try_get("id")?,
+            actor_uuid: row.try_get("actor_uuid")?,
+            actor: row.try_get("actor")?,
+            action: row.try_get::<String, _>("action")?.parse().map_err(parse_error)?,
+            target_uuid: row.try_get("target_uuid")?,
+            ip: row.try_get("ip")?,
+            user_agent: row.try_get("user_agent")?,
+            outcome: row.try_get::<String, _>("outcome")?.parse().map_err(parse_error)?,
+            detail: row.try_get("detail")?,
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
+        })
+    }
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor_uuid.is_none_or(|uuid| entry.actor_uuid == Some(uuid))
+            && self.action.is_none_or(|action| entry.action == action)
+            && self.target_uuid.is_none_or(|uuid| entry.target_uuid == Some(uuid))
+            && self.outcome.is_none_or(|outcome| entry.outcome == outcome)
+            && self.from.is_none_or(|from| entry.timestamp as i64 >= from)
+            && self.to.is_none_or(|to| entry.timestamp as i64 <= to)
+            && self.before.is_none_or(|before| entry.id < before)
+    }
+}
+
+/// Appends to the audit log \
+/// Failures are only logged as the action itself already happened
+pub async fn record(appstate: &Appstate, entry: AuditEntry) {
+    if let Err(e) = appstate.audit.append(&entry).await {
+        eprintln!("Failed to write {} audit entry of {:?}: {}", entry.action, entry.actor_uuid, e);
+    }
+}
+
+/// Renders entries as csv with a header row
+pub fn to_csv(entries: &[AuditEntry]) -> String {
+    let mut csv = String::from("id,timestamp,actor_uuid,actor,action,target_uuid,ip,user_agent,outcome,detail\r\n");
+    for entry in entries {
+        let fields = [
+            entry.id.to_string(),
+            DateTime::from_timestamp(entry.timestamp as i64, 0).map(|t| t.to_rfc3339()).unwrap_or_default(),
+            entry.actor_uuid.map(|u| u.to_string()).unwrap_or_default(),
+            entry.actor.clone().unwrap_or_default(),
+            entry.action.to_string(),
+            entry.target_uuid.map(|u| u.to_string()).unwrap_or_default(),
+            entry.ip.clone().unwrap_or_default(),
+            entry.user_agent.clone().unwrap_or_default(),
+            entry.outcome.to_string(),
+            entry.detail.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Quotes a field if needed and defuses values a spreadsheet would run as formula
+fn csv_field(value: &str) -> String {
+    let value = match value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        true => format!("'{}", value),
+        false => value.to_string(),
+    };
+    match value.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value,
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let action = match self {
+            AuditAction::Login => "login",
+            AuditAction::PasswordChange => "password_change",
+            AuditAction::UsernameChange => "username_change",
+            AuditAction::Upload => "upload",
+            AuditAction::Download => "download",
+            AuditAction::Delete => "delete",
+            AuditAction::AccountDisable => "account_disable",
+            AuditAction::AccountEnable => "account_enable",
+            AuditAction::PermissionChange => "permission_change",
+            AuditAction::PasswordReset => "password_reset",
+            AuditAction::QuotaChange => "quota_change",
+            AuditAction::ForcedLogout => "forced_logout",
+        };
+        write!(f, "{}", action)
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "login" => Ok(AuditAction::Login),
+            "password_change" => Ok(AuditAction::PasswordChange),
+            "username_change" => Ok(AuditAction::UsernameChange),
+            "upload" => Ok(AuditAction::Upload),
+            "download" => Ok(AuditAction::Download),
+            "delete" => Ok(AuditAction::Delete),
+            "account_disable" => Ok(AuditAction::AccountDisable),
+            "account_enable" => Ok(AuditAction::AccountEnable),
+            "permission_change" => Ok(AuditAction::PermissionChange),
+            "password_reset" => Ok(AuditAction::PasswordReset),
+            "quota_change" => Ok(AuditAction::QuotaChange),
+            "forced_logout" => Ok(AuditAction::ForcedLogout),
+            other => Err(format!("unknown audit action {}", other)),
+        }
+    }
+}
+
+impl Display for AuditOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let outcome = match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure => "failure",
+        };
+        write!(f, "{}", outcome)
+    }
+}
+
+impl FromStr for AuditOutcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(AuditOutcome::Success),
+            "failure" => Ok(AuditOutcome::Failure),
+            other => Err(format!("unknown audit outcome {}", other)),
+        }
+    }
+}
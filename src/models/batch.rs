@@ -0,0 +1,350 @@
+use crate::jobs::batch::RunBatch;
+use crate::jobs::scan::ScanFile;
+use crate::models::appstate::Appstate;
+use crate::models::audit::{self, AuditAction, AuditEntry, AuditOutcome};
+use crate::models::change::{self, ChangeKind};
+use crate::models::file::{normalize_tags, File, PartialFile, ScanStatus, MAX_TAGS};
+use crate::models::job::QueuedJob;
+use crate::models::user::User;
+use crate::repository::file::FileUpdate;
+use crate::util::ip::ClientInfo;
+use crate::util::validation;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::Row;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Batches with more operations run in the background and have to be polled
+pub const BACKGROUND_THRESHOLD: usize = 100;
+/// Most operations a single batch can have
+pub const MAX_OPERATIONS: usize = 10000;
+/// Seconds finished background batches can be polled
+const RETENTION: i64 = 3600;
+/// Operations of a background batch between two saves of its progress
+pub const PROGRESS_INTERVAL: usize = 50;
+
+/// Single operation of a batch, e.g. `{"op": "rename", "reference_uuid": "…", "filename": "a.txt"}`
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Delete { reference_uuid: Uuid },
+    /// Replaces the directory part of the filename, an empty directory moves the file to the top level
+    Move { reference_uuid: Uuid, directory: String },
+    /// Stores an independent copy with the same tags and metadata, keeps the filename unless one is given
+    Copy { reference_uuid: Uuid, filename: Option<String> },
+    Rename { reference_uuid: Uuid, filename: String },
+    /// Removals win over additions
+    Tag {
+        reference_uuid: Uuid,
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+}
+
+impl Operation {
+    pub fn reference_uuid(&self) -> Uuid {
+        match self {
+            Operation::Delete { reference_uuid }
+            | Operation::Move { reference_uuid, .. }
+            | Operation::Copy { reference_uuid, .. }
+            | Operation::Rename { reference_uuid, .. }
+            | Operation::Tag { reference_uuid, .. } => *reference_uuid,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    Ok,
+    /// The file doesn't exist or belongs to someone else
+    NotFound,
+    /// The operation failed validation, e.g. an empty filename
+    Invalid,
+    QuotaExceeded,
+    /// Unexpected failure, the operation may be retried
+    Failed,
+}
+
+/// Outcome of a single operation
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ItemResult {
+    /// Position of the operation in the request
+    pub index: usize,
+    pub reference_uuid: Uuid,
+    pub status: ItemStatus,
+    /// Reason if the operation didn't succeed
+    pub message: Option<String>,
+    /// Reference of the file created by a copy
+    pub copy_uuid: Option<Uuid>,
+}
+
+/// Failure of a single operation
+type ItemError = (ItemStatus, String);
+
+/// Batch running in the background, the job appends the results as it works through the operations
+#[derive(Clone, Debug)]
+pub struct Batch {
+    pub uuid: Uuid,
+    pub owner_uuid: Uuid,
+    pub total: usize,
+    /// Results of the completed operations, in order
+    pub results: Vec<ItemResult>,
+    pub finished: bool,
+    pub timestamp: usize,
+}
+
+impl Batch {
+    /// Maps PgRow to Batch
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            uuid: row.try_get("uuid")?,
+            owner_uuid: row.try_get("owner_uuid")?,
+            total: row.try_get::<i32, _>("total")? as usize,
+            results: row.try_get::<Json<Vec<ItemResult>>, _>("results")?.0,
+            finished: row.try_get("finished")?,
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
+        })
+    }
+}
+
+/// Stores the batch and queues the job working through it, returns the uuid it's polled with \
+/// Finished batches older than the retention are removed on the way
+pub async fn start(operations: Vec<Operation>, user: &User, client: &ClientInfo, appstate: &Appstate)
+    -> Result<Uuid, Box<dyn Error + Send + Sync>> {
+    appstate.batches.prune(Utc::now().timestamp() - RETENTION).await?;
+
+    let batch = Batch {
+        uuid: Uuid::new_v4(),
+        owner_uuid: user.uuid,
+        total: operations.len(),
+        results: Vec::new(),
+        finished: false,
+        timestamp: Utc::now().timestamp() as usize,
+    };
+    appstate.batches.insert(&batch).await?;
+    let job = RunBatch { batch_uuid: batch.uuid, owner_uuid: user.uuid, operations, client: client.clone() };
+    QueuedJob::enqueue(&job, None, None, appstate).await?;
+    Ok(batch.uuid)
+}
+
+/// Applies the operations of user in order, `first_index` is the position of the first one in the batch \
+/// Consecutive renames, moves and tags are written in one transaction, deletes and copies touch the disk and run on their own
+pub async fn execute(first_index: usize, operations: Vec<Operation>, user: &User, client: &ClientInfo, appstate: &Appstate)
+    -> Vec<ItemResult> {
+    let mut results = Vec::with_capacity(operations.len());
+    let mut pending = Pending::default();
+    for (index, operation) in (first_index..).zip(operations) {
+        let reference_uuid = operation.reference_uuid();
+        // None once the operation is queued for the transaction
+        let outcome = match operation {
+            Operation::Delete { .. } => {
+                results.append(&mut pending.apply(user, appstate).await);
+                Some(delete(reference_uuid, user, client, appstate).await.map(|_| None))
+            },
+            Operation::Copy { filename, .. } => {
+                results.append(&mut pending.apply(user, appstate).await);
+                Some(copy(reference_uuid, filename, user, appstate).await.map(Some))
+            },
+            Operation::Move { directory, .. } => pending.add_move(index, reference_uuid, &directory, user, appstate).await.err().map(Err),
+            Operation::Rename { filename, .. } => pending.add_rename(index, reference_uuid, filename, user, appstate).await.err().map(Err),
+            Operation::Tag { add, remove, .. } => pending.add_tags(index, reference_uuid, &add, &remove, user, appstate).await.err().map(Err),
+        };
+        if let Some(outcome) = outcome {
+            results.push(ItemResult::new(index, reference_uuid, outcome));
+        }
+    }
+    results.append(&mut pending.apply(user, appstate).await);
+    // failed validations are reported before the transaction they were left out of
+    results.sort_by_key(|result| result.index);
+    results
+}
+
+impl ItemResult {
+    /// `outcome` holds the reference of a copy if one was made
+    fn new(index: usize, reference_uuid: Uuid, outcome: Result<Option<Uuid>, ItemError>) -> Self {
+        let (status, message, copy_uuid) = match outcome {
+            Ok(copy_uuid) => (ItemStatus::Ok, None, copy_uuid),
+            Err((status, message)) => (status, Some(message), None),
+        };
+        Self { index, reference_uuid, status, message, copy_uuid }
+    }
+}
+
+/// Renames, moves and tags waiting to be written in one transaction
+#[derive(Default)]
+struct Pending {
+    /// Position of every update in the batch
+    indexes: Vec<usize>,
+    updates: Vec<FileUpdate>,
+    /// Names the files will have after the updates, moves of renamed files keep the new name
+    filenames: HashMap<Uuid, String>,
+}
+
+impl Pending {
+    async fn add_move(&mut self, index: usize, reference_uuid: Uuid, directory: &str, user: &User, appstate: &Appstate)
+        -> Result<(), ItemError> {
+        let current = self.filename(reference_uuid, user, appstate).await?;
+        let name = current.rsplit('/').next().unwrap_or_default();
+        let directory = directory.trim_matches('/');
+        let filename = if directory.is_empty() { name.to_string() } else { format!("{}/{}", directory, name) };
+        self.rename(index, reference_uuid, filename, ChangeKind::Moved)
+    }
+
+    async fn add_rename(&mut self, index: usize, reference_uuid: Uuid, filename: String, user: &User, appstate: &Appstate)
+        -> Result<(), ItemError> {
+        self.filename(reference_uuid, user, appstate).await?;
+        self.rename(index, reference_uuid, filename, ChangeKind::Renamed)
+    }
+
+    async fn add_tags(&mut self, index: usize, reference_uuid: Uuid, add: &[String], remove: &[String], user: &User, appstate: &Appstate)
+        -> Result<(), ItemError> {
+        self.filename(reference_uuid, user, appstate).await?;
+        let add = normalize_tags(add);
+        if let Some((tag, reason)) = add.iter().find_map(|tag| match validation::tag(tag) {
+            (false, reason) => Some((tag, reason)),
+            _ => None,
+        }) {
+            return Err((ItemStatus::Invalid, format!("{}: {}", tag, reason)))
+        }
+        self.indexes.push(index);
+        self.updates.push(FileUpdate::Tags { reference_uuid, add, remove: normalize_tags(remove), max_tags: MAX_TAGS });
+        Ok(())
+    }
+
+    /// Name of a file of user after the queued updates, fails if it doesn't exist
+    async fn filename(&self, reference_uuid: Uuid, user: &User, appstate: &Appstate) -> Result<String, ItemError> {
+        match self.filenames.get(&reference_uuid) {
+            Some(filename) => Ok(filename.clone()),
+            None => Ok(fetch(reference_uuid, user, appstate).await?.filename),
+        }
+    }
+
+    fn rename(&mut self, index: usize, reference_uuid: Uuid, filename: String, kind: ChangeKind) -> Result<(), ItemError> {
+        if let (false, reason) = validation::filename(&filename) {
+            return Err((ItemStatus::Invalid, reason))
+        }
+        self.filenames.insert(reference_uuid, filename.clone());
+        self.indexes.push(index);
+        self.updates.push(FileUpdate::Filename { reference_uuid, filename, kind });
+        Ok(())
+    }
+
+    /// Writes the queued updates and their changes, all of them fail if the transaction does
+    async fn apply(&mut self, user: &User, appstate: &Appstate) -> Vec<ItemResult> {
+        let indexes = std::mem::take(&mut self.indexes);
+        let updates = std::mem::take(&mut self.updates);
+        self.filenames.clear();
+        if updates.is_empty() {
+            return Vec::new()
+        }
+
+        let changes = match change::apply(appstate, user.uuid, &updates).await {
+            Ok(o) => o,
+            Err(e) => {
+                let failed = failed("Failed to write changes to db")(e);
+                return indexes.into_iter().zip(updates)
+                    .map(|(index, update)| ItemResult::new(index, update.reference_uuid(), Err(failed.clone())))
+                    .collect()
+            }
+        };
+
+        let mut results = Vec::with_capacity(updates.len());
+        for ((index, update), change) in indexes.into_iter().zip(updates).zip(changes) {
+            let outcome = match (change, &update) {
+                (Some(_), _) => Ok(None),
+                (None, FileUpdate::Tags { .. }) => Err((ItemStatus::Invalid, format!("Files can have at most {} tags", MAX_TAGS))),
+                // deleted since it was validated
+                (None, _) => Err((ItemStatus::NotFound, "File does not exist".to_string())),
+            };
+            results.push(ItemResult::new(index, update.reference_uuid(), outcome));
+        }
+        results
+    }
+}
+
+/// File of user the operation applies to
+async fn fetch(reference_uuid: Uuid, user: &User, appstate: &Appstate) -> Result<File, ItemError> {
+    appstate.files.get(reference_uuid, user.uuid).await
+        .map_err(failed("Failed to fetch file from db"))?
+        .ok_or((ItemStatus::NotFound, "File does not exist".to_string()))
+}
+
+async fn delete(reference_uuid: Uuid, user: &User, client: &ClientInfo, appstate: &Appstate) -> Result<(), ItemError> {
+    let file = fetch(reference_uuid, user, appstate).await?;
+    // a file missing on disk is gone already
+    match file.delete_from_disk().await {
+        Ok(_) => {},
+        Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == ErrorKind::NotFound) => {},
+        Err(e) => return Err(failed("Failed to delete from disk")(e)),
+    }
+    change::apply(appstate, user.uuid, &[FileUpdate::Delete { reference_uuid: file.reference_uuid }]).await
+        .map_err(failed("Failed to delete from db"))?;
+    let entry = AuditEntry::new(AuditAction::Delete, AuditOutcome::Success).actor(user).target(file.reference_uuid).client(client);
+    audit::record(appstate, entry.detail(file.filename)).await;
+    Ok(())
+}
+
+async fn copy(reference_uuid: Uuid, filename: Option<String>, user: &User, appstate: &Appstate) -> Result<Uuid, ItemError> {
+    let file = fetch(reference_uuid, user, appstate).await?;
+    if file.scan_status == ScanStatus::Infected {
+        return Err((ItemStatus::Invalid, "File is quarantined".to_string()))
+    }
+    let filename = filename.unwrap_or_else(|| file.filename.clone());
+    if let (false, reason) = validation::filename(&filename) {
+        return Err((ItemStatus::Invalid, reason))
+    }
+
+    let usage = appstate.files.usage(user.uuid).await
+        .map_err(failed("Failed to fetch storage usage"))?
+        .bytes;
+    if user.quota.is_some_and(|quota| usage + file.size > quota) {
+        return Err((ItemStatus::QuotaExceeded, "Storage quota exceeded".to_string()))
+    }
+
+    let mut copy = File::construct(None, filename, user, file.size, appstate).await
+        .ok_or((ItemStatus::Failed, "Failed to construct File".to_string()))?;
+    copy.mime_type = file.mime_type.clone();
+    copy.tags = file.tags.clone();
+    copy.metadata = file.metadata.clone();
+    copy.sha256 = file.sha256.clone();
+    // only a clean verdict carries over, everything else is scanned again like an upload
+    copy.scan_status = match (&appstate.scanner, file.scan_status) {
+        (Some(_), ScanStatus::Clean) => ScanStatus::Clean,
+        (Some(_), _) => ScanStatus::Pending,
+        (None, status) => status,
+    };
+
+    // removes the copy unless it's referenced in the db
+    let partial = PartialFile::new(&copy.absolute_path);
+    tokio::fs::copy(&file.absolute_path, &copy.absolute_path).await
+        .map_err(failed("Failed to copy file on disk"))?;
+    change::apply(appstate, user.uuid, &[FileUpdate::Insert(Box::new(copy.clone()))]).await
+        .map_err(failed("Failed to write to db"))?;
+    partial.keep();
+
+    if copy.scan_status == ScanStatus::Pending {
+        if let Err(e) = ScanFile::enqueue(copy.reference_uuid, copy.owner_uuid, appstate).await {
+            eprintln!("Failed to queue the scan of {}: {}", copy.reference_uuid, e);
+        }
+    } else if let Err(e) = copy.index(appstate).await {
+        eprintln!("Failed to index {}: {}", copy.reference_uuid, e);
+    }
+    Ok(copy.reference_uuid)
+}
+
+/// Logs the cause and returns a failure with message
+fn failed<E: std::fmt::Display>(message: &'static str) -> impl Fn(E) -> ItemError {
+    move |e| {
+        eprintln!("{}: {}", message, e);
+        (ItemStatus::Failed, message.to_string())
+    }
+}
@@ -0,0 +1,100 @@
+use crate::models::appstate::Appstate;
+use crate::models::webhook;
+use crate::repository::error::RepositoryError;
+use crate::repository::file::FileUpdate;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// Uploaded, imported or copied
+    Created,
+    Renamed,
+    /// Moved into another directory
+    Moved,
+    /// Tags, star, metadata or the content changed
+    Updated,
+    Deleted,
+}
+
+/// Entry of the change journal
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Change {
+    /// Increases with every change of the owner, used as cursor
+    pub id: i64,
+    #[serde(skip)]
+    pub owner_uuid: Uuid,
+    pub reference_uuid: Uuid,
+    pub kind: ChangeKind,
+    /// Filename after the change, only set if the change concerns the name
+    pub filename: Option<String>,
+    pub timestamp: usize,
+}
+
+impl Change {
+    /// Maps PgRow to Change
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            owner_uuid: row.try_get("owner_uuid")?,
+            reference_uuid: row.try_get("reference_uuid")?,
+            kind: row.try_get::<String, _>("kind")?.parse().map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+            filename: row.try_get("filename")?,
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
+        })
+    }
+}
+
+/// Applies updates of files of `owner_uuid` together with their journal entries and publishes the changes \
+/// Returns the change of every update, None if its file doesn't exist or it was left alone
+pub async fn apply(appstate: &Appstate, owner_uuid: Uuid, updates: &[FileUpdate]) -> Result<Vec<Option<Change>>, RepositoryError> {
+    let changes = appstate.files.apply(owner_uuid, updates).await?;
+    for change in changes.iter().flatten() {
+        publish(appstate, change).await;
+    }
+    Ok(changes)
+}
+
+/// Notifies connected clients of a journaled change and queues the webhooks subscribed to it
+pub async fn publish(appstate: &Appstate, change: &Change) {
+    // clients that miss the event catch up from the journal
+    if let Err(e) = appstate.events.publish(change).await {
+        eprintln!("Failed to publish change {}: {}", change.id, e);
+    }
+    webhook::notify(appstate, change).await;
+}
+
+impl Display for ChangeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Renamed => "renamed",
+            ChangeKind::Moved => "moved",
+            ChangeKind::Updated => "updated",
+            ChangeKind::Deleted => "deleted",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl FromStr for ChangeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(ChangeKind::Created),
+            "renamed" => Ok(ChangeKind::Renamed),
+            "moved" => Ok(ChangeKind::Moved),
+            "updated" => Ok(ChangeKind::Updated),
+            "deleted" => Ok(ChangeKind::Deleted),
+            other => Err(format!("unknown change kind {}", other)),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::models::appstate::Appstate;
+use crate::models::file::QUARANTINE_DIR;
+use crate::models::user::User;
+use crate::repository::file::FileFilter;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::error::Error;
+use std::io::ErrorKind;
+use uuid::Uuid;
+
+/// Files purged per batch, the lease is renewed after every batch
+const PURGE_BATCH_SIZE: usize = 500;
+/// Seconds a worker owns a deletion before another worker may resume it
+const LEASE_DURATION: i64 = 300;
+
+/// Pending deletion of an account and all of its data
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AccountDeletion {
+    pub user_uuid: Uuid,
+    /// Unix timestamp after which the deletion starts and can't be cancelled anymore
+    pub scheduled_for: i64,
+    pub lease_until: Option<i64>,
+    pub timestamp: usize,
+}
+
+impl AccountDeletion {
+    /// Maps PgRow to AccountDeletion
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            user_uuid: row.try_get("user_uuid")?,
+            scheduled_for: row.try_get::<DateTime<Utc>, _>("scheduled_for")?.timestamp(),
+            lease_until: row.try_get::<Option<DateTime<Utc>>, _>("lease_until")?.map(|t| t.timestamp()),
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
+        })
+    }
+
+    /// Schedules the deletion of user after `grace_period` seconds \
+    /// Scheduling again keeps the original schedule
+    pub async fn schedule(user: &User, grace_period: i64, appstate: &Appstate)
+        -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let scheduled_for = Utc::now().timestamp() + grace_period;
+        Ok(appstate.deletions.schedule(user.uuid, scheduled_for).await?)
+    }
+
+    /// Claims the deletion of a user if it's due and isn't owned by another worker
+    pub async fn claim(user_uuid: Uuid, appstate: &Appstate) -> Result<Option<Self>, Box<dyn Error + Send + Sync>> {
+        Ok(appstate.deletions.claim(user_uuid, LEASE_DURATION).await?)
+    }
+
+    /// Removes the user, all of their files on disk and in db and everything else tied to the account \
+    /// Works in batches and can be resumed after being interrupted, the deletion itself is removed last
+    pub async fn purge(&self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let user_uuid = self.user_uuid;
+
+        // lock the account out while it's being purged
+        let username = appstate.users.get(user_uuid).await?.map(|user| user.username);
+        appstate.users.set_disabled(user_uuid, true).await?;
+        appstate.users.set_tokenid(user_uuid, Uuid::new_v4()).await?;
+
+        // delete files in batches
+        loop {
+            appstate.deletions.renew(user_uuid, LEASE_DURATION).await?;
+
+            let files = appstate.files.list_filtered(user_uuid, &FileFilter::default(), PURGE_BATCH_SIZE, 0).await?;
+            if files.is_empty() {
+                break
+            }
+
+            for file in &files {
+                match tokio::fs::remove_file(&file.absolute_path).await {
+                    Ok(_) => {},
+                    Err(e) if e.kind() == ErrorKind::NotFound => {},
+                    Err(e) => return Err(e.into()),
+                }
+                appstate.files.delete(file.reference_uuid, user_uuid).await?;
+            }
+        }
+
+        // remove the user directories with anything left over
+        let quarantine_dir = format!("{}/{}/{}", appstate.file_location, QUARANTINE_DIR, user_uuid);
+        for dir in [format!("{}/{}", appstate.file_location, user_uuid), quarantine_dir] {
+            match tokio::fs::remove_dir_all(&dir).await {
+                Ok(_) => {},
+                Err(e) if e.kind() == ErrorKind::NotFound => {},
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // forget failed logins, request counters expire on their own
+        if let Some(username) = username {
+            let key = format!("login:user:{}", username.to_lowercase());
+            appstate.rate_limiter.reset_failures(&key).await?;
+        }
+
+        // remove the user and the deletion itself
+        appstate.email_changes.delete(user_uuid).await?;
+        appstate.identities.unlink_all(user_uuid).await?;
+        appstate.users.delete(user_uuid).await?;
+        appstate.deletions.delete(user_uuid).await?;
+        Ok(())
+    }
+}
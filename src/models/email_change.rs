@@ -0,0 +1,64 @@
+use crate::models::appstate::Appstate;
+use crate::models::user::User;
+use crate::repository::error::RepositoryError;
+use crate::util::token;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::error::Error;
+use uuid::Uuid;
+
+/// Pending change of a users email, waiting for the new address to be confirmed
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct EmailChange {
+    pub user_uuid: Uuid,
+    pub new_email: String,
+    pub expires: i64,
+}
+
+impl EmailChange {
+    /// Maps PgRow to EmailChange
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            user_uuid: row.try_get("user_uuid")?,
+            new_email: row.try_get("new_email")?,
+            expires: row.try_get::<DateTime<Utc>, _>("expires")?.timestamp(),
+        })
+    }
+
+    /// Stores a pending change for user, replacing any previous one \
+    /// Returns the confirmation token, only its hash is stored
+    pub async fn create(user: &User, new_email: String, appstate: &Appstate)
+        -> Result<String, Box<dyn Error + Send + Sync>> {
+        let token = token::generate();
+        let change = Self {
+            user_uuid: user.uuid,
+            new_email,
+            expires: Utc::now().timestamp() + appstate.config.account.email_token_lifetime,
+        };
+        appstate.email_changes.upsert(&change, &token::hash(&token)).await?;
+
+        Ok(token)
+    }
+
+    /// Consumes a confirmation token and applies the new email to the user \
+    /// Returns the applied change and the previous email, None if the token is unknown or expired \
+    /// Conflict if the new email got taken by another user in the meantime
+    pub async fn confirm(token: &str, appstate: &Appstate) -> Result<Option<(Self, String)>, RepositoryError> {
+        // taken first, so a token can't be used twice
+        let Some(change) = appstate.email_changes.take(&token::hash(token)).await? else {
+            return Ok(None)
+        };
+        if change.expires < Utc::now().timestamp() {
+            return Ok(None)
+        }
+
+        let Some(user) = appstate.users.get(change.user_uuid).await? else {
+            return Ok(None)
+        };
+        appstate.users.set_email(user.uuid, &change.new_email).await?;
+
+        Ok(Some((change, user.email)))
+    }
+}
@@ -1,14 +1,49 @@
 use crate::models::appstate::Appstate;
+use crate::models::change::Change;
 use crate::models::user::User;
-use chrono::Utc;
+use crate::repository::file::FileUpdate;
+use crate::util::scan::scanner::Verdict;
+use crate::util::{extract, mime};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::postgres::PgRow;
+use sqlx::types::Json;
 use sqlx::Row;
+use std::collections::{BTreeMap, HashSet};
 use std::error::Error;
-use std::path::Path;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 use tokio::io::AsyncWriteExt;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// Most tags a single file can have
+pub const MAX_TAGS: usize = 50;
+/// Most metadata entries a single file can have
+pub const MAX_METADATA: usize = 50;
+/// Directory below the file location infected files are moved to
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// Result of the virus scan of a file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanStatus {
+    /// Stored while scanning was disabled or before it was added
+    #[default]
+    Unscanned,
+    /// Waiting for the background scan
+    Pending,
+    Clean,
+    /// Quarantined and never served
+    Infected,
+    /// The scanner couldn't be reached or gave up, retried in the background
+    Failed,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct File {
     pub reference_uuid: Uuid,
@@ -19,6 +54,16 @@ pub struct File {
     pub absolute_path: String, /* Can't use Path as it doesn't implement Clone */
     /// Filesize in bytes
     pub size: usize,
+    /// Guessed from the filename unless the client sent a specific type
+    pub mime_type: String,
+    pub starred: bool,
+    /// Lowercase and sorted
+    pub tags: Vec<String>,
+    /// Arbitrary key-value pairs set by the owner
+    pub metadata: BTreeMap<String, String>,
+    /// Hex sha256 of the content, None for files stored before checksums were kept
+    pub sha256: Option<String>,
+    pub scan_status: ScanStatus,
 
     pub timestamp: usize,
 }
@@ -30,6 +75,12 @@ impl File {
         Self {
             reference_uuid,
             owner_uuid,
+            mime_type: mime::guess(&filename),
+            starred: false,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+            sha256: None,
+            scan_status: ScanStatus::Unscanned,
             filename,
             relative_path,
             absolute_path,
@@ -51,6 +102,12 @@ impl File {
         let file = Self {
             reference_uuid: ref_id,
             owner_uuid: user.uuid,
+            mime_type: mime::guess(&filename),
+            starred: false,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+            sha256: None,
+            scan_status: ScanStatus::Unscanned,
             filename,
             relative_path,
             absolute_path,
@@ -84,8 +141,8 @@ impl File {
             tokio::fs::create_dir(parent).await?;
         }
 
-        // check for file size under 100MB
-        if self.size > 100000000_usize { /* 100M bytes = 100 MB */
+        // check for file size under the configured maximum
+        if self.size > appstate.config.storage.max_file_size {
             return Ok(false)
         }
         Ok(true)
@@ -93,73 +150,26 @@ impl File {
 
     /// Maps PgRow to File \
     /// DOES NOT CHECK FOR VALIDATION
-    pub fn from_pg_row(row: PgRow) -> Result<Self, Box<dyn Error>> {
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
         Ok(Self {
-            reference_uuid: Uuid::parse_str(row.try_get("reference_uuid")?)?,
-            owner_uuid: Uuid::parse_str(row.try_get("owner_uuid")?)?,
+            reference_uuid: row.try_get("reference_uuid")?,
+            owner_uuid: row.try_get("owner_uuid")?,
             filename: row.try_get("filename")?,
             relative_path: row.try_get("relative_path")?,
             absolute_path: row.try_get("absolute_path")?,
             size: row.try_get::<i64, _>("size")? as usize,
-            timestamp: row.try_get::<i64, _>("timestamp")? as usize,
+            mime_type: row.try_get("mime_type")?,
+            starred: row.try_get("starred")?,
+            tags: row.try_get("tags")?,
+            metadata: row.try_get::<Json<_>, _>("metadata")?.0,
+            sha256: row.try_get("sha256")?,
+            scan_status: row.try_get::<String, _>("scan_status")?.parse()
+                .map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
         })
     }
 
 
-    /// writes self to db connection from appstate \
-    /// DOES NOT CHECK FOR VALIDATION
-    pub async fn write_to_db(&self, appstate: &Appstate) -> Result<(), Box<dyn Error>> {
-        let conn = &appstate.db_pool;
-
-        let query = r"INSERT INTO file (reference_uuid, owner_uuid, filename, relative_path, absolute_path, size)
-                         VALUES ($1, $2, $3, $4, $5, $6)";
-        let _query = sqlx::query(query)
-            .bind(&self.reference_uuid.to_string())
-            .bind(&self.owner_uuid.to_string())
-            .bind(&self.filename)
-            .bind(&self.relative_path)
-            .bind(&self.absolute_path)
-            .bind(self.size.clone() as i64)
-            .execute(conn.as_ref())
-            .await?;
-
-        Ok(())
-    }
-    /// retrieves self from db by reference uuid \
-    /// DOES NOT CHECK FOR VALIDATION
-    pub async fn get_from_db(
-        reference_uuid: Uuid,
-        owner_uuid: Uuid,
-        appstate: &Appstate,
-    ) -> Result<Self, Box<dyn Error>> {
-        let conn = &appstate.db_pool;
-
-        let query = r"SELECT * FROM file WHERE reference_uuid = $1 AND owner_uuid = $2";
-        let row = sqlx::query(query)
-            .bind(reference_uuid.to_string())
-            .bind(owner_uuid.to_string())
-            .fetch_one(conn.as_ref())
-            .await?;
-
-        let file = File::from_pg_row(row)?;
-        Ok(file)
-    }
-
-    /// deletes file row from db by reference_uuid, owner_uuid, and filename
-    pub async fn delete_from_db(&self, appstate: &Appstate) -> Result<(), Box<dyn Error>> {
-        let conn = &appstate.db_pool;
-
-        let query =
-            r"DELETE FROM file WHERE reference_uuid = $1 AND owner_uuid = $2 AND filename = $3";
-        let _query = sqlx::query(query)
-            .bind(&self.reference_uuid.to_string())
-            .bind(&self.owner_uuid.to_string())
-            .bind(&self.filename)
-            .execute(conn.as_ref())
-            .await?;
-
-        Ok(())
-    }
     /// NOT RECOMMENDED FOR LARGE FILES! use stream instead\
     /// DOES NOT CHECK FOR VALIDATION \
     /// writes file data to path specified in params
@@ -176,7 +186,7 @@ impl File {
             tokio::fs::create_dir_all(parent_path).await?
         }
 
-        let _ = tokio::fs::write(path, content).await?;
+        tokio::fs::write(path, content).await?;
         Ok(())
     }
 
@@ -197,13 +207,204 @@ impl File {
             .await?;
 
         // write
-        file_options.write(chunk).await?;
+        file_options.write_all(chunk).await?;
 
         Ok(())
     }
 
+    /// Stores the text of the file for the search \
+    /// Skipped for types without text and files above `search.max_extract_size`
+    pub async fn index(&self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.size > appstate.config.search.max_extract_size {
+            return Ok(())
+        }
+        if let Some(text) = extract::text(&self.absolute_path, &self.mime_type).await? {
+            appstate.files.set_text(self.reference_uuid, &text).await?;
+        }
+        Ok(())
+    }
+
+    /// Hashes the content on disk
+    pub async fn compute_sha256(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let path = self.absolute_path.clone();
+        let sha256 = tokio::task::spawn_blocking(move || -> std::io::Result<String> {
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+            Ok(hex::encode(hasher.finalize()))
+        }).await??;
+        Ok(sha256)
+    }
+
+    /// Returns the checksum, files without one are hashed and the result is stored
+    pub async fn ensure_sha256(&mut self, appstate: &Appstate) -> Result<String, Box<dyn Error + Send + Sync>> {
+        if let Some(sha256) = &self.sha256 {
+            return Ok(sha256.clone())
+        }
+        let sha256 = self.compute_sha256().await?;
+        appstate.files.set_sha256(self.reference_uuid, &sha256).await?;
+        self.sha256 = Some(sha256.clone());
+        Ok(sha256)
+    }
+
     pub async fn delete_from_disk(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
         tokio::fs::remove_file(Path::new(&self.absolute_path)).await?;
         Ok(())
     }
+
+    /// Scans the content with the configured scanner and quarantines infected files \
+    /// The verdict is journaled, its change is returned to be published \
+    /// A failed scan is stored as `failed` and returned, nothing happens without a scanner
+    pub async fn scan(&mut self, appstate: &Appstate) -> Result<Option<Change>, Box<dyn Error + Send + Sync>> {
+        let Some(scanner) = &appstate.scanner else {
+            return Ok(None)
+        };
+
+        // empty uploads aren't written to disk
+        let verdict = match self.size {
+            0 => Ok(Verdict::Clean),
+            _ => scanner.scan(Path::new(&self.absolute_path)).await,
+        };
+        let status = match verdict {
+            Ok(Verdict::Clean) => ScanStatus::Clean,
+            Ok(Verdict::Infected(signature)) => {
+                eprintln!("Found {} in {} of {}, quarantining it", signature, self.reference_uuid, self.owner_uuid);
+                ScanStatus::Infected
+            },
+            Err(e) => {
+                appstate.files.set_scan_status(self.reference_uuid, ScanStatus::Failed).await?;
+                self.scan_status = ScanStatus::Failed;
+                return Err(e)
+            },
+        };
+        // stored first, so an infected file isn't served even if moving it fails
+        let update = FileUpdate::Scanned { reference_uuid: self.reference_uuid, status };
+        let change = appstate.files.apply(self.owner_uuid, &[update]).await?.pop().flatten();
+        self.scan_status = status;
+        if status == ScanStatus::Infected {
+            self.quarantine(appstate).await?;
+        }
+        Ok(change)
+    }
+
+    /// Moves the file out of the storage of its owner, it can still be deleted
+    async fn quarantine(&mut self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let relative_path = format!("{}/{}/{}", QUARANTINE_DIR, self.owner_uuid, self.reference_uuid);
+        if self.relative_path == relative_path {
+            return Ok(())
+        }
+        let absolute_path = format!("{}/{}", appstate.file_location, relative_path);
+        if let Some(parent) = Path::new(&absolute_path).parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::rename(&self.absolute_path, &absolute_path).await?;
+        appstate.files.set_location(self.reference_uuid, &relative_path, &absolute_path).await?;
+        self.relative_path = relative_path;
+        self.absolute_path = absolute_path;
+        Ok(())
+    }
+}
+
+/// Trims and lowercases tags, sorted without duplicates as they're stored
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Guard of a file that is still being written \
+/// Removes it when dropped before `keep`, e.g. on errors, client disconnects or an aborted shutdown
+pub struct PartialFile {
+    path: String,
+    kept: bool,
+}
+
+impl PartialFile {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string(), kept: false }
+    }
+
+    /// The file is complete and referenced in the db
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if self.kept {
+            return
+        }
+        let path = std::mem::take(&mut self.path);
+        let remove = move || match std::fs::remove_file(&path) {
+            Ok(_) => {},
+            Err(e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => eprintln!("FATAL: DANGLING FILE: {}; ERROR: {}", path, e),
+        };
+        // drop can't be async, the removal mustn't block a runtime worker though
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(remove)),
+            Err(_) => remove(),
+        }
+    }
+}
+
+/// Files written more recently may belong to an upload that is still running
+pub const RECENT_WRITE: Duration = Duration::from_secs(3600);
+
+/// Files below `root` that aren't in `known` and weren't written within `RECENT_WRITE` \
+/// Returns their absolute and relative paths, e.g. partial files of uploads cut off by a crash
+pub async fn orphaned_files(root: &Path, known: &HashSet<String>) -> Result<Vec<(PathBuf, String)>, Box<dyn Error + Send + Sync>> {
+    let mut orphans = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+                continue
+            }
+
+            let relative_path = path.strip_prefix(root)?.to_string_lossy().replace('\\', "/");
+            if known.contains(&relative_path) {
+                continue
+            }
+            let modified = entry.metadata().await?.modified()?;
+            if SystemTime::now().duration_since(modified).unwrap_or_default() < RECENT_WRITE {
+                continue
+            }
+            orphans.push((path, relative_path));
+        }
+    }
+    Ok(orphans)
+}
+
+impl Display for ScanStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            ScanStatus::Unscanned => "unscanned",
+            ScanStatus::Pending => "pending",
+            ScanStatus::Clean => "clean",
+            ScanStatus::Infected => "infected",
+            ScanStatus::Failed => "failed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl FromStr for ScanStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unscanned" => Ok(ScanStatus::Unscanned),
+            "pending" => Ok(ScanStatus::Pending),
+            "clean" => Ok(ScanStatus::Clean),
+            "infected" => Ok(ScanStatus::Infected),
+            "failed" => Ok(ScanStatus::Failed),
+            other => Err(format!("unknown scan status {}", other)),
+        }
+    }
 }
@@ -0,0 +1,98 @@
+use crate::models::appstate::Appstate;
+use crate::models::user::{Permission, User};
+use crate::repository::error::RepositoryError;
+use crate::util::{token, validation};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHasher};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use uuid::Uuid;
+
+/// Attempts to find a free username when provisioning a user
+const USERNAME_ATTEMPTS: usize = 5;
+
+/// Account at an external identity provider linked to a local user
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ExternalIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub user_uuid: Uuid,
+}
+
+impl ExternalIdentity {
+    pub fn new(issuer: String, subject: String, user_uuid: Uuid) -> Self {
+        Self { issuer, subject, user_uuid }
+    }
+
+    /// Retrieves the user linked to the identity
+    pub async fn get_user(&self, appstate: &Appstate) -> Result<Option<User>, Box<dyn Error + Send + Sync>> {
+        match appstate.identities.user_uuid(&self.issuer, &self.subject).await? {
+            Some(user_uuid) => Ok(appstate.users.get(user_uuid).await?),
+            None => Ok(None),
+        }
+    }
+
+    /// Creates a local user for an identity that logs in for the first time and links both \
+    /// The user gets a random password, so it can only log in through the provider
+    pub async fn provision_user(
+        issuer: String,
+        subject: String,
+        preferred_username: Option<&str>,
+        email: Option<String>,
+        appstate: &Appstate,
+    ) -> Result<User, Box<dyn Error + Send + Sync>> {
+        let salt = SaltString::generate(&mut OsRng);
+        let password = Argon2::default()
+            .hash_password(token::generate().as_bytes(), &salt)
+            .map_err(|e| e.to_string())?
+            .to_string();
+
+        let base = username_base(preferred_username.or(email.as_deref()));
+        let mut username = base.clone();
+
+        for _ in 0..USERNAME_ATTEMPTS {
+            let user = User::new(
+                username.clone(),
+                password.clone(),
+                email.clone().unwrap_or_default(),
+                Permission::USER,
+            );
+
+            match appstate.users.insert(&user).await {
+                Ok(_) => {
+                    appstate.identities.link(&ExternalIdentity::new(issuer, subject, user.uuid)).await?;
+                    return Ok(user)
+                },
+                Err(RepositoryError::Conflict) => {
+                    // taken, try again with a random suffix
+                    let suffix = &token::generate()[..4];
+                    username = format!("{}_{}", &base[..base.len().min(11)], suffix);
+                },
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err("Failed to find a free username".into())
+    }
+}
+
+/// Derives a valid username from the name or email the provider sent
+fn username_base(name: Option<&str>) -> String {
+    let name = name.unwrap_or_default();
+    let name = name.split('@').next().unwrap_or_default();
+
+    let mut username: String = name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .take(16)
+        .collect();
+
+    if username.len() < 4 {
+        username = format!("user_{}", username);
+    }
+
+    match validation::username(&username) {
+        (true, _) => username,
+        (false, _) => format!("user_{}", &Uuid::new_v4().simple().to_string()[..8]),
+    }
+}
@@ -0,0 +1,273 @@
+use crate::models::appstate::Appstate;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Seconds a worker owns a job, renewed while the job runs
+pub const LEASE_DURATION: i64 = 60;
+/// Seconds before the first retry of a failed job, doubled for every further one
+const RETRY_BASE: i64 = 30;
+/// Upper bound of the delay between attempts
+const MAX_RETRY_DELAY: i64 = 3600;
+
+/// Work done outside of requests, stored as json so it survives restarts and can run on any worker
+#[async_trait]
+pub trait Job: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Stored with every job, has to stay the same across releases
+    const KIND: &'static str;
+    /// Attempts before the job is marked as failed
+    const MAX_ATTEMPTS: u32 = 5;
+
+    /// An error is retried with backoff, so jobs have to be safe to run again
+    async fn run(self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Waiting for `run_at`, also after a failed attempt that will be retried
+    Queued,
+    Running,
+    Succeeded,
+    /// Ran out of attempts
+    Failed,
+}
+
+/// Job as stored in the queue
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct QueuedJob {
+    pub id: i64,
+    pub kind: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// Unix timestamp the job is due at
+    pub run_at: i64,
+    /// Unix timestamp until which the worker running the job owns it
+    pub lease_until: Option<i64>,
+    /// Claim of the worker running the job
+    #[serde(skip)]
+    pub lease_token: Option<Uuid>,
+    /// Error of the latest failed attempt
+    pub last_error: Option<String>,
+    pub unique_key: Option<String>,
+    pub finished_at: Option<i64>,
+    pub timestamp: usize,
+}
+
+/// Number of jobs of a kind in a status
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct JobCount {
+    pub kind: String,
+    pub status: JobStatus,
+    pub count: i64,
+}
+
+type Handler = Box<dyn Fn(Arc<Appstate>, serde_json::Value) -> BoxFuture<'static, Result<(), Box<dyn Error + Send + Sync>>>
+    + Send + Sync>;
+
+/// When a recurring job runs, the next run is stored so restarts don't move it
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    /// Every that many seconds, counted from the previous run
+    Every(i64),
+    /// Times of a cron expression in UTC with a leading seconds field, e.g. `0 30 2 * * *` for 2:30 every day
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub fn cron(expression: &str) -> Result<Self, cron::error::Error> {
+        Ok(Self::Cron(Box::new(cron::Schedule::from_str(expression)?)))
+    }
+
+    /// Unix timestamp of the first run after `now`, unset if the expression has no further times
+    pub fn next_run(&self, now: i64) -> Option<i64> {
+        match self {
+            Self::Every(interval) => Some(now + interval),
+            Self::Cron(schedule) => {
+                let now = DateTime::from_timestamp(now, 0)?;
+                schedule.after(&now).next().map(|time| time.timestamp())
+            },
+        }
+    }
+
+    /// Intervals start with a run, cron expressions wait for their first time
+    pub fn due_when_new(&self) -> bool {
+        matches!(self, Self::Every(_))
+    }
+}
+
+/// Job enqueued by a schedule
+struct Recurring {
+    name: &'static str,
+    schedule: Schedule,
+    kind: &'static str,
+    payload: serde_json::Value,
+    max_attempts: u32,
+}
+
+/// Jobs a worker knows how to run, workers only claim jobs of registered kinds
+#[derive(Default)]
+pub struct Registry {
+    handlers: HashMap<&'static str, Handler>,
+    schedules: Vec<Recurring>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<J: Job>(mut self) -> Self {
+        self.handlers.insert(J::KIND, Box::new(|appstate, payload| Box::pin(async move {
+            let job: J = serde_json::from_value(payload)?;
+            job.run(&appstate).await
+        })));
+        self
+    }
+
+    /// Also registers the job, it's enqueued once per interval across all instances
+    pub fn every<J: Job>(self, interval: i64, job: J) -> Self {
+        self.at(Schedule::Every(interval), job)
+    }
+
+    /// Same as `every` at the times of a cron expression, panics if it's invalid
+    pub fn cron<J: Job>(self, expression: &str, job: J) -> Self {
+        let schedule = Schedule::cron(expression).unwrap_or_else(|e| panic!("invalid cron expression {}: {}", expression, e));
+        self.at(schedule, job)
+    }
+
+    fn at<J: Job>(mut self, schedule: Schedule, job: J) -> Self {
+        self.schedules.push(Recurring {
+            name: J::KIND,
+            schedule,
+            kind: J::KIND,
+            payload: serde_json::to_value(job).expect("jobs serialize to json"),
+            max_attempts: J::MAX_ATTEMPTS,
+        });
+        self.register::<J>()
+    }
+
+    pub fn kinds(&self) -> Vec<String> {
+        self.handlers.keys().map(|kind| kind.to_string()).collect()
+    }
+
+    pub async fn run(&self, job: &QueuedJob, appstate: Arc<Appstate>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let handler = self.handlers.get(job.kind.as_str()).ok_or("job kind isn't registered")?;
+        handler(appstate, job.payload.clone()).await
+    }
+
+    /// Enqueues the recurring jobs that are due, the job of a schedule isn't queued twice
+    pub async fn enqueue_due(&self, appstate: &Appstate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for recurring in &self.schedules {
+            let now = Utc::now().timestamp();
+            let Some(next_run) = recurring.schedule.next_run(now) else {
+                continue
+            };
+            if appstate.jobs.schedule_due(recurring.name, next_run, recurring.schedule.due_when_new()).await? {
+                appstate.jobs.insert(recurring.kind, &recurring.payload, recurring.max_attempts, now, Some(recurring.name)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl QueuedJob {
+    /// Maps PgRow to QueuedJob
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            kind: row.try_get("kind")?,
+            payload: row.try_get("payload")?,
+            status: row.try_get::<String, _>("status")?.parse()
+                .map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+            attempts: row.try_get::<i32, _>("attempts")? as u32,
+            max_attempts: row.try_get::<i32, _>("max_attempts")? as u32,
+            run_at: row.try_get::<DateTime<Utc>, _>("run_at")?.timestamp(),
+            lease_until: row.try_get::<Option<DateTime<Utc>>, _>("lease_until")?.map(|t| t.timestamp()),
+            lease_token: row.try_get("lease_token")?,
+            last_error: row.try_get("last_error")?,
+            unique_key: row.try_get("unique_key")?,
+            finished_at: row.try_get::<Option<DateTime<Utc>>, _>("finished_at")?.map(|t| t.timestamp()),
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
+        })
+    }
+
+    /// Queues a job that is due at `run_at`, right away without it \
+    /// Returns None if a job with the same `unique_key` is already queued
+    pub async fn enqueue<J: Job>(job: &J, run_at: Option<DateTime<Utc>>, unique_key: Option<&str>, appstate: &Appstate)
+        -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
+        let payload = serde_json::to_value(job)?;
+        let run_at = run_at.unwrap_or_else(Utc::now).timestamp();
+        Ok(appstate.jobs.insert(J::KIND, &payload, J::MAX_ATTEMPTS, run_at, unique_key).await?)
+    }
+
+    /// Claims a due job of one of `kinds` that isn't owned by another worker and counts the attempt
+    pub async fn claim(kinds: &[String], appstate: &Appstate) -> Result<Option<Self>, Box<dyn Error + Send + Sync>> {
+        Ok(appstate.jobs.claim(kinds, LEASE_DURATION).await?)
+    }
+
+    /// Methods below return false if another worker claimed the job in the meantime
+    pub async fn renew_lease(&self, appstate: &Appstate) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        Ok(appstate.jobs.renew(self.id, self.lease()?, LEASE_DURATION).await?)
+    }
+
+    pub async fn succeed(&self, appstate: &Appstate) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        Ok(appstate.jobs.succeed(self.id, self.lease()?).await?)
+    }
+
+    /// Queues the job again with backoff, or marks it as failed once it ran out of attempts
+    pub async fn fail(&self, error: &str, appstate: &Appstate) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        // doubles with every attempt
+        let delay = (RETRY_BASE << (self.attempts.clamp(1, 32) - 1)).min(MAX_RETRY_DELAY);
+        let retry_at = match self.attempts >= self.max_attempts {
+            true => None,
+            false => Some(Utc::now().timestamp() + delay),
+        };
+        Ok(appstate.jobs.fail(self.id, self.lease()?, error, retry_at).await?)
+    }
+
+    /// Only claimed jobs have a lease
+    fn lease(&self) -> Result<Uuid, &'static str> {
+        self.lease_token.ok_or("job wasn't claimed")
+    }
+}
+
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "succeeded" => Ok(JobStatus::Succeeded),
+            "failed" => Ok(JobStatus::Failed),
+            other => Err(format!("unknown job status {}", other)),
+        }
+    }
+}
@@ -1,17 +1,20 @@
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use crate::error::ApiError;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use async_trait::async_trait;
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
-use axum::http::StatusCode;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
 use sqlx::{Row, Type};
-use std::error::Error;
 use std::future::{ready, Future};
+use std::sync::LazyLock;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Debug, Type, Clone, Serialize, Deserialize)]
+#[derive(Debug, Type, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[sqlx(type_name = "permission")]
 pub enum Permission{
     USER,
@@ -28,11 +31,41 @@ pub struct User {
     pub(crate) permission: Permission,
     pub(crate) tokenid: Uuid,
     pub(crate) timestamp: usize,
+
+    /// Storage quota in bytes, None means unlimited
+    pub(crate) quota: Option<usize>,
+    pub(crate) disabled: bool,
+    /// User has to change their password before doing anything else
+    pub(crate) password_reset: bool,
 }
+/// Hash of a random password, verified against when a user doesn't exist
+/// so unknown usernames take as long as wrong passwords
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(Uuid::new_v4().to_string().as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .unwrap_or_default()
+});
+
 // for passing user data to next handler with auth middleware
 #[derive(Clone)]
 pub struct AuthUser(pub User);
 
+/// Token the request was authenticated with, passed next to `AuthUser`
+#[derive(Clone, Copy)]
+pub struct Session {
+    /// Unix timestamp of the login
+    pub auth_time: usize,
+}
+
+impl Session {
+    /// Logged in within the last `window` seconds
+    pub fn is_recent(&self, window: i64) -> bool {
+        chrono::Utc::now().timestamp() - (self.auth_time as i64) < window
+    }
+}
+
 
 impl User {
     pub fn new(username: String, password: String, email: String, permission: Permission) -> Self {
@@ -44,18 +77,24 @@ impl User {
             permission,
             tokenid: Uuid::new_v4(),
             timestamp: Utc::now().timestamp() as usize,
+            quota: None,
+            disabled: false,
+            password_reset: false,
         }
     }
     /// Maps PgRow to User
-    pub fn from_pg_row(row: PgRow) -> Result<User, Box<dyn Error>> {
+    pub fn from_pg_row(row: PgRow) -> Result<User, sqlx::Error> {
         Ok(User{
-            uuid: Uuid::parse_str(row.try_get("uuid")?)?,
+            uuid: row.try_get("uuid")?,
             username: row.try_get("username")?,
             password: row.try_get("password")?,
             email: row.try_get("email")?,
             permission: row.try_get("permission")?,
-            tokenid: Uuid::parse_str(row.try_get("tokenid")?)?,
-            timestamp: row.try_get::<i64, _>("timestamp")? as usize,
+            tokenid: row.try_get("tokenid")?,
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
+            quota: row.try_get::<Option<i64>, _>("quota")?.map(|q| q as usize),
+            disabled: row.try_get("disabled")?,
+            password_reset: row.try_get("password_reset")?,
         })
     }
     /// Compares hashed password from self with un-hashed attempt
@@ -65,6 +104,25 @@ impl User {
         let argon2 = Argon2::default();
         Ok(argon2.verify_password(attempt.as_bytes(), &parsed_hash).is_ok())
     }
+
+    /// Verifies attempt against a dummy hash, always false \
+    /// Used to keep the timing of logins for unknown users the same as for known ones
+    pub fn compare_dummy_password(attempt: String) -> Result<bool, argon2::password_hash::Error> {
+        let parsed_hash = PasswordHash::new(&DUMMY_HASH)?;
+        let argon2 = Argon2::default();
+        let _ = argon2.verify_password(attempt.as_bytes(), &parsed_hash);
+        Ok(false)
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.permission == Permission::ADMIN
+    }
+
+    /// Hashes a password with argon2 and a random salt
+    pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(Argon2::default().hash_password(password.as_bytes(), &salt)?.to_string())
+    }
 }
 
 #[async_trait]
@@ -72,7 +130,7 @@ impl<S> FromRequestParts<S> for AuthUser
 where
     S: Send + Sync
 {
-    type Rejection = StatusCode;
+    type Rejection = ApiError;
 
     fn from_request_parts(
         parts: &mut Parts,
@@ -83,7 +141,7 @@ where
             .get::<User>()
             .cloned()
             .map(AuthUser)
-            .ok_or(StatusCode::IM_A_TEAPOT);
+            .ok_or(ApiError::Unauthorized("Not logged in"));
 
         ready(user)
     }
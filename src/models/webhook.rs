@@ -0,0 +1,238 @@
+use crate::models::appstate::Appstate;
+use crate::models::change::{Change, ChangeKind};
+use crate::util::outbound;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Most webhooks a single user can have
+pub const MAX_WEBHOOKS: usize = 20;
+/// Events a webhook can subscribe to \
+/// There are no shares in drive, so there is no `share.created` either
+pub const EVENTS: [&str; 5] = ["file.uploaded", "file.renamed", "file.moved", "file.updated", "file.deleted"];
+/// Sent by the test ping, webhooks can't subscribe to it
+pub const PING: &str = "ping";
+/// Seconds a worker owns a delivery before another one may retry it
+pub const LEASE_DURATION: i64 = 300;
+/// Upper bound of the delay between attempts
+const MAX_RETRY_DELAY: u64 = 6 * 3600;
+
+/// Subscription of a user to file events
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Webhook {
+    pub uuid: Uuid,
+    pub owner_uuid: Uuid,
+    pub url: String,
+    /// Key of the HMAC signature of every payload
+    pub secret: String,
+    /// Sorted without duplicates
+    pub events: Vec<String>,
+    pub timestamp: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Waiting for its next attempt
+    Pending,
+    Delivered,
+    /// Ran out of attempts
+    Failed,
+}
+
+/// Queued payload of a webhook and the outcome of its latest attempt
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Delivery {
+    pub id: i64,
+    pub webhook_uuid: Uuid,
+    pub event: String,
+    /// Json body exactly as sent
+    pub payload: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// Http status the receiver answered the latest attempt with
+    pub response_status: Option<u16>,
+    /// Why the latest attempt failed
+    pub error: Option<String>,
+    /// Unix timestamp of the next attempt of a pending delivery
+    pub next_attempt: i64,
+    pub timestamp: usize,
+}
+
+/// Outcome of a single attempt
+pub struct Attempt {
+    pub status: DeliveryStatus,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    /// Unix timestamp, only used for pending deliveries
+    pub next_attempt: i64,
+}
+
+impl Webhook {
+    pub fn new(owner_uuid: Uuid, url: String, secret: String, events: Vec<String>) -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            owner_uuid,
+            url,
+            secret,
+            events,
+            timestamp: Utc::now().timestamp() as usize,
+        }
+    }
+
+    /// Maps PgRow to Webhook
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            uuid: row.try_get("uuid")?,
+            owner_uuid: row.try_get("owner_uuid")?,
+            url: row.try_get("url")?,
+            secret: row.try_get("secret")?,
+            events: row.try_get("events")?,
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
+        })
+    }
+}
+
+impl Delivery {
+    /// Maps PgRow to Delivery
+    pub fn from_pg_row(row: PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            webhook_uuid: row.try_get("webhook_uuid")?,
+            event: row.try_get("event")?,
+            payload: row.try_get("payload")?,
+            status: row.try_get::<String, _>("status")?.parse().map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+            attempts: row.try_get::<i32, _>("attempts")? as u32,
+            response_status: row.try_get::<Option<i32>, _>("response_status")?.map(|s| s as u16),
+            error: row.try_get("error")?,
+            next_attempt: row.try_get::<DateTime<Utc>, _>("next_attempt")?.timestamp(),
+            timestamp: row.try_get::<DateTime<Utc>, _>("timestamp")?.timestamp() as usize,
+        })
+    }
+}
+
+/// Name of the event a change is delivered as
+pub fn event_of(kind: ChangeKind) -> &'static str {
+    match kind {
+        ChangeKind::Created => "file.uploaded",
+        ChangeKind::Renamed => "file.renamed",
+        ChangeKind::Moved => "file.moved",
+        ChangeKind::Updated => "file.updated",
+        ChangeKind::Deleted => "file.deleted",
+    }
+}
+
+/// Queues the change for every webhook of its owner subscribed to it \
+/// Failures are only logged as the change itself already happened
+pub async fn notify(appstate: &Appstate, change: &Change) {
+    let event = event_of(change.kind);
+    let webhooks = match appstate.webhooks.subscribed(change.owner_uuid, event).await {
+        Ok(o) => o,
+        Err(e) => return eprintln!("Failed to fetch webhooks of {}: {}", change.owner_uuid, e),
+    };
+    for webhook in webhooks {
+        if let Err(e) = enqueue(appstate, &webhook, event, json!(change)).await {
+            eprintln!("Failed to queue {} for webhook {}: {}", event, webhook.uuid, e);
+        }
+    }
+}
+
+/// Queues a payload and attempts the first delivery right away, the worker retries failed ones
+pub async fn enqueue(appstate: &Appstate, webhook: &Webhook, event: &str, data: serde_json::Value)
+    -> Result<Delivery, Box<dyn std::error::Error + Send + Sync>> {
+    let payload = json!({ "event": event, "timestamp": Utc::now().timestamp(), "data": data }).to_string();
+    let delivery = appstate.webhooks.enqueue(webhook.uuid, event, &payload).await?;
+
+    let appstate = appstate.clone();
+    let id = delivery.id;
+    tokio::spawn(async move {
+        if let Err(e) = attempt(&appstate, Some(id)).await {
+            eprintln!("Failed to attempt delivery {}: {}", id, e);
+        }
+    });
+    Ok(delivery)
+}
+
+/// Claims a due delivery, `id` claims a specific one, and sends it \
+/// Returns false if there was nothing to deliver
+pub async fn attempt(appstate: &Appstate, id: Option<i64>) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let Some((delivery, webhook)) = appstate.webhooks.claim(id, LEASE_DURATION).await? else {
+        return Ok(false)
+    };
+
+    let timestamp = Utc::now().timestamp();
+    // the resolver of the client only sees host names, addresses written into the url are checked here
+    let url = reqwest::Url::parse(&webhook.url)?;
+    let result = match outbound::check_url(&url, &appstate.config.webhooks.allowed_hosts).await {
+        Ok(()) => appstate.http.post(url)
+            .timeout(Duration::from_secs(appstate.config.webhooks.timeout))
+            .header("content-type", "application/json")
+            .header("x-drive-event", &delivery.event)
+            .header("x-drive-delivery", delivery.id)
+            .header("x-drive-timestamp", timestamp)
+            .header("x-drive-signature", format!("sha256={}", sign(&webhook.secret, timestamp, &delivery.payload)))
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+
+    let (response_status, error) = match result {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+        Ok(response) => (Some(response.status().as_u16()), Some(format!("Receiver answered with {}", response.status()))),
+        Err(e) => (None, Some(e)),
+    };
+
+    let config = &appstate.config.webhooks;
+    let status = match &error {
+        None => DeliveryStatus::Delivered,
+        Some(_) if delivery.attempts >= config.max_attempts => DeliveryStatus::Failed,
+        Some(_) => DeliveryStatus::Pending,
+    };
+    // doubles with every attempt
+    let delay = config.retry_base.saturating_mul(1 << (delivery.attempts.clamp(1, 32) - 1)).min(MAX_RETRY_DELAY);
+    let attempt = Attempt { status, response_status, error, next_attempt: timestamp + delay as i64 };
+    appstate.webhooks.finish(delivery.id, &attempt).await?;
+    Ok(true)
+}
+
+/// Hex HMAC-SHA256 of `{timestamp}.{payload}`, receivers should also reject old timestamps
+pub fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+impl Display for DeliveryStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl FromStr for DeliveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "failed" => Ok(DeliveryStatus::Failed),
+            other => Err(format!("unknown delivery status {}", other)),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::error::{ErrorBody, FieldError};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// Base of the generated specification \
+/// Paths are collected from the `#[utoipa::path]` handlers registered in `router::app`
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Drive", description = "Self-hosted drive"),
+    tags(
+        (name = "users", description = "Accounts, login and profile"),
+        (name = "files", description = "Upload, download and deletion of files"),
+        (name = "webhooks", description = "Signed notifications about file events sent to user defined urls"),
+        (name = "admin", description = "User management, admin permission required"),
+    ),
+    components(schemas(ErrorBody, FieldError)),
+    modifiers(&CookieAuth)
+)]
+pub struct ApiDoc;
+
+/// Login token set as private `token` cookie by the login routes
+struct CookieAuth;
+
+impl Modify for CookieAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("token")))
+        );
+    }
+}
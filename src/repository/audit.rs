@@ -0,0 +1,16 @@
+use crate::models::audit::{AuditEntry, AuditFilter};
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+
+/// Storage of the audit log, entries are only appended and pruned
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    /// Ignores the id and timestamp of `entry`, both are assigned on write
+    async fn append(&self, entry: &AuditEntry) -> Result<(), RepositoryError>;
+
+    /// Entries matching `filter`, newest first
+    async fn query(&self, filter: &AuditFilter, limit: usize) -> Result<Vec<AuditEntry>, RepositoryError>;
+
+    /// Deletes entries written before the unix timestamp, returns how many
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError>;
+}
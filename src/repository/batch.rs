@@ -0,0 +1,21 @@
+use crate::models::batch::{Batch, ItemResult};
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Storage of the progress of background batches, shared by the server and the workers
+#[async_trait]
+pub trait BatchRepository: Send + Sync {
+    async fn insert(&self, batch: &Batch) -> Result<(), RepositoryError>;
+
+    /// Only returns batches of `owner_uuid`
+    async fn get(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<Option<Batch>, RepositoryError>;
+
+    /// Appends the results of the next operations
+    async fn record(&self, uuid: Uuid, results: &[ItemResult]) -> Result<(), RepositoryError>;
+
+    async fn finish(&self, uuid: Uuid) -> Result<(), RepositoryError>;
+
+    /// Removes finished batches started before the unix timestamp `before`, returns how many were removed
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError>;
+}
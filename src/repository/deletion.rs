@@ -0,0 +1,28 @@
+use crate::models::deletion::AccountDeletion;
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Storage of scheduled account deletions, at most one per user
+#[async_trait]
+pub trait DeletionRepository: Send + Sync {
+    async fn get(&self, user_uuid: Uuid) -> Result<Option<AccountDeletion>, RepositoryError>;
+
+    /// Schedules the deletion for the unix timestamp, an existing deletion keeps its schedule and is returned
+    async fn schedule(&self, user_uuid: Uuid, scheduled_for: i64) -> Result<AccountDeletion, RepositoryError>;
+
+    /// Only cancels deletions that are neither due nor owned by a worker, returns false if there was none
+    async fn cancel(&self, user_uuid: Uuid) -> Result<bool, RepositoryError>;
+
+    /// Due deletions that aren't owned by a worker, oldest schedule first
+    async fn due(&self) -> Result<Vec<AccountDeletion>, RepositoryError>;
+
+    /// Takes the deletion if it's due and isn't owned by another worker \
+    /// Nobody else can claim it for `lease` seconds, so a crashed worker only delays it
+    async fn claim(&self, user_uuid: Uuid, lease: i64) -> Result<Option<AccountDeletion>, RepositoryError>;
+
+    async fn renew(&self, user_uuid: Uuid, lease: i64) -> Result<(), RepositoryError>;
+
+    /// Removes the deletion once the account is purged
+    async fn delete(&self, user_uuid: Uuid) -> Result<(), RepositoryError>;
+}
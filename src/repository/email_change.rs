@@ -0,0 +1,20 @@
+use crate::models::email_change::EmailChange;
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Storage of pending email changes, at most one per user \
+/// Changes are found by the hash of their confirmation token, the token itself isn't stored
+#[async_trait]
+pub trait EmailChangeRepository: Send + Sync {
+    /// Replaces a pending change of the same user
+    async fn upsert(&self, change: &EmailChange, token_hash: &str) -> Result<(), RepositoryError>;
+
+    /// Pending change of a user, expired ones are left out
+    async fn get(&self, user_uuid: Uuid) -> Result<Option<EmailChange>, RepositoryError>;
+
+    /// Removes the change of the token and returns it, also if it has expired
+    async fn take(&self, token_hash: &str) -> Result<Option<EmailChange>, RepositoryError>;
+
+    async fn delete(&self, user_uuid: Uuid) -> Result<(), RepositoryError>;
+}
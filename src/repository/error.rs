@@ -0,0 +1,37 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Error of every repository method
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// Collides with an existing entry, e.g. a taken username
+    Conflict,
+    /// Failure of the backing store
+    Backend(Box<dyn Error + Send + Sync>),
+}
+
+impl Display for RepositoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepositoryError::Conflict => write!(f, "entry already exists"),
+            RepositoryError::Backend(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RepositoryError {}
+
+impl From<sqlx::Error> for RepositoryError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => RepositoryError::Conflict,
+            e => RepositoryError::Backend(e.into()),
+        }
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for RepositoryError {
+    fn from(e: std::sync::PoisonError<T>) -> Self {
+        RepositoryError::Backend(e.to_string().into())
+    }
+}
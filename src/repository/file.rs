@@ -0,0 +1,130 @@
+use crate::models::change::{Change, ChangeKind};
+use crate::models::file::{File, ScanStatus};
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Amount and size of stored files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Bytes of all files
+    pub bytes: usize,
+    pub files: usize,
+}
+
+/// Narrows down the files of a user, unset fields match everything
+#[derive(Clone, Debug, Default)]
+pub struct FileFilter {
+    /// Lowercase like the stored tags
+    pub tag: Option<String>,
+    pub starred: Option<bool>,
+}
+
+/// Change of a single file that is journaled together with it
+#[derive(Clone, Debug)]
+pub enum FileUpdate {
+    /// Stored with its tags, metadata and star, journaled as created
+    Insert(Box<File>),
+    /// `kind` is either renamed or moved
+    Filename { reference_uuid: Uuid, filename: String, kind: ChangeKind },
+    /// Left alone if the file would end up with more than `max_tags` tags
+    Tags { reference_uuid: Uuid, add: Vec<String>, remove: Vec<String>, max_tags: usize },
+    ReplaceTags { reference_uuid: Uuid, tags: Vec<String> },
+    Starred { reference_uuid: Uuid, starred: bool },
+    /// A missing value removes the entry
+    Metadata { reference_uuid: Uuid, key: String, value: Option<String> },
+    /// Verdict of the virus scanner
+    Scanned { reference_uuid: Uuid, status: ScanStatus },
+    /// Size and hash of content that changed on disk
+    Content { reference_uuid: Uuid, size: usize, sha256: String },
+    /// Journaled with the last filename
+    Delete { reference_uuid: Uuid },
+}
+
+impl FileUpdate {
+    pub fn reference_uuid(&self) -> Uuid {
+        match self {
+            FileUpdate::Insert(file) => file.reference_uuid,
+            FileUpdate::Filename { reference_uuid, .. }
+            | FileUpdate::Tags { reference_uuid, .. }
+            | FileUpdate::ReplaceTags { reference_uuid, .. }
+            | FileUpdate::Starred { reference_uuid, .. }
+            | FileUpdate::Metadata { reference_uuid, .. }
+            | FileUpdate::Scanned { reference_uuid, .. }
+            | FileUpdate::Content { reference_uuid, .. }
+            | FileUpdate::Delete { reference_uuid } => *reference_uuid,
+        }
+    }
+
+    /// Kind of the change journaled for it
+    pub fn kind(&self) -> ChangeKind {
+        match self {
+            FileUpdate::Insert(_) => ChangeKind::Created,
+            FileUpdate::Filename { kind, .. } => *kind,
+            FileUpdate::Delete { .. } => ChangeKind::Deleted,
+            _ => ChangeKind::Updated,
+        }
+    }
+}
+
+/// Storage of file metadata, the contents are on disk
+#[async_trait]
+pub trait FileRepository: Send + Sync {
+    /// Only returns files of `owner_uuid`
+    async fn get(&self, reference_uuid: Uuid, owner_uuid: Uuid) -> Result<Option<File>, RepositoryError>;
+
+    /// Returns false if the file doesn't exist or isn't owned by `owner_uuid` \
+    /// Isn't journaled, only used when the account of the owner is purged
+    async fn delete(&self, reference_uuid: Uuid, owner_uuid: Uuid) -> Result<bool, RepositoryError>;
+
+    /// All files of a user, oldest first
+    async fn list(&self, owner_uuid: Uuid) -> Result<Vec<File>, RepositoryError>;
+
+    /// Files of a user matching `filter`, newest first
+    async fn list_filtered(&self, owner_uuid: Uuid, filter: &FileFilter, limit: usize, offset: usize)
+        -> Result<Vec<File>, RepositoryError>;
+
+    /// All files of every user, used to check the storage
+    async fn all(&self) -> Result<Vec<File>, RepositoryError>;
+
+    /// Corrects the recorded size, e.g. after it was found to differ from the file on disk
+    async fn set_size(&self, reference_uuid: Uuid, size: usize) -> Result<bool, RepositoryError>;
+
+    async fn set_mime_type(&self, reference_uuid: Uuid, mime_type: &str) -> Result<bool, RepositoryError>;
+
+    async fn set_sha256(&self, reference_uuid: Uuid, sha256: &str) -> Result<bool, RepositoryError>;
+
+    async fn set_scan_status(&self, reference_uuid: Uuid, status: ScanStatus) -> Result<bool, RepositoryError>;
+
+    /// Moves the file to another path on disk, e.g. into the quarantine
+    async fn set_location(&self, reference_uuid: Uuid, relative_path: &str, absolute_path: &str) -> Result<bool, RepositoryError>;
+
+    /// Replaces the extracted text the search looks into
+    async fn set_text(&self, reference_uuid: Uuid, text: &str) -> Result<bool, RepositoryError>;
+
+    /// Files of `owner_uuid` whose name, tag, type or text matches `query`, best matches first \
+    /// Names match fuzzily, types by their full name or either half, e.g. `image` or `pdf`
+    async fn search(&self, owner_uuid: Uuid, query: &str, limit: usize, offset: usize)
+        -> Result<Vec<File>, RepositoryError>;
+
+    async fn usage(&self, owner_uuid: Uuid) -> Result<Usage, RepositoryError>;
+
+    /// Usage of multiple users at once, users without files are missing from the map
+    async fn usage_by_owner(&self, owner_uuids: &[Uuid]) -> Result<HashMap<Uuid, Usage>, RepositoryError>;
+
+    /// Usage of all users together
+    async fn total_usage(&self) -> Result<Usage, RepositoryError>;
+
+    /// Applies the updates in order in one transaction and journals every applied one, nothing is kept if one fails \
+    /// Journal ids of an owner only increase in the order they become visible \
+    /// Tags are expected to be normalized already \
+    /// Returns the change of every update, None if its file doesn't exist, isn't owned by `owner_uuid` or was left alone
+    async fn apply(&self, owner_uuid: Uuid, updates: &[FileUpdate]) -> Result<Vec<Option<Change>>, RepositoryError>;
+
+    /// Id of the latest change of `owner_uuid`, 0 without changes
+    async fn last_change_id(&self, owner_uuid: Uuid) -> Result<i64, RepositoryError>;
+
+    /// Changes of `owner_uuid` with an id above `cursor`, oldest first
+    async fn changes(&self, owner_uuid: Uuid, cursor: i64, limit: usize) -> Result<Vec<Change>, RepositoryError>;
+}
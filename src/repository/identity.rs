@@ -0,0 +1,17 @@
+use crate::models::identity::ExternalIdentity;
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Links between accounts at identity providers and local users
+#[async_trait]
+pub trait IdentityRepository: Send + Sync {
+    /// Fails with `Conflict` if the identity is already linked to any user
+    async fn link(&self, identity: &ExternalIdentity) -> Result<(), RepositoryError>;
+
+    /// User the identity is linked to
+    async fn user_uuid(&self, issuer: &str, subject: &str) -> Result<Option<Uuid>, RepositoryError>;
+
+    /// Removes every identity linked to the user
+    async fn unlink_all(&self, user_uuid: Uuid) -> Result<(), RepositoryError>;
+}
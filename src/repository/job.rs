@@ -0,0 +1,48 @@
+use crate::models::job::{JobCount, JobStatus, QueuedJob};
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Storage of the job queue and the runs of recurring jobs
+#[async_trait]
+pub trait JobRepository: Send + Sync {
+    /// Queues a job that is due at the unix timestamp `run_at` \
+    /// Returns None if a job with the same `unique_key` is already queued
+    async fn insert(&self, kind: &str, payload: &serde_json::Value, max_attempts: u32, run_at: i64, unique_key: Option<&str>)
+        -> Result<Option<i64>, RepositoryError>;
+
+    /// Takes a due job of one of `kinds` that isn't owned by another worker and counts the attempt \
+    /// Running jobs whose lease expired, e.g. after a crash, are claimed again if they have attempts left \
+    /// Nobody else can claim it for `lease` seconds, the returned job carries a new `lease_token`
+    async fn claim(&self, kinds: &[String], lease: i64) -> Result<Option<QueuedJob>, RepositoryError>;
+
+    /// Methods below only touch the job while `lease_token` is the one of its latest claim \
+    /// They return false if the lease was lost, e.g. to a worker that claimed the job after it expired
+    async fn renew(&self, id: i64, lease_token: Uuid, lease: i64) -> Result<bool, RepositoryError>;
+
+    async fn succeed(&self, id: i64, lease_token: Uuid) -> Result<bool, RepositoryError>;
+
+    /// Queues the job again at the unix timestamp `retry_at`, marks it as failed without one
+    async fn fail(&self, id: i64, lease_token: Uuid, error: &str, retry_at: Option<i64>) -> Result<bool, RepositoryError>;
+
+    async fn get(&self, id: i64) -> Result<Option<QueuedJob>, RepositoryError>;
+
+    /// Jobs matching the filters, newest first
+    async fn list(&self, status: Option<JobStatus>, kind: Option<&str>, limit: usize, offset: usize)
+        -> Result<Vec<QueuedJob>, RepositoryError>;
+
+    /// Number of jobs per kind and status
+    async fn counts(&self) -> Result<Vec<JobCount>, RepositoryError>;
+
+    /// Queues a failed job again with fresh attempts, returns false if there's no such failed job \
+    /// The unique key is dropped as another job may have taken its place in the meantime
+    async fn retry(&self, id: i64) -> Result<bool, RepositoryError>;
+
+    /// Fails running jobs that lost their worker on the last attempt and deletes jobs finished before the unix timestamp \
+    /// Returns how many were deleted
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError>;
+
+    /// Moves the next run of a schedule to `next_run` if it's due, only one caller wins per run \
+    /// A new schedule is stored with `next_run` and only due if `due_when_new`
+    async fn schedule_due(&self, name: &str, next_run: i64, due_when_new: bool) -> Result<bool, RepositoryError>;
+}
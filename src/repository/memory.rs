@@ -0,0 +1,953 @@
+use crate::models::audit::{AuditEntry, AuditFilter};
+use crate::models::batch::{Batch, ItemResult};
+use crate::models::change::{Change, ChangeKind};
+use crate::models::deletion::AccountDeletion;
+use crate::models::email_change::EmailChange;
+use crate::models::file::{File, ScanStatus};
+use crate::models::identity::ExternalIdentity;
+use crate::models::job::{JobCount, JobStatus, QueuedJob};
+use crate::models::user::{Permission, User};
+use crate::repository::audit::AuditRepository;
+use crate::repository::batch::BatchRepository;
+use crate::repository::deletion::DeletionRepository;
+use crate::repository::email_change::EmailChangeRepository;
+use crate::repository::error::RepositoryError;
+use crate::repository::file::{FileFilter, FileRepository, FileUpdate, Usage};
+use crate::repository::identity::IdentityRepository;
+use crate::repository::job::JobRepository;
+use crate::repository::user::UserRepository;
+use crate::repository::webhook::WebhookRepository;
+use crate::models::webhook::{Attempt, Delivery, DeliveryStatus, Webhook};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Keeps users in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryUserRepository {
+    users: Mutex<HashMap<Uuid, User>>,
+}
+
+impl MemoryUserRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies change to a single user and reports whether the user exists
+    fn update(&self, uuid: Uuid, change: impl FnOnce(&mut User)) -> Result<bool, RepositoryError> {
+        let mut users = self.users.lock()?;
+        match users.get_mut(&uuid) {
+            Some(user) => {
+                change(user);
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryUserRepository {
+    async fn insert(&self, user: &User) -> Result<(), RepositoryError> {
+        let mut users = self.users.lock()?;
+        if users.contains_key(&user.uuid) || users.values().any(|u| u.username == user.username) {
+            return Err(RepositoryError::Conflict)
+        }
+        users.insert(user.uuid, user.clone());
+        Ok(())
+    }
+
+    async fn count(&self) -> Result<usize, RepositoryError> {
+        Ok(self.users.lock()?.len())
+    }
+
+    async fn ping(&self) -> Result<(), RepositoryError> {
+        Ok(())
+    }
+
+    async fn get(&self, uuid: Uuid) -> Result<Option<User>, RepositoryError> {
+        Ok(self.users.lock()?.get(&uuid).cloned())
+    }
+
+    async fn get_by_username(&self, username: &str) -> Result<Option<User>, RepositoryError> {
+        Ok(self.users.lock()?.values().find(|u| u.username == username).cloned())
+    }
+
+    async fn list(&self, search: &str, limit: usize, offset: usize) -> Result<Vec<User>, RepositoryError> {
+        let search = search.to_lowercase();
+        let mut users: Vec<User> = self.users.lock()?
+            .values()
+            .filter(|u| u.username.to_lowercase().contains(&search) || u.email.to_lowercase().contains(&search))
+            .cloned()
+            .collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+
+        Ok(users.into_iter().skip(offset).take(limit).collect())
+    }
+
+    async fn set_username(&self, uuid: Uuid, username: &str) -> Result<bool, RepositoryError> {
+        let mut users = self.users.lock()?;
+        if users.values().any(|u| u.username == username && u.uuid != uuid) {
+            return Err(RepositoryError::Conflict)
+        }
+        match users.get_mut(&uuid) {
+            Some(user) => {
+                user.username = username.to_string();
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn set_password(&self, uuid: Uuid, password: &str, tokenid: Uuid, reset_required: bool)
+        -> Result<bool, RepositoryError> {
+        self.update(uuid, |user| {
+            user.password = password.to_string();
+            user.tokenid = tokenid;
+            user.password_reset = reset_required;
+        })
+    }
+
+    async fn set_tokenid(&self, uuid: Uuid, tokenid: Uuid) -> Result<bool, RepositoryError> {
+        self.update(uuid, |user| user.tokenid = tokenid)
+    }
+
+    async fn set_email(&self, uuid: Uuid, email: &str) -> Result<bool, RepositoryError> {
+        if self.users.lock()?.values().any(|u| u.uuid != uuid && u.email.eq_ignore_ascii_case(email)) {
+            return Err(RepositoryError::Conflict)
+        }
+        self.update(uuid, |user| user.email = email.to_string())
+    }
+
+    async fn set_permission(&self, uuid: Uuid, permission: Permission) -> Result<bool, RepositoryError> {
+        self.update(uuid, |user| user.permission = permission)
+    }
+
+    async fn set_quota(&self, uuid: Uuid, quota: Option<usize>) -> Result<bool, RepositoryError> {
+        self.update(uuid, |user| user.quota = quota)
+    }
+
+    async fn set_disabled(&self, uuid: Uuid, disabled: bool) -> Result<bool, RepositoryError> {
+        self.update(uuid, |user| user.disabled = disabled)
+    }
+
+    async fn require_password_reset(&self, uuid: Uuid) -> Result<bool, RepositoryError> {
+        self.update(uuid, |user| user.password_reset = true)
+    }
+
+    async fn delete(&self, uuid: Uuid) -> Result<bool, RepositoryError> {
+        Ok(self.users.lock()?.remove(&uuid).is_some())
+    }
+}
+
+/// Keeps file metadata in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryFileRepository {
+    files: Mutex<HashMap<Uuid, File>>,
+    /// Extracted text by reference uuid
+    texts: Mutex<HashMap<Uuid, String>>,
+    /// Change journal of all owners, the id is the position plus one
+    changes: Mutex<Vec<Change>>,
+}
+
+impl MemoryFileRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Adds and removes tags, returns false and leaves the file alone if it would end up with more than `max_tags`
+fn update_tags(file: &mut File, add: &[String], remove: &[String], max_tags: usize) -> bool {
+    let mut tags: Vec<String> = file.tags.iter().chain(add).filter(|tag| !remove.contains(tag)).cloned().collect();
+    tags.sort();
+    tags.dedup();
+    if tags.len() > max_tags {
+        return false
+    }
+    file.tags = tags;
+    true
+}
+
+/// Appends to the change journal, ids are the position in it
+fn journal(changes: &mut Vec<Change>, owner_uuid: Uuid, reference_uuid: Uuid, kind: ChangeKind, filename: Option<&str>) -> Change {
+    let change = Change {
+        id: changes.len() as i64 + 1,
+        owner_uuid,
+        reference_uuid,
+        kind,
+        filename: filename.map(str::to_string),
+        timestamp: Utc::now().timestamp() as usize,
+    };
+    changes.push(change.clone());
+    change
+}
+
+/// Sums up the usage of files
+fn usage<'a>(files: impl Iterator<Item = &'a File>) -> Usage {
+    files.fold(Usage::default(), |usage, file| Usage {
+        bytes: usage.bytes + file.size,
+        files: usage.files + 1,
+    })
+}
+
+#[async_trait]
+impl FileRepository for MemoryFileRepository {
+    async fn get(&self, reference_uuid: Uuid, owner_uuid: Uuid) -> Result<Option<File>, RepositoryError> {
+        Ok(self.files.lock()?
+            .get(&reference_uuid)
+            .filter(|f| f.owner_uuid == owner_uuid)
+            .cloned())
+    }
+
+    async fn delete(&self, reference_uuid: Uuid, owner_uuid: Uuid) -> Result<bool, RepositoryError> {
+        let mut files = self.files.lock()?;
+        if files.get(&reference_uuid).is_none_or(|f| f.owner_uuid != owner_uuid) {
+            return Ok(false)
+        }
+        files.remove(&reference_uuid);
+        self.texts.lock()?.remove(&reference_uuid);
+        Ok(true)
+    }
+
+    async fn list(&self, owner_uuid: Uuid) -> Result<Vec<File>, RepositoryError> {
+        let mut files: Vec<File> = self.files.lock()?
+            .values()
+            .filter(|f| f.owner_uuid == owner_uuid)
+            .cloned()
+            .collect();
+        files.sort_by_key(|f| f.timestamp);
+        Ok(files)
+    }
+
+    async fn list_filtered(&self, owner_uuid: Uuid, filter: &FileFilter, limit: usize, offset: usize)
+        -> Result<Vec<File>, RepositoryError> {
+        let mut files: Vec<File> = self.files.lock()?
+            .values()
+            .filter(|f| f.owner_uuid == owner_uuid)
+            .filter(|f| filter.tag.as_ref().is_none_or(|tag| f.tags.contains(tag)))
+            .filter(|f| filter.starred.is_none_or(|starred| f.starred == starred))
+            .cloned()
+            .collect();
+        files.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(a.reference_uuid.cmp(&b.reference_uuid)));
+
+        Ok(files.into_iter().skip(offset).take(limit).collect())
+    }
+
+    async fn all(&self) -> Result<Vec<File>, RepositoryError> {
+        Ok(self.files.lock()?.values().cloned().collect())
+    }
+
+    async fn set_size(&self, reference_uuid: Uuid, size: usize) -> Result<bool, RepositoryError> {
+        match self.files.lock()?.get_mut(&reference_uuid) {
+            Some(file) => {
+                file.size = size;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn set_mime_type(&self, reference_uuid: Uuid, mime_type: &str) -> Result<bool, RepositoryError> {
+        match self.files.lock()?.get_mut(&reference_uuid) {
+            Some(file) => {
+                file.mime_type = mime_type.to_string();
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn set_sha256(&self, reference_uuid: Uuid, sha256: &str) -> Result<bool, RepositoryError> {
+        match self.files.lock()?.get_mut(&reference_uuid) {
+            Some(file) => {
+                file.sha256 = Some(sha256.to_string());
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn set_scan_status(&self, reference_uuid: Uuid, status: ScanStatus) -> Result<bool, RepositoryError> {
+        match self.files.lock()?.get_mut(&reference_uuid) {
+            Some(file) => {
+                file.scan_status = status;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn set_location(&self, reference_uuid: Uuid, relative_path: &str, absolute_path: &str) -> Result<bool, RepositoryError> {
+        match self.files.lock()?.get_mut(&reference_uuid) {
+            Some(file) => {
+                file.relative_path = relative_path.to_string();
+                file.absolute_path = absolute_path.to_string();
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn set_text(&self, reference_uuid: Uuid, text: &str) -> Result<bool, RepositoryError> {
+        if !self.files.lock()?.contains_key(&reference_uuid) {
+            return Ok(false)
+        }
+        self.texts.lock()?.insert(reference_uuid, text.to_string());
+        Ok(true)
+    }
+
+    /// Substring matches only, names before tags and types before text
+    async fn search(&self, owner_uuid: Uuid, query: &str, limit: usize, offset: usize)
+        -> Result<Vec<File>, RepositoryError> {
+        let query = query.to_lowercase();
+        let words: Vec<&str> = query.split_whitespace().collect();
+        // same lock order as delete
+        let files = self.files.lock()?;
+        let texts = self.texts.lock()?;
+
+        let mut matches: Vec<(u8, File)> = files
+            .values()
+            .filter(|f| f.owner_uuid == owner_uuid)
+            .filter_map(|f| {
+                let (kind, subtype) = f.mime_type.split_once('/').unwrap_or((&f.mime_type, ""));
+                let text = texts.get(&f.reference_uuid).map(|t| t.to_lowercase()).unwrap_or_default();
+                let rank = if f.filename.to_lowercase().contains(&query) {
+                    0
+                } else if f.tags.contains(&query) || [f.mime_type.as_str(), kind, subtype].contains(&query.as_str()) {
+                    1
+                } else if !words.is_empty() && words.iter().all(|word| text.contains(word)) {
+                    2
+                } else {
+                    return None
+                };
+                Some((rank, f.clone()))
+            })
+            .collect();
+        matches.sort_by(|(rank_a, a), (rank_b, b)| rank_a.cmp(rank_b).then(b.timestamp.cmp(&a.timestamp)));
+
+        Ok(matches.into_iter().map(|(_, file)| file).skip(offset).take(limit).collect())
+    }
+
+    async fn usage(&self, owner_uuid: Uuid) -> Result<Usage, RepositoryError> {
+        Ok(usage(self.files.lock()?.values().filter(|f| f.owner_uuid == owner_uuid)))
+    }
+
+    async fn usage_by_owner(&self, owner_uuids: &[Uuid]) -> Result<HashMap<Uuid, Usage>, RepositoryError> {
+        let files = self.files.lock()?;
+        Ok(owner_uuids.iter()
+            .map(|owner| (*owner, usage(files.values().filter(|f| f.owner_uuid == *owner))))
+            .filter(|(_, usage)| usage.files > 0)
+            .collect())
+    }
+
+    async fn total_usage(&self) -> Result<Usage, RepositoryError> {
+        Ok(usage(self.files.lock()?.values()))
+    }
+
+    /// Can't fail halfway once inserts were checked for conflicts, so holding the locks is as good as a transaction
+    async fn apply(&self, owner_uuid: Uuid, updates: &[FileUpdate]) -> Result<Vec<Option<Change>>, RepositoryError> {
+        let mut files = self.files.lock()?;
+        let mut changes = self.changes.lock()?;
+        let mut texts = self.texts.lock()?;
+        if updates.iter().any(|update| matches!(update, FileUpdate::Insert(file) if files.contains_key(&file.reference_uuid))) {
+            return Err(RepositoryError::Conflict)
+        }
+
+        Ok(updates.iter().map(|update| {
+            let reference_uuid = update.reference_uuid();
+            let filename = match update {
+                FileUpdate::Insert(file) => {
+                    files.insert(reference_uuid, File { owner_uuid, ..*file.clone() });
+                    Some(file.filename.clone())
+                },
+                FileUpdate::Delete { .. } => {
+                    files.get(&reference_uuid).filter(|f| f.owner_uuid == owner_uuid)?;
+                    texts.remove(&reference_uuid);
+                    files.remove(&reference_uuid).map(|file| file.filename)
+                },
+                _ => {
+                    let file = files.get_mut(&reference_uuid).filter(|f| f.owner_uuid == owner_uuid)?;
+                    match update {
+                        FileUpdate::Filename { filename, .. } => file.filename = filename.clone(),
+                        FileUpdate::Tags { add, remove, max_tags, .. } => if !update_tags(file, add, remove, *max_tags) {
+                            return None
+                        },
+                        FileUpdate::ReplaceTags { tags, .. } => file.tags = tags.clone(),
+                        FileUpdate::Starred { starred, .. } => file.starred = *starred,
+                        FileUpdate::Metadata { key, value: Some(value), .. } => {
+                            file.metadata.insert(key.clone(), value.clone());
+                        },
+                        FileUpdate::Metadata { key, value: None, .. } => {
+                            file.metadata.remove(key);
+                        },
+                        FileUpdate::Scanned { status, .. } => file.scan_status = *status,
+                        FileUpdate::Content { size, sha256, .. } => {
+                            file.size = *size;
+                            file.sha256 = Some(sha256.clone());
+                        },
+                        FileUpdate::Insert(_) | FileUpdate::Delete { .. } => {},
+                    }
+                    matches!(update, FileUpdate::Filename { .. }).then(|| file.filename.clone())
+                },
+            };
+            Some(journal(&mut changes, owner_uuid, reference_uuid, update.kind(), filename.as_deref()))
+        }).collect())
+    }
+
+    async fn last_change_id(&self, owner_uuid: Uuid) -> Result<i64, RepositoryError> {
+        Ok(self.changes.lock()?.iter().rev()
+            .find(|change| change.owner_uuid == owner_uuid)
+            .map_or(0, |change| change.id))
+    }
+
+    async fn changes(&self, owner_uuid: Uuid, cursor: i64, limit: usize) -> Result<Vec<Change>, RepositoryError> {
+        Ok(self.changes.lock()?.iter()
+            .skip(cursor.max(0) as usize)
+            .filter(|change| change.owner_uuid == owner_uuid)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Keeps webhooks and their deliveries in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryWebhookRepository {
+    webhooks: Mutex<Vec<Webhook>>,
+    /// Deliveries of all webhooks by id
+    deliveries: Mutex<BTreeMap<i64, Delivery>>,
+    /// Lease expiry by delivery id
+    leases: Mutex<HashMap<i64, i64>>,
+}
+
+impl MemoryWebhookRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for MemoryWebhookRepository {
+    async fn insert(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        let mut webhooks = self.webhooks.lock()?;
+        if webhooks.iter().any(|w| w.uuid == webhook.uuid) {
+            return Err(RepositoryError::Conflict)
+        }
+        webhooks.push(webhook.clone());
+        Ok(())
+    }
+
+    async fn get(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<Option<Webhook>, RepositoryError> {
+        Ok(self.webhooks.lock()?.iter().find(|w| w.uuid == uuid && w.owner_uuid == owner_uuid).cloned())
+    }
+
+    async fn list(&self, owner_uuid: Uuid) -> Result<Vec<Webhook>, RepositoryError> {
+        Ok(self.webhooks.lock()?.iter().filter(|w| w.owner_uuid == owner_uuid).cloned().collect())
+    }
+
+    async fn delete(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<bool, RepositoryError> {
+        let mut webhooks = self.webhooks.lock()?;
+        let before = webhooks.len();
+        webhooks.retain(|w| !(w.uuid == uuid && w.owner_uuid == owner_uuid));
+        if webhooks.len() == before {
+            return Ok(false)
+        }
+        self.deliveries.lock()?.retain(|_, d| d.webhook_uuid != uuid);
+        Ok(true)
+    }
+
+    async fn subscribed(&self, owner_uuid: Uuid, event: &str) -> Result<Vec<Webhook>, RepositoryError> {
+        Ok(self.webhooks.lock()?.iter()
+            .filter(|w| w.owner_uuid == owner_uuid && w.events.iter().any(|e| e == event))
+            .cloned()
+            .collect())
+    }
+
+    async fn enqueue(&self, webhook_uuid: Uuid, event: &str, payload: &str) -> Result<Delivery, RepositoryError> {
+        let mut deliveries = self.deliveries.lock()?;
+        let now = Utc::now().timestamp();
+        let delivery = Delivery {
+            id: deliveries.last_key_value().map_or(1, |(id, _)| id + 1),
+            webhook_uuid,
+            event: event.to_string(),
+            payload: payload.to_string(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            response_status: None,
+            error: None,
+            next_attempt: now,
+            timestamp: now as usize,
+        };
+        deliveries.insert(delivery.id, delivery.clone());
+        Ok(delivery)
+    }
+
+    async fn claim(&self, id: Option<i64>, lease: i64) -> Result<Option<(Delivery, Webhook)>, RepositoryError> {
+        let now = Utc::now().timestamp();
+        let mut deliveries = self.deliveries.lock()?;
+        let mut leases = self.leases.lock()?;
+        let Some(delivery) = deliveries.values_mut()
+            .filter(|d| id.is_none_or(|id| d.id == id))
+            .filter(|d| d.status == DeliveryStatus::Pending && d.next_attempt <= now)
+            .filter(|d| leases.get(&d.id).is_none_or(|until| *until < now))
+            .min_by_key(|d| d.next_attempt) else {
+            return Ok(None)
+        };
+        let Some(webhook) = self.webhooks.lock()?.iter().find(|w| w.uuid == delivery.webhook_uuid).cloned() else {
+            return Ok(None)
+        };
+
+        delivery.attempts += 1;
+        leases.insert(delivery.id, now + lease);
+        Ok(Some((delivery.clone(), webhook)))
+    }
+
+    async fn finish(&self, id: i64, attempt: &Attempt) -> Result<(), RepositoryError> {
+        if let Some(delivery) = self.deliveries.lock()?.get_mut(&id) {
+            delivery.status = attempt.status;
+            delivery.response_status = attempt.response_status;
+            delivery.error = attempt.error.clone();
+            delivery.next_attempt = attempt.next_attempt;
+        }
+        self.leases.lock()?.remove(&id);
+        Ok(())
+    }
+
+    async fn deliveries(&self, webhook_uuid: Uuid, limit: usize) -> Result<Vec<Delivery>, RepositoryError> {
+        Ok(self.deliveries.lock()?.values().rev()
+            .filter(|d| d.webhook_uuid == webhook_uuid)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError> {
+        let mut deliveries = self.deliveries.lock()?;
+        let count = deliveries.len();
+        deliveries.retain(|_, d| d.status == DeliveryStatus::Pending || d.timestamp as i64 >= before);
+        Ok((count - deliveries.len()) as u64)
+    }
+}
+
+/// Keeps the audit log in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryAuditRepository {
+    /// Oldest first, ids only increase
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl MemoryAuditRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AuditRepository for MemoryAuditRepository {
+    async fn append(&self, entry: &AuditEntry) -> Result<(), RepositoryError> {
+        let mut entries = self.entries.lock()?;
+        let id = entries.last().map_or(1, |last| last.id + 1);
+        entries.push(AuditEntry { id, timestamp: Utc::now().timestamp() as usize, ..entry.clone() });
+        Ok(())
+    }
+
+    async fn query(&self, filter: &AuditFilter, limit: usize) -> Result<Vec<AuditEntry>, RepositoryError> {
+        Ok(self.entries.lock()?.iter().rev()
+            .filter(|entry| filter.matches(entry))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError> {
+        let mut entries = self.entries.lock()?;
+        let count = entries.len();
+        entries.retain(|entry| entry.timestamp as i64 >= before);
+        Ok((count - entries.len()) as u64)
+    }
+}
+
+/// Keeps scheduled deletions in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryDeletionRepository {
+    deletions: Mutex<HashMap<Uuid, AccountDeletion>>,
+}
+
+impl MemoryDeletionRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Due and not owned by a worker
+fn claimable(deletion: &AccountDeletion, now: i64) -> bool {
+    deletion.scheduled_for <= now && deletion.lease_until.is_none_or(|until| until < now)
+}
+
+#[async_trait]
+impl DeletionRepository for MemoryDeletionRepository {
+    async fn get(&self, user_uuid: Uuid) -> Result<Option<AccountDeletion>, RepositoryError> {
+        Ok(self.deletions.lock()?.get(&user_uuid).cloned())
+    }
+
+    async fn schedule(&self, user_uuid: Uuid, scheduled_for: i64) -> Result<AccountDeletion, RepositoryError> {
+        Ok(self.deletions.lock()?
+            .entry(user_uuid)
+            .or_insert_with(|| AccountDeletion {
+                user_uuid,
+                scheduled_for,
+                lease_until: None,
+                timestamp: Utc::now().timestamp() as usize,
+            })
+            .clone())
+    }
+
+    async fn cancel(&self, user_uuid: Uuid) -> Result<bool, RepositoryError> {
+        let now = Utc::now().timestamp();
+        let mut deletions = self.deletions.lock()?;
+        match deletions.get(&user_uuid) {
+            Some(deletion) if deletion.lease_until.is_none() && deletion.scheduled_for > now => {
+                deletions.remove(&user_uuid);
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    async fn due(&self) -> Result<Vec<AccountDeletion>, RepositoryError> {
+        let now = Utc::now().timestamp();
+        let mut due: Vec<AccountDeletion> = self.deletions.lock()?
+            .values()
+            .filter(|d| claimable(d, now))
+            .cloned()
+            .collect();
+        due.sort_by_key(|d| d.scheduled_for);
+        Ok(due)
+    }
+
+    async fn claim(&self, user_uuid: Uuid, lease: i64) -> Result<Option<AccountDeletion>, RepositoryError> {
+        let now = Utc::now().timestamp();
+        match self.deletions.lock()?.get_mut(&user_uuid).filter(|d| claimable(d, now)) {
+            Some(deletion) => {
+                deletion.lease_until = Some(now + lease);
+                Ok(Some(deletion.clone()))
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn renew(&self, user_uuid: Uuid, lease: i64) -> Result<(), RepositoryError> {
+        if let Some(deletion) = self.deletions.lock()?.get_mut(&user_uuid) {
+            deletion.lease_until = Some(Utc::now().timestamp() + lease);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, user_uuid: Uuid) -> Result<(), RepositoryError> {
+        self.deletions.lock()?.remove(&user_uuid);
+        Ok(())
+    }
+}
+
+/// Keeps pending email changes in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryEmailChangeRepository {
+    /// Change and the hash of its token by user
+    changes: Mutex<HashMap<Uuid, (EmailChange, String)>>,
+}
+
+impl MemoryEmailChangeRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl EmailChangeRepository for MemoryEmailChangeRepository {
+    async fn upsert(&self, change: &EmailChange, token_hash: &str) -> Result<(), RepositoryError> {
+        self.changes.lock()?.insert(change.user_uuid, (change.clone(), token_hash.to_string()));
+        Ok(())
+    }
+
+    async fn get(&self, user_uuid: Uuid) -> Result<Option<EmailChange>, RepositoryError> {
+        let now = Utc::now().timestamp();
+        Ok(self.changes.lock()?
+            .get(&user_uuid)
+            .map(|(change, _)| change)
+            .filter(|change| change.expires > now)
+            .cloned())
+    }
+
+    async fn take(&self, token_hash: &str) -> Result<Option<EmailChange>, RepositoryError> {
+        let mut changes = self.changes.lock()?;
+        let user_uuid = changes.iter()
+            .find(|(_, (_, hash))| hash == token_hash)
+            .map(|(user_uuid, _)| *user_uuid);
+        Ok(user_uuid.and_then(|user_uuid| changes.remove(&user_uuid)).map(|(change, _)| change))
+    }
+
+    async fn delete(&self, user_uuid: Uuid) -> Result<(), RepositoryError> {
+        self.changes.lock()?.remove(&user_uuid);
+        Ok(())
+    }
+}
+
+/// Keeps linked identities in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryIdentityRepository {
+    identities: Mutex<Vec<ExternalIdentity>>,
+}
+
+impl MemoryIdentityRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl IdentityRepository for MemoryIdentityRepository {
+    async fn link(&self, identity: &ExternalIdentity) -> Result<(), RepositoryError> {
+        let mut identities = self.identities.lock()?;
+        if identities.iter().any(|i| i.issuer == identity.issuer && i.subject == identity.subject) {
+            return Err(RepositoryError::Conflict)
+        }
+        identities.push(identity.clone());
+        Ok(())
+    }
+
+    async fn user_uuid(&self, issuer: &str, subject: &str) -> Result<Option<Uuid>, RepositoryError> {
+        Ok(self.identities.lock()?.iter()
+            .find(|i| i.issuer == issuer && i.subject == subject)
+            .map(|i| i.user_uuid))
+    }
+
+    async fn unlink_all(&self, user_uuid: Uuid) -> Result<(), RepositoryError> {
+        self.identities.lock()?.retain(|i| i.user_uuid != user_uuid);
+        Ok(())
+    }
+}
+
+/// Keeps the job queue in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryJobRepository {
+    jobs: Mutex<BTreeMap<i64, QueuedJob>>,
+    /// Next run of every recurring job by name
+    schedules: Mutex<HashMap<String, i64>>,
+}
+
+impl MemoryJobRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies change to a single running job while `lease_token` owns it and reports whether it did
+    fn update(&self, id: i64, lease_token: Uuid, change: impl FnOnce(&mut QueuedJob)) -> Result<bool, RepositoryError> {
+        let mut jobs = self.jobs.lock()?;
+        match jobs.get_mut(&id).filter(|j| j.status == JobStatus::Running && j.lease_token == Some(lease_token)) {
+            Some(job) => {
+                change(job);
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl JobRepository for MemoryJobRepository {
+    async fn insert(&self, kind: &str, payload: &serde_json::Value, max_attempts: u32, run_at: i64, unique_key: Option<&str>)
+        -> Result<Option<i64>, RepositoryError> {
+        let mut jobs = self.jobs.lock()?;
+        if unique_key.is_some() && jobs.values().any(|j| j.status == JobStatus::Queued && j.unique_key.as_deref() == unique_key) {
+            return Ok(None)
+        }
+        let id = jobs.last_key_value().map_or(1, |(id, _)| id + 1);
+        jobs.insert(id, QueuedJob {
+            id,
+            kind: kind.to_string(),
+            payload: payload.clone(),
+            status: JobStatus::Queued,
+            attempts: 0,
+            max_attempts,
+            run_at,
+            lease_until: None,
+            lease_token: None,
+            last_error: None,
+            unique_key: unique_key.map(str::to_string),
+            finished_at: None,
+            timestamp: Utc::now().timestamp() as usize,
+        });
+        Ok(Some(id))
+    }
+
+    async fn claim(&self, kinds: &[String], lease: i64) -> Result<Option<QueuedJob>, RepositoryError> {
+        let now = Utc::now().timestamp();
+        let mut jobs = self.jobs.lock()?;
+        let Some(job) = jobs.values_mut()
+            .filter(|j| kinds.contains(&j.kind) && j.run_at <= now)
+            .filter(|j| match j.status {
+                JobStatus::Queued => true,
+                JobStatus::Running => j.lease_until.is_some_and(|until| until < now) && j.attempts < j.max_attempts,
+                _ => false,
+            })
+            .min_by_key(|j| j.run_at) else {
+            return Ok(None)
+        };
+
+        job.status = JobStatus::Running;
+        job.attempts += 1;
+        job.lease_until = Some(now + lease);
+        job.lease_token = Some(Uuid::new_v4());
+        Ok(Some(job.clone()))
+    }
+
+    async fn renew(&self, id: i64, lease_token: Uuid, lease: i64) -> Result<bool, RepositoryError> {
+        self.update(id, lease_token, |job| job.lease_until = Some(Utc::now().timestamp() + lease))
+    }
+
+    async fn succeed(&self, id: i64, lease_token: Uuid) -> Result<bool, RepositoryError> {
+        self.update(id, lease_token, |job| {
+            job.status = JobStatus::Succeeded;
+            job.lease_until = None;
+            job.lease_token = None;
+            job.finished_at = Some(Utc::now().timestamp());
+        })
+    }
+
+    async fn fail(&self, id: i64, lease_token: Uuid, error: &str, retry_at: Option<i64>) -> Result<bool, RepositoryError> {
+        let now = Utc::now().timestamp();
+        self.update(id, lease_token, |job| {
+            job.status = if retry_at.is_some() { JobStatus::Queued } else { JobStatus::Failed };
+            job.run_at = retry_at.unwrap_or(now);
+            job.lease_until = None;
+            job.lease_token = None;
+            job.last_error = Some(error.to_string());
+            job.finished_at = if retry_at.is_some() { None } else { Some(now) };
+        })
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<QueuedJob>, RepositoryError> {
+        Ok(self.jobs.lock()?.get(&id).cloned())
+    }
+
+    async fn list(&self, status: Option<JobStatus>, kind: Option<&str>, limit: usize, offset: usize)
+        -> Result<Vec<QueuedJob>, RepositoryError> {
+        Ok(self.jobs.lock()?.values().rev()
+            .filter(|j| status.is_none_or(|status| j.status == status))
+            .filter(|j| kind.is_none_or(|kind| j.kind == kind))
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn counts(&self) -> Result<Vec<JobCount>, RepositoryError> {
+        let mut counts: BTreeMap<(String, String), JobCount> = BTreeMap::new();
+        for job in self.jobs.lock()?.values() {
+            counts.entry((job.kind.clone(), job.status.to_string()))
+                .or_insert_with(|| JobCount { kind: job.kind.clone(), status: job.status, count: 0 })
+                .count += 1;
+        }
+        Ok(counts.into_values().collect())
+    }
+
+    async fn retry(&self, id: i64) -> Result<bool, RepositoryError> {
+        match self.jobs.lock()?.get_mut(&id).filter(|j| j.status == JobStatus::Failed) {
+            Some(job) => {
+                job.status = JobStatus::Queued;
+                job.attempts = 0;
+                job.run_at = Utc::now().timestamp();
+                job.finished_at = None;
+                job.unique_key = None;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError> {
+        let now = Utc::now().timestamp();
+        let mut jobs = self.jobs.lock()?;
+        for job in jobs.values_mut() {
+            let abandoned = job.status == JobStatus::Running
+                && job.lease_until.is_some_and(|until| until < now)
+                && job.attempts >= job.max_attempts;
+            if abandoned {
+                job.status = JobStatus::Failed;
+                job.lease_until = None;
+                job.lease_token = None;
+                job.finished_at = Some(now);
+                job.last_error.get_or_insert_with(|| "worker stopped while running the job".to_string());
+            }
+        }
+        let count = jobs.len();
+        jobs.retain(|_, j| j.finished_at.is_none_or(|finished| finished >= before));
+        Ok((count - jobs.len()) as u64)
+    }
+
+    async fn schedule_due(&self, name: &str, next_run: i64, due_when_new: bool) -> Result<bool, RepositoryError> {
+        let now = Utc::now().timestamp();
+        let mut schedules = self.schedules.lock()?;
+        let due = match schedules.get(name) {
+            Some(scheduled) if *scheduled > now => return Ok(false),
+            Some(_) => true,
+            None => due_when_new,
+        };
+        schedules.insert(name.to_string(), next_run);
+        Ok(due)
+    }
+}
+
+/// Keeps the progress of background batches in memory, used in tests that run without a database
+#[derive(Default)]
+pub struct MemoryBatchRepository {
+    batches: Mutex<HashMap<Uuid, Batch>>,
+}
+
+impl MemoryBatchRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl BatchRepository for MemoryBatchRepository {
+    async fn insert(&self, batch: &Batch) -> Result<(), RepositoryError> {
+        let mut batches = self.batches.lock()?;
+        if batches.contains_key(&batch.uuid) {
+            return Err(RepositoryError::Conflict)
+        }
+        batches.insert(batch.uuid, batch.clone());
+        Ok(())
+    }
+
+    async fn get(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<Option<Batch>, RepositoryError> {
+        Ok(self.batches.lock()?
+            .get(&uuid)
+            .filter(|batch| batch.owner_uuid == owner_uuid)
+            .cloned())
+    }
+
+    async fn record(&self, uuid: Uuid, results: &[ItemResult]) -> Result<(), RepositoryError> {
+        if let Some(batch) = self.batches.lock()?.get_mut(&uuid) {
+            batch.results.extend_from_slice(results);
+        }
+        Ok(())
+    }
+
+    async fn finish(&self, uuid: Uuid) -> Result<(), RepositoryError> {
+        if let Some(batch) = self.batches.lock()?.get_mut(&uuid) {
+            batch.finished = true;
+        }
+        Ok(())
+    }
+
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError> {
+        let mut batches = self.batches.lock()?;
+        let count = batches.len();
+        batches.retain(|_, batch| !batch.finished || batch.timestamp as i64 >= before);
+        Ok((count - batches.len()) as u64)
+    }
+}
@@ -0,0 +1,1202 @@
+use crate::models::audit::{AuditEntry, AuditFilter};
+use crate::models::batch::{Batch, ItemResult};
+use crate::models::change::{Change, ChangeKind};
+use crate::models::deletion::AccountDeletion;
+use crate::models::email_change::EmailChange;
+use crate::models::file::{File, ScanStatus};
+use crate::models::identity::ExternalIdentity;
+use crate::models::job::{JobCount, JobStatus, QueuedJob};
+use crate::models::user::{Permission, User};
+use crate::repository::audit::AuditRepository;
+use crate::repository::batch::BatchRepository;
+use crate::repository::deletion::DeletionRepository;
+use crate::repository::email_change::EmailChangeRepository;
+use crate::repository::error::RepositoryError;
+use crate::repository::file::{FileFilter, FileRepository, FileUpdate, Usage};
+use crate::repository::identity::IdentityRepository;
+use crate::repository::job::JobRepository;
+use crate::repository::user::UserRepository;
+use crate::repository::webhook::WebhookRepository;
+use crate::models::webhook::{Attempt, Delivery, Webhook};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::types::Json;
+use sqlx::{PgConnection, Pool, Postgres, Row};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Makes user input match literally in a `LIKE ... ESCAPE '\'` pattern
+fn escape_like(value: &str) -> String {
+    value.replace('\\', r"\\").replace('%', r"\%").replace('_', r"\_")
+}
+
+/// Keeps users in the `users` table
+pub struct PgUserRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgUserRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+
+    /// Runs an update of a single user and reports whether the user exists
+    async fn update<'q>(&self, query: sqlx::query::Query<'q, Postgres, sqlx::postgres::PgArguments>)
+        -> Result<bool, RepositoryError> {
+        let result = query.execute(self.db_pool.as_ref()).await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgUserRepository {
+    async fn insert(&self, user: &User) -> Result<(), RepositoryError> {
+        let query =
+            r"INSERT INTO users (uuid, username, email, password, permission, tokenid) VALUES ($1, $2, $3, $4, $5, $6)";
+        sqlx::query(query)
+            .bind(user.uuid)
+            .bind(&user.username)
+            .bind(&user.email)
+            .bind(&user.password)
+            .bind(&user.permission)
+            .bind(user.tokenid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn count(&self) -> Result<usize, RepositoryError> {
+        let count: i64 = sqlx::query_scalar(r"SELECT COUNT(*) FROM users")
+            .fetch_one(self.db_pool.as_ref())
+            .await?;
+
+        Ok(count as usize)
+    }
+
+    async fn ping(&self) -> Result<(), RepositoryError> {
+        sqlx::query(r"SELECT 1").execute(self.db_pool.as_ref()).await?;
+        Ok(())
+    }
+
+    async fn get(&self, uuid: Uuid) -> Result<Option<User>, RepositoryError> {
+        let row = sqlx::query(r"SELECT * FROM users WHERE uuid = $1")
+            .bind(uuid)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(User::from_pg_row).transpose()?)
+    }
+
+    async fn get_by_username(&self, username: &str) -> Result<Option<User>, RepositoryError> {
+        let row = sqlx::query(r"SELECT * FROM users WHERE username = $1")
+            .bind(username)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(User::from_pg_row).transpose()?)
+    }
+
+    async fn list(&self, search: &str, limit: usize, offset: usize) -> Result<Vec<User>, RepositoryError> {
+        let query = r"SELECT * FROM users
+                      WHERE username ILIKE $1 ESCAPE '\' OR email ILIKE $1 ESCAPE '\'
+                      ORDER BY username
+                      LIMIT $2 OFFSET $3";
+        let rows = sqlx::query(query)
+            .bind(format!("%{}%", escape_like(search)))
+            .bind(limit.min(i64::MAX as usize) as i64)
+            .bind(offset.min(i64::MAX as usize) as i64)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(User::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn set_username(&self, uuid: Uuid, username: &str) -> Result<bool, RepositoryError> {
+        self.update(sqlx::query(r"UPDATE users SET username = $1 WHERE uuid = $2")
+            .bind(username.to_string())
+            .bind(uuid)
+        ).await
+    }
+
+    async fn set_password(&self, uuid: Uuid, password: &str, tokenid: Uuid, reset_required: bool)
+        -> Result<bool, RepositoryError> {
+        self.update(sqlx::query(r"UPDATE users SET password = $1, tokenid = $2, password_reset = $3 WHERE uuid = $4")
+            .bind(password.to_string())
+            .bind(tokenid)
+            .bind(reset_required)
+            .bind(uuid)
+        ).await
+    }
+
+    async fn set_tokenid(&self, uuid: Uuid, tokenid: Uuid) -> Result<bool, RepositoryError> {
+        self.update(sqlx::query(r"UPDATE users SET tokenid = $1 WHERE uuid = $2")
+            .bind(tokenid)
+            .bind(uuid)
+        ).await
+    }
+
+    async fn set_email(&self, uuid: Uuid, email: &str) -> Result<bool, RepositoryError> {
+        let query = r"SELECT EXISTS (SELECT 1 FROM users WHERE lower(email) = lower($1) AND uuid <> $2)";
+        let taken: bool = sqlx::query_scalar(query)
+            .bind(email)
+            .bind(uuid)
+            .fetch_one(self.db_pool.as_ref())
+            .await?;
+        if taken {
+            return Err(RepositoryError::Conflict)
+        }
+
+        self.update(sqlx::query(r"UPDATE users SET email = $1 WHERE uuid = $2")
+            .bind(email.to_string())
+            .bind(uuid)
+        ).await
+    }
+
+    async fn set_permission(&self, uuid: Uuid, permission: Permission) -> Result<bool, RepositoryError> {
+        self.update(sqlx::query(r"UPDATE users SET permission = $1 WHERE uuid = $2")
+            .bind(permission)
+            .bind(uuid)
+        ).await
+    }
+
+    async fn set_quota(&self, uuid: Uuid, quota: Option<usize>) -> Result<bool, RepositoryError> {
+        self.update(sqlx::query(r"UPDATE users SET quota = $1 WHERE uuid = $2")
+            .bind(quota.map(|q| q as i64))
+            .bind(uuid)
+        ).await
+    }
+
+    async fn set_disabled(&self, uuid: Uuid, disabled: bool) -> Result<bool, RepositoryError> {
+        self.update(sqlx::query(r"UPDATE users SET disabled = $1 WHERE uuid = $2")
+            .bind(disabled)
+            .bind(uuid)
+        ).await
+    }
+
+    async fn require_password_reset(&self, uuid: Uuid) -> Result<bool, RepositoryError> {
+        self.update(sqlx::query(r"UPDATE users SET password_reset = TRUE WHERE uuid = $1")
+            .bind(uuid)
+        ).await
+    }
+
+    /// Rows referencing the user other than files are removed by the cascade
+    async fn delete(&self, uuid: Uuid) -> Result<bool, RepositoryError> {
+        self.update(sqlx::query(r"DELETE FROM users WHERE uuid = $1")
+            .bind(uuid)
+        ).await
+    }
+}
+
+/// Keeps file metadata in the `file` table
+pub struct PgFileRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgFileRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+/// Runs an update of a single file and reports whether the file exists
+async fn update_file<'q>(tx: &mut PgConnection, query: sqlx::query::Query<'q, Postgres, sqlx::postgres::PgArguments>)
+    -> Result<bool, sqlx::Error> {
+    let result = query.execute(tx).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Held until the transaction ends, ids come from a sequence and the lock keeps a smaller id of the same owner from committing later
+async fn lock_journal(tx: &mut PgConnection, owner_uuid: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(r"SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+        .bind(owner_uuid.to_string())
+        .execute(tx)
+        .await?;
+    Ok(())
+}
+
+/// Appends to the change journal, the journal of the owner has to be locked
+async fn journal(tx: &mut PgConnection, owner_uuid: Uuid, reference_uuid: Uuid, kind: ChangeKind, filename: Option<&str>)
+    -> Result<Change, sqlx::Error> {
+    let row = sqlx::query(
+        r"INSERT INTO file_change (owner_uuid, reference_uuid, kind, filename) VALUES ($1, $2, $3, $4) RETURNING *")
+        .bind(owner_uuid)
+        .bind(reference_uuid)
+        .bind(kind.to_string())
+        .bind(filename)
+        .fetch_one(tx)
+        .await?;
+    Change::from_pg_row(row)
+}
+
+#[async_trait]
+impl FileRepository for PgFileRepository {
+    async fn get(&self, reference_uuid: Uuid, owner_uuid: Uuid) -> Result<Option<File>, RepositoryError> {
+        let row = sqlx::query(r"SELECT * FROM file WHERE reference_uuid = $1 AND owner_uuid = $2")
+            .bind(reference_uuid)
+            .bind(owner_uuid)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(File::from_pg_row).transpose()?)
+    }
+
+    async fn delete(&self, reference_uuid: Uuid, owner_uuid: Uuid) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(r"DELETE FROM file WHERE reference_uuid = $1 AND owner_uuid = $2")
+            .bind(reference_uuid)
+            .bind(owner_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list(&self, owner_uuid: Uuid) -> Result<Vec<File>, RepositoryError> {
+        let rows = sqlx::query(r"SELECT * FROM file WHERE owner_uuid = $1 ORDER BY timestamp")
+            .bind(owner_uuid)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(File::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn list_filtered(&self, owner_uuid: Uuid, filter: &FileFilter, limit: usize, offset: usize)
+        -> Result<Vec<File>, RepositoryError> {
+        let query = r"SELECT * FROM file
+                      WHERE owner_uuid = $1
+                        AND ($2::TEXT IS NULL OR tags @> ARRAY[$2::TEXT])
+                        AND ($3::BOOLEAN IS NULL OR starred = $3)
+                      ORDER BY timestamp DESC, reference_uuid
+                      LIMIT $4 OFFSET $5";
+        let rows = sqlx::query(query)
+            .bind(owner_uuid)
+            .bind(&filter.tag)
+            .bind(filter.starred)
+            .bind(limit.min(i64::MAX as usize) as i64)
+            .bind(offset.min(i64::MAX as usize) as i64)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(File::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn all(&self) -> Result<Vec<File>, RepositoryError> {
+        let rows = sqlx::query(r"SELECT * FROM file")
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(File::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn set_size(&self, reference_uuid: Uuid, size: usize) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(r"UPDATE file SET size = $1 WHERE reference_uuid = $2")
+            .bind(size as i64)
+            .bind(reference_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_mime_type(&self, reference_uuid: Uuid, mime_type: &str) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(r"UPDATE file SET mime_type = $1 WHERE reference_uuid = $2")
+            .bind(mime_type)
+            .bind(reference_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_sha256(&self, reference_uuid: Uuid, sha256: &str) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(r"UPDATE file SET sha256 = $1 WHERE reference_uuid = $2")
+            .bind(sha256)
+            .bind(reference_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_scan_status(&self, reference_uuid: Uuid, status: ScanStatus) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(r"UPDATE file SET scan_status = $1 WHERE reference_uuid = $2")
+            .bind(status.to_string())
+            .bind(reference_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_location(&self, reference_uuid: Uuid, relative_path: &str, absolute_path: &str) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(r"UPDATE file SET relative_path = $1, absolute_path = $2 WHERE reference_uuid = $3")
+            .bind(relative_path)
+            .bind(absolute_path)
+            .bind(reference_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_text(&self, reference_uuid: Uuid, text: &str) -> Result<bool, RepositoryError> {
+        let query = r"INSERT INTO file_text (reference_uuid, content)
+                      SELECT reference_uuid, $2 FROM file WHERE reference_uuid = $1
+                      ON CONFLICT (reference_uuid) DO UPDATE SET content = EXCLUDED.content";
+        let result = sqlx::query(query)
+            .bind(reference_uuid)
+            .bind(text)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn search(&self, owner_uuid: Uuid, query: &str, limit: usize, offset: usize)
+        -> Result<Vec<File>, RepositoryError> {
+        // fuzzy or substring filename matches, tags, types and the full text search of the extracted text
+        let sql = r"SELECT file.* FROM file
+                    LEFT JOIN file_text ON file_text.reference_uuid = file.reference_uuid
+                    CROSS JOIN websearch_to_tsquery('simple', $2) AS text_query
+                    WHERE file.owner_uuid = $1 AND (
+                        $2 <% file.filename
+                        OR file.filename ILIKE $3 ESCAPE '\'
+                        OR file.tags @> ARRAY[lower($2)]
+                        OR lower($2) IN (file.mime_type, split_part(file.mime_type, '/', 1), split_part(file.mime_type, '/', 2))
+                        OR file_text.search_vector @@ text_query
+                    )
+                    ORDER BY GREATEST(word_similarity($2, file.filename), ts_rank(file_text.search_vector, text_query)) DESC,
+                             file.timestamp DESC
+                    LIMIT $4 OFFSET $5";
+        let rows = sqlx::query(sql)
+            .bind(owner_uuid)
+            .bind(query)
+            .bind(format!("%{}%", escape_like(query)))
+            .bind(limit.min(i64::MAX as usize) as i64)
+            .bind(offset.min(i64::MAX as usize) as i64)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(File::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn usage(&self, owner_uuid: Uuid) -> Result<Usage, RepositoryError> {
+        let query = r"SELECT COALESCE(SUM(size), 0)::BIGINT, COUNT(*) FROM file WHERE owner_uuid = $1";
+        let (bytes, files): (i64, i64) = sqlx::query_as(query)
+            .bind(owner_uuid)
+            .fetch_one(self.db_pool.as_ref())
+            .await?;
+
+        Ok(Usage { bytes: bytes as usize, files: files as usize })
+    }
+
+    async fn usage_by_owner(&self, owner_uuids: &[Uuid]) -> Result<HashMap<Uuid, Usage>, RepositoryError> {
+        let query = r"SELECT owner_uuid, COALESCE(SUM(size), 0)::BIGINT, COUNT(*) FROM file
+                      WHERE owner_uuid = ANY($1)
+                      GROUP BY owner_uuid";
+        let rows: Vec<(Uuid, i64, i64)> = sqlx::query_as(query)
+            .bind(owner_uuids)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter()
+            .map(|(owner, bytes, files)| (owner, Usage { bytes: bytes as usize, files: files as usize }))
+            .collect())
+    }
+
+    async fn total_usage(&self) -> Result<Usage, RepositoryError> {
+        let query = r"SELECT COALESCE(SUM(size), 0)::BIGINT, COUNT(*) FROM file";
+        let (bytes, files): (i64, i64) = sqlx::query_as(query)
+            .fetch_one(self.db_pool.as_ref())
+            .await?;
+
+        Ok(Usage { bytes: bytes as usize, files: files as usize })
+    }
+
+    async fn apply(&self, owner_uuid: Uuid, updates: &[FileUpdate]) -> Result<Vec<Option<Change>>, RepositoryError> {
+        let mut tx = self.db_pool.begin().await?;
+        lock_journal(&mut tx, owner_uuid).await?;
+
+        let mut changes = Vec::with_capacity(updates.len());
+        for update in updates {
+            let reference_uuid = update.reference_uuid();
+            // filename of the change if the file was updated
+            let filename: Option<Option<String>> = match update {
+                FileUpdate::Insert(file) => {
+                    let query = r"INSERT INTO file (reference_uuid, owner_uuid, filename, relative_path, absolute_path, size, mime_type,
+                                                    sha256, scan_status, starred, tags, metadata)
+                                  VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)";
+                    sqlx::query(query)
+                        .bind(file.reference_uuid)
+                        .bind(owner_uuid)
+                        .bind(&file.filename)
+                        .bind(&file.relative_path)
+                        .bind(&file.absolute_path)
+                        .bind(file.size as i64)
+                        .bind(&file.mime_type)
+                        .bind(&file.sha256)
+                        .bind(file.scan_status.to_string())
+                        .bind(file.starred)
+                        .bind(&file.tags)
+                        .bind(Json(&file.metadata))
+                        .execute(&mut *tx)
+                        .await?;
+                    Some(Some(file.filename.clone()))
+                },
+                FileUpdate::Filename { filename, .. } => {
+                    sqlx::query_scalar(r"UPDATE file SET filename = $1 WHERE reference_uuid = $2 AND owner_uuid = $3 RETURNING filename")
+                        .bind(filename)
+                        .bind(reference_uuid)
+                        .bind(owner_uuid)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                        .map(Some)
+                },
+                FileUpdate::Tags { add, remove, max_tags, .. } => {
+                    // the new tags are computed twice, once to check the limit
+                    let query = r"UPDATE file SET tags = ARRAY(
+                                      SELECT DISTINCT tag FROM unnest(tags || $1::TEXT[]) AS tag WHERE tag <> ALL($2) ORDER BY tag
+                                  )
+                                  WHERE reference_uuid = $3 AND owner_uuid = $4
+                                    AND (SELECT COUNT(DISTINCT tag) FROM unnest(tags || $1::TEXT[]) AS tag WHERE tag <> ALL($2)) <= $5";
+                    update_file(&mut tx, sqlx::query(query)
+                        .bind(add)
+                        .bind(remove)
+                        .bind(reference_uuid)
+                        .bind(owner_uuid)
+                        .bind((*max_tags).min(i64::MAX as usize) as i64)
+                    ).await?.then_some(None)
+                },
+                FileUpdate::ReplaceTags { tags, .. } => {
+                    update_file(&mut tx, sqlx::query(r"UPDATE file SET tags = $1 WHERE reference_uuid = $2 AND owner_uuid = $3")
+                        .bind(tags)
+                        .bind(reference_uuid)
+                        .bind(owner_uuid)
+                    ).await?.then_some(None)
+                },
+                FileUpdate::Starred { starred, .. } => {
+                    update_file(&mut tx, sqlx::query(r"UPDATE file SET starred = $1 WHERE reference_uuid = $2 AND owner_uuid = $3")
+                        .bind(starred)
+                        .bind(reference_uuid)
+                        .bind(owner_uuid)
+                    ).await?.then_some(None)
+                },
+                FileUpdate::Metadata { key, value, .. } => {
+                    // a null value removes the key
+                    let query = r"UPDATE file SET metadata = CASE WHEN $2::TEXT IS NULL THEN metadata - $1::TEXT
+                                                                  ELSE metadata || jsonb_build_object($1::TEXT, $2::TEXT) END
+                                  WHERE reference_uuid = $3 AND owner_uuid = $4";
+                    update_file(&mut tx, sqlx::query(query)
+                        .bind(key)
+                        .bind(value)
+                        .bind(reference_uuid)
+                        .bind(owner_uuid)
+                    ).await?.then_some(None)
+                },
+                FileUpdate::Scanned { status, .. } => {
+                    update_file(&mut tx, sqlx::query(r"UPDATE file SET scan_status = $1 WHERE reference_uuid = $2 AND owner_uuid = $3")
+                        .bind(status.to_string())
+                        .bind(reference_uuid)
+                        .bind(owner_uuid)
+                    ).await?.then_some(None)
+                },
+                FileUpdate::Content { size, sha256, .. } => {
+                    update_file(&mut tx, sqlx::query(r"UPDATE file SET size = $1, sha256 = $2 WHERE reference_uuid = $3 AND owner_uuid = $4")
+                        .bind(*size as i64)
+                        .bind(sha256)
+                        .bind(reference_uuid)
+                        .bind(owner_uuid)
+                    ).await?.then_some(None)
+                },
+                FileUpdate::Delete { .. } => {
+                    sqlx::query_scalar(r"DELETE FROM file WHERE reference_uuid = $1 AND owner_uuid = $2 RETURNING filename")
+                        .bind(reference_uuid)
+                        .bind(owner_uuid)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                        .map(Some)
+                },
+            };
+            changes.push(match filename {
+                Some(filename) => Some(journal(&mut tx, owner_uuid, reference_uuid, update.kind(), filename.as_deref()).await?),
+                None => None,
+            });
+        }
+        tx.commit().await?;
+
+        Ok(changes)
+    }
+
+    async fn last_change_id(&self, owner_uuid: Uuid) -> Result<i64, RepositoryError> {
+        let id: Option<i64> = sqlx::query_scalar(r"SELECT MAX(id) FROM file_change WHERE owner_uuid = $1")
+            .bind(owner_uuid)
+            .fetch_one(self.db_pool.as_ref())
+            .await?;
+
+        Ok(id.unwrap_or(0))
+    }
+
+    async fn changes(&self, owner_uuid: Uuid, cursor: i64, limit: usize) -> Result<Vec<Change>, RepositoryError> {
+        let rows = sqlx::query(r"SELECT * FROM file_change WHERE owner_uuid = $1 AND id > $2 ORDER BY id LIMIT $3")
+            .bind(owner_uuid)
+            .bind(cursor)
+            .bind(limit.min(i64::MAX as usize) as i64)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(Change::from_pg_row).collect::<Result<_, _>>()?)
+    }
+}
+
+/// Keeps webhooks in the `webhook` table and their queue in `webhook_delivery`
+pub struct PgWebhookRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgWebhookRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for PgWebhookRepository {
+    async fn insert(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        sqlx::query(r"INSERT INTO webhook (uuid, owner_uuid, url, secret, events) VALUES ($1, $2, $3, $4, $5)")
+            .bind(webhook.uuid)
+            .bind(webhook.owner_uuid)
+            .bind(&webhook.url)
+            .bind(&webhook.secret)
+            .bind(&webhook.events)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<Option<Webhook>, RepositoryError> {
+        let row = sqlx::query(r"SELECT * FROM webhook WHERE uuid = $1 AND owner_uuid = $2")
+            .bind(uuid)
+            .bind(owner_uuid)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(Webhook::from_pg_row).transpose()?)
+    }
+
+    async fn list(&self, owner_uuid: Uuid) -> Result<Vec<Webhook>, RepositoryError> {
+        let rows = sqlx::query(r"SELECT * FROM webhook WHERE owner_uuid = $1 ORDER BY timestamp, uuid")
+            .bind(owner_uuid)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(Webhook::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn delete(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(r"DELETE FROM webhook WHERE uuid = $1 AND owner_uuid = $2")
+            .bind(uuid)
+            .bind(owner_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn subscribed(&self, owner_uuid: Uuid, event: &str) -> Result<Vec<Webhook>, RepositoryError> {
+        let rows = sqlx::query(r"SELECT * FROM webhook WHERE owner_uuid = $1 AND $2 = ANY(events) ORDER BY timestamp, uuid")
+            .bind(owner_uuid)
+            .bind(event)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(Webhook::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn enqueue(&self, webhook_uuid: Uuid, event: &str, payload: &str) -> Result<Delivery, RepositoryError> {
+        let row = sqlx::query(r"INSERT INTO webhook_delivery (webhook_uuid, event, payload) VALUES ($1, $2, $3) RETURNING *")
+            .bind(webhook_uuid)
+            .bind(event)
+            .bind(payload)
+            .fetch_one(self.db_pool.as_ref())
+            .await?;
+
+        Ok(Delivery::from_pg_row(row)?)
+    }
+
+    async fn claim(&self, id: Option<i64>, lease: i64) -> Result<Option<(Delivery, Webhook)>, RepositoryError> {
+        let now = Utc::now();
+        // skipping locked rows lets several workers claim at once without waiting on each other
+        let query = r"UPDATE webhook_delivery SET attempts = attempts + 1, lease_until = $1
+                      WHERE id = (
+                          SELECT id FROM webhook_delivery
+                          WHERE status = 'pending' AND next_attempt <= $2 AND (lease_until IS NULL OR lease_until < $2)
+                              AND ($3::BIGINT IS NULL OR id = $3)
+                          ORDER BY next_attempt LIMIT 1 FOR UPDATE SKIP LOCKED
+                      ) RETURNING *";
+        let Some(row) = sqlx::query(query)
+            .bind(now + TimeDelta::seconds(lease))
+            .bind(now)
+            .bind(id)
+            .fetch_optional(self.db_pool.as_ref())
+            .await? else {
+            return Ok(None)
+        };
+        let delivery = Delivery::from_pg_row(row)?;
+
+        let row = sqlx::query(r"SELECT * FROM webhook WHERE uuid = $1")
+            .bind(delivery.webhook_uuid)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+        // deleted in between, its deliveries are gone as well
+        Ok(row.map(Webhook::from_pg_row).transpose()?.map(|webhook| (delivery, webhook)))
+    }
+
+    async fn finish(&self, id: i64, attempt: &Attempt) -> Result<(), RepositoryError> {
+        let query = r"UPDATE webhook_delivery SET status = $1, response_status = $2, error = $3, next_attempt = $4, lease_until = NULL
+                      WHERE id = $5";
+        sqlx::query(query)
+            .bind(attempt.status.to_string())
+            .bind(attempt.response_status.map(i32::from))
+            .bind(&attempt.error)
+            .bind(DateTime::from_timestamp(attempt.next_attempt, 0).unwrap_or_else(Utc::now))
+            .bind(id)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn deliveries(&self, webhook_uuid: Uuid, limit: usize) -> Result<Vec<Delivery>, RepositoryError> {
+        let rows = sqlx::query(r"SELECT * FROM webhook_delivery WHERE webhook_uuid = $1 ORDER BY id DESC LIMIT $2")
+            .bind(webhook_uuid)
+            .bind(limit.min(i64::MAX as usize) as i64)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(Delivery::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError> {
+        let result = sqlx::query(r"DELETE FROM webhook_delivery WHERE status <> 'pending' AND timestamp < $1")
+            .bind(DateTime::from_timestamp(before, 0).unwrap_or_else(Utc::now))
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Keeps the audit log in the `audit_log` table, which refuses updates
+pub struct PgAuditRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgAuditRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl AuditRepository for PgAuditRepository {
+    async fn append(&self, entry: &AuditEntry) -> Result<(), RepositoryError> {
+        let query = r"INSERT INTO audit_log (actor_uuid, actor, action, target_uuid, ip, user_agent, outcome, detail)
+                      VALUES ($1, $2, $3, $4, $5, $6, $7, $8)";
+        sqlx::query(query)
+            .bind(entry.actor_uuid)
+            .bind(&entry.actor)
+            .bind(entry.action.to_string())
+            .bind(entry.target_uuid)
+            .bind(&entry.ip)
+            .bind(&entry.user_agent)
+            .bind(entry.outcome.to_string())
+            .bind(&entry.detail)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn query(&self, filter: &AuditFilter, limit: usize) -> Result<Vec<AuditEntry>, RepositoryError> {
+        let query = r"SELECT * FROM audit_log
+                      WHERE ($1::UUID IS NULL OR actor_uuid = $1)
+                          AND ($2::VARCHAR IS NULL OR action = $2)
+                          AND ($3::UUID IS NULL OR target_uuid = $3)
+                          AND ($4::VARCHAR IS NULL OR outcome = $4)
+                          AND ($5::TIMESTAMPTZ IS NULL OR timestamp >= $5)
+                          AND ($6::TIMESTAMPTZ IS NULL OR timestamp < $6)
+                          AND ($7::BIGINT IS NULL OR id < $7)
+                      ORDER BY id DESC LIMIT $8";
+        let rows = sqlx::query(query)
+            .bind(filter.actor_uuid)
+            .bind(filter.action.map(|a| a.to_string()))
+            .bind(filter.target_uuid)
+            .bind(filter.outcome.map(|o| o.to_string()))
+            .bind(filter.from.and_then(|t| DateTime::from_timestamp(t, 0)))
+            // `to` is inclusive down to the second
+            .bind(filter.to.and_then(|t| DateTime::from_timestamp(t + 1, 0)))
+            .bind(filter.before)
+            .bind(limit.min(i64::MAX as usize) as i64)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(AuditEntry::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError> {
+        let result = sqlx::query(r"DELETE FROM audit_log WHERE timestamp < $1")
+            .bind(DateTime::from_timestamp(before, 0).unwrap_or_else(Utc::now))
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Keeps scheduled deletions in the `account_deletion` table
+pub struct PgDeletionRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgDeletionRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl DeletionRepository for PgDeletionRepository {
+    async fn get(&self, user_uuid: Uuid) -> Result<Option<AccountDeletion>, RepositoryError> {
+        let row = sqlx::query(r"SELECT * FROM account_deletion WHERE user_uuid = $1")
+            .bind(user_uuid)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(AccountDeletion::from_pg_row).transpose()?)
+    }
+
+    async fn schedule(&self, user_uuid: Uuid, scheduled_for: i64) -> Result<AccountDeletion, RepositoryError> {
+        let query = r"INSERT INTO account_deletion (user_uuid, scheduled_for) VALUES ($1, $2)
+                      ON CONFLICT (user_uuid) DO UPDATE SET user_uuid = EXCLUDED.user_uuid
+                      RETURNING *";
+        let row = sqlx::query(query)
+            .bind(user_uuid)
+            .bind(DateTime::from_timestamp(scheduled_for, 0))
+            .fetch_one(self.db_pool.as_ref())
+            .await?;
+
+        Ok(AccountDeletion::from_pg_row(row)?)
+    }
+
+    async fn cancel(&self, user_uuid: Uuid) -> Result<bool, RepositoryError> {
+        let query = r"DELETE FROM account_deletion WHERE user_uuid = $1 AND lease_until IS NULL AND scheduled_for > $2";
+        let result = sqlx::query(query)
+            .bind(user_uuid)
+            .bind(Utc::now())
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn due(&self) -> Result<Vec<AccountDeletion>, RepositoryError> {
+        let query = r"SELECT * FROM account_deletion
+                      WHERE scheduled_for <= $1 AND (lease_until IS NULL OR lease_until < $1)
+                      ORDER BY scheduled_for";
+        let rows = sqlx::query(query)
+            .bind(Utc::now())
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(AccountDeletion::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn claim(&self, user_uuid: Uuid, lease: i64) -> Result<Option<AccountDeletion>, RepositoryError> {
+        let now = Utc::now();
+        let query = r"UPDATE account_deletion SET lease_until = $1
+                      WHERE user_uuid = $3 AND scheduled_for <= $2 AND (lease_until IS NULL OR lease_until < $2)
+                      RETURNING *";
+        let row = sqlx::query(query)
+            .bind(now + TimeDelta::seconds(lease))
+            .bind(now)
+            .bind(user_uuid)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(AccountDeletion::from_pg_row).transpose()?)
+    }
+
+    async fn renew(&self, user_uuid: Uuid, lease: i64) -> Result<(), RepositoryError> {
+        sqlx::query(r"UPDATE account_deletion SET lease_until = $1 WHERE user_uuid = $2")
+            .bind(Utc::now() + TimeDelta::seconds(lease))
+            .bind(user_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, user_uuid: Uuid) -> Result<(), RepositoryError> {
+        sqlx::query(r"DELETE FROM account_deletion WHERE user_uuid = $1")
+            .bind(user_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Keeps pending email changes in the `email_change` table
+pub struct PgEmailChangeRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgEmailChangeRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl EmailChangeRepository for PgEmailChangeRepository {
+    async fn upsert(&self, change: &EmailChange, token_hash: &str) -> Result<(), RepositoryError> {
+        let query = r"INSERT INTO email_change (token_hash, user_uuid, new_email, expires) VALUES ($1, $2, $3, $4)
+                      ON CONFLICT (user_uuid) DO UPDATE SET
+                          token_hash = EXCLUDED.token_hash,
+                          new_email = EXCLUDED.new_email,
+                          expires = EXCLUDED.expires";
+        sqlx::query(query)
+            .bind(token_hash)
+            .bind(change.user_uuid)
+            .bind(&change.new_email)
+            .bind(DateTime::from_timestamp(change.expires, 0))
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, user_uuid: Uuid) -> Result<Option<EmailChange>, RepositoryError> {
+        let row = sqlx::query(r"SELECT * FROM email_change WHERE user_uuid = $1 AND expires > $2")
+            .bind(user_uuid)
+            .bind(Utc::now())
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(EmailChange::from_pg_row).transpose()?)
+    }
+
+    async fn take(&self, token_hash: &str) -> Result<Option<EmailChange>, RepositoryError> {
+        let row = sqlx::query(r"DELETE FROM email_change WHERE token_hash = $1 RETURNING *")
+            .bind(token_hash)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(EmailChange::from_pg_row).transpose()?)
+    }
+
+    async fn delete(&self, user_uuid: Uuid) -> Result<(), RepositoryError> {
+        sqlx::query(r"DELETE FROM email_change WHERE user_uuid = $1")
+            .bind(user_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Keeps linked identities in the `external_identity` table
+pub struct PgIdentityRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgIdentityRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl IdentityRepository for PgIdentityRepository {
+    async fn link(&self, identity: &ExternalIdentity) -> Result<(), RepositoryError> {
+        sqlx::query(r"INSERT INTO external_identity (issuer, subject, user_uuid) VALUES ($1, $2, $3)")
+            .bind(&identity.issuer)
+            .bind(&identity.subject)
+            .bind(identity.user_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn user_uuid(&self, issuer: &str, subject: &str) -> Result<Option<Uuid>, RepositoryError> {
+        let user_uuid = sqlx::query_scalar(r"SELECT user_uuid FROM external_identity WHERE issuer = $1 AND subject = $2")
+            .bind(issuer)
+            .bind(subject)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(user_uuid)
+    }
+
+    async fn unlink_all(&self, user_uuid: Uuid) -> Result<(), RepositoryError> {
+        sqlx::query(r"DELETE FROM external_identity WHERE user_uuid = $1")
+            .bind(user_uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Keeps the job queue in the `job` table and the runs of recurring jobs in `job_schedule`
+pub struct PgJobRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgJobRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl JobRepository for PgJobRepository {
+    async fn insert(&self, kind: &str, payload: &serde_json::Value, max_attempts: u32, run_at: i64, unique_key: Option<&str>)
+        -> Result<Option<i64>, RepositoryError> {
+        let query = r"INSERT INTO job (kind, payload, max_attempts, run_at, unique_key) VALUES ($1, $2, $3, $4, $5)
+                      ON CONFLICT (unique_key) WHERE status = 'queued' DO NOTHING
+                      RETURNING id";
+        let id = sqlx::query_scalar(query)
+            .bind(kind)
+            .bind(payload)
+            .bind(max_attempts as i32)
+            .bind(DateTime::from_timestamp(run_at, 0))
+            .bind(unique_key)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(id)
+    }
+
+    async fn claim(&self, kinds: &[String], lease: i64) -> Result<Option<QueuedJob>, RepositoryError> {
+        let now = Utc::now();
+        let query = r"UPDATE job SET status = 'running', attempts = attempts + 1, lease_until = $1, lease_token = $4
+                      WHERE id = (
+                          SELECT id FROM job
+                          WHERE kind = ANY($3) AND run_at <= $2
+                              AND (status = 'queued' OR (status = 'running' AND lease_until < $2 AND attempts < max_attempts))
+                          ORDER BY run_at
+                          LIMIT 1
+                          FOR UPDATE SKIP LOCKED
+                      )
+                      RETURNING *";
+        let row = sqlx::query(query)
+            .bind(now + TimeDelta::seconds(lease))
+            .bind(now)
+            .bind(kinds)
+            .bind(Uuid::new_v4())
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(QueuedJob::from_pg_row).transpose()?)
+    }
+
+    async fn renew(&self, id: i64, lease_token: Uuid, lease: i64) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(r"UPDATE job SET lease_until = $1 WHERE id = $2 AND lease_token = $3 AND status = 'running'")
+            .bind(Utc::now() + TimeDelta::seconds(lease))
+            .bind(id)
+            .bind(lease_token)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn succeed(&self, id: i64, lease_token: Uuid) -> Result<bool, RepositoryError> {
+        let query = r"UPDATE job SET status = 'succeeded', lease_until = NULL, lease_token = NULL, finished_at = $1
+                      WHERE id = $2 AND lease_token = $3 AND status = 'running'";
+        let result = sqlx::query(query)
+            .bind(Utc::now())
+            .bind(id)
+            .bind(lease_token)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn fail(&self, id: i64, lease_token: Uuid, error: &str, retry_at: Option<i64>) -> Result<bool, RepositoryError> {
+        let now = Utc::now();
+        let (status, run_at, finished_at) = match retry_at {
+            Some(retry_at) => (JobStatus::Queued, DateTime::from_timestamp(retry_at, 0).unwrap_or(now), None),
+            None => (JobStatus::Failed, now, Some(now)),
+        };
+
+        let query = r"UPDATE job SET status = $1, run_at = $2, lease_until = NULL, lease_token = NULL, last_error = $3, finished_at = $4
+                      WHERE id = $5 AND lease_token = $6 AND status = 'running'";
+        let result = sqlx::query(query)
+            .bind(status.to_string())
+            .bind(run_at)
+            .bind(error)
+            .bind(finished_at)
+            .bind(id)
+            .bind(lease_token)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<QueuedJob>, RepositoryError> {
+        let row = sqlx::query(r"SELECT * FROM job WHERE id = $1")
+            .bind(id)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(QueuedJob::from_pg_row).transpose()?)
+    }
+
+    async fn list(&self, status: Option<JobStatus>, kind: Option<&str>, limit: usize, offset: usize)
+        -> Result<Vec<QueuedJob>, RepositoryError> {
+        let query = r"SELECT * FROM job WHERE ($1::VARCHAR IS NULL OR status = $1) AND ($2::VARCHAR IS NULL OR kind = $2)
+                      ORDER BY id DESC LIMIT $3 OFFSET $4";
+        let rows = sqlx::query(query)
+            .bind(status.map(|s| s.to_string()))
+            .bind(kind)
+            .bind(limit.min(i64::MAX as usize) as i64)
+            .bind(offset.min(i64::MAX as usize) as i64)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(QueuedJob::from_pg_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn counts(&self) -> Result<Vec<JobCount>, RepositoryError> {
+        let query = r"SELECT kind, status, COUNT(*) AS count FROM job GROUP BY kind, status ORDER BY kind, status";
+        let rows = sqlx::query(query)
+            .fetch_all(self.db_pool.as_ref())
+            .await?;
+
+        let mut counts = Vec::with_capacity(rows.len());
+        for row in rows {
+            counts.push(JobCount {
+                kind: row.try_get("kind")?,
+                status: row.try_get::<String, _>("status")?.parse().map_err(|e: String| RepositoryError::Backend(e.into()))?,
+                count: row.try_get("count")?,
+            });
+        }
+        Ok(counts)
+    }
+
+    async fn retry(&self, id: i64) -> Result<bool, RepositoryError> {
+        let query = r"UPDATE job SET status = 'queued', attempts = 0, run_at = $1, finished_at = NULL, unique_key = NULL
+                      WHERE id = $2 AND status = 'failed'";
+        let result = sqlx::query(query)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError> {
+        let query = r"UPDATE job SET status = 'failed', lease_until = NULL, lease_token = NULL, finished_at = $1,
+                          last_error = COALESCE(last_error, 'worker stopped while running the job')
+                      WHERE status = 'running' AND lease_until < $1 AND attempts >= max_attempts";
+        sqlx::query(query)
+            .bind(Utc::now())
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        let result = sqlx::query(r"DELETE FROM job WHERE finished_at < $1")
+            .bind(DateTime::from_timestamp(before, 0))
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn schedule_due(&self, name: &str, next_run: i64, due_when_new: bool) -> Result<bool, RepositoryError> {
+        // xmax is only zero for inserted rows
+        let query = r"INSERT INTO job_schedule (name, next_run) VALUES ($1, $3)
+                      ON CONFLICT (name) DO UPDATE SET next_run = $3 WHERE job_schedule.next_run <= $2
+                      RETURNING xmax = 0";
+        let inserted: Option<bool> = sqlx::query_scalar(query)
+            .bind(name)
+            .bind(Utc::now())
+            .bind(DateTime::from_timestamp(next_run, 0))
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(inserted.is_some_and(|inserted| !inserted || due_when_new))
+    }
+}
+
+/// Keeps the progress of background batches in the `batch` table
+pub struct PgBatchRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgBatchRepository {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl BatchRepository for PgBatchRepository {
+    async fn insert(&self, batch: &Batch) -> Result<(), RepositoryError> {
+        sqlx::query(r"INSERT INTO batch (uuid, owner_uuid, total, results, finished) VALUES ($1, $2, $3, $4, $5)")
+            .bind(batch.uuid)
+            .bind(batch.owner_uuid)
+            .bind(batch.total as i32)
+            .bind(Json(&batch.results))
+            .bind(batch.finished)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<Option<Batch>, RepositoryError> {
+        let row = sqlx::query(r"SELECT * FROM batch WHERE uuid = $1 AND owner_uuid = $2")
+            .bind(uuid)
+            .bind(owner_uuid)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(row.map(Batch::from_pg_row).transpose()?)
+    }
+
+    async fn record(&self, uuid: Uuid, results: &[ItemResult]) -> Result<(), RepositoryError> {
+        sqlx::query(r"UPDATE batch SET results = results || $2 WHERE uuid = $1")
+            .bind(uuid)
+            .bind(Json(results))
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn finish(&self, uuid: Uuid) -> Result<(), RepositoryError> {
+        sqlx::query(r"UPDATE batch SET finished = true WHERE uuid = $1")
+            .bind(uuid)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError> {
+        let result = sqlx::query(r"DELETE FROM batch WHERE finished AND timestamp < $1")
+            .bind(DateTime::from_timestamp(before, 0).unwrap_or_else(Utc::now))
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,50 @@
+use crate::models::user::{Permission, User};
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Storage of user accounts \
+/// Setters return false if the user doesn't exist
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// Fails with `Conflict` if the username is taken
+    async fn insert(&self, user: &User) -> Result<(), RepositoryError>;
+
+    async fn count(&self) -> Result<usize, RepositoryError>;
+
+    /// Checks that the backing store answers, used by the readiness probe
+    async fn ping(&self) -> Result<(), RepositoryError>;
+
+    async fn get(&self, uuid: Uuid) -> Result<Option<User>, RepositoryError>;
+
+    async fn get_by_username(&self, username: &str) -> Result<Option<User>, RepositoryError>;
+
+    /// Users whose username or email contains `search`, ignoring case, ordered by username
+    async fn list(&self, search: &str, limit: usize, offset: usize) -> Result<Vec<User>, RepositoryError>;
+
+    /// Fails with `Conflict` if the username is taken
+    async fn set_username(&self, uuid: Uuid, username: &str) -> Result<bool, RepositoryError>;
+
+    /// Replaces the password hash and the token id, which ends all sessions \
+    /// `reset_required` forces the user to change the password again before doing anything else
+    async fn set_password(&self, uuid: Uuid, password: &str, tokenid: Uuid, reset_required: bool)
+        -> Result<bool, RepositoryError>;
+
+    /// A new token id ends all sessions
+    async fn set_tokenid(&self, uuid: Uuid, tokenid: Uuid) -> Result<bool, RepositoryError>;
+
+    /// Conflict if another user already has the email, compared case-insensitively
+    async fn set_email(&self, uuid: Uuid, email: &str) -> Result<bool, RepositoryError>;
+
+    async fn set_permission(&self, uuid: Uuid, permission: Permission) -> Result<bool, RepositoryError>;
+
+    async fn set_quota(&self, uuid: Uuid, quota: Option<usize>) -> Result<bool, RepositoryError>;
+
+    async fn set_disabled(&self, uuid: Uuid, disabled: bool) -> Result<bool, RepositoryError>;
+
+    /// The user has to change their password before doing anything else
+    async fn require_password_reset(&self, uuid: Uuid) -> Result<bool, RepositoryError>;
+
+    /// Files of the user have to be deleted before
+    async fn delete(&self, uuid: Uuid) -> Result<bool, RepositoryError>;
+}
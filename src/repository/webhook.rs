@@ -0,0 +1,38 @@
+use crate::models::webhook::{Attempt, Delivery, Webhook};
+use crate::repository::error::RepositoryError;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Storage of webhooks and the queue of their deliveries
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn insert(&self, webhook: &Webhook) -> Result<(), RepositoryError>;
+
+    /// Only returns webhooks of `owner_uuid`
+    async fn get(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<Option<Webhook>, RepositoryError>;
+
+    /// All webhooks of a user, oldest first
+    async fn list(&self, owner_uuid: Uuid) -> Result<Vec<Webhook>, RepositoryError>;
+
+    /// Also drops its deliveries, returns false if the webhook doesn't exist or isn't owned by `owner_uuid`
+    async fn delete(&self, uuid: Uuid, owner_uuid: Uuid) -> Result<bool, RepositoryError>;
+
+    /// Webhooks of `owner_uuid` subscribed to `event`
+    async fn subscribed(&self, owner_uuid: Uuid, event: &str) -> Result<Vec<Webhook>, RepositoryError>;
+
+    /// Queues a pending delivery that is due right away
+    async fn enqueue(&self, webhook_uuid: Uuid, event: &str, payload: &str) -> Result<Delivery, RepositoryError>;
+
+    /// Takes a due pending delivery, the one with `id` if given, and counts the attempt \
+    /// Nobody else can claim it for `lease` seconds, so a crashed worker only delays it
+    async fn claim(&self, id: Option<i64>, lease: i64) -> Result<Option<(Delivery, Webhook)>, RepositoryError>;
+
+    /// Records the outcome of the attempt and releases the delivery
+    async fn finish(&self, id: i64, attempt: &Attempt) -> Result<(), RepositoryError>;
+
+    /// Latest deliveries of a webhook, newest first
+    async fn deliveries(&self, webhook_uuid: Uuid, limit: usize) -> Result<Vec<Delivery>, RepositoryError>;
+
+    /// Deletes delivered and failed deliveries created before the unix timestamp, returns how many
+    async fn prune(&self, before: i64) -> Result<u64, RepositoryError>;
+}
@@ -0,0 +1,176 @@
+use crate::handlers::admin::authorize::admin;
+use crate::handlers::docs::spec::{docs_css, docs_js, docs_ui, openapi_json};
+use crate::handlers::health::{healthz, readyz};
+use crate::handlers::metrics::{metrics, track_metrics};
+use crate::handlers::ratelimit::rate_limit;
+use crate::handlers::request_id::request_id;
+use crate::handlers::users::authenticate::{auth, auth_pending_reset};
+use crate::handlers::users::update;
+use crate::handlers::{admin, audit, changes, files, users, webhooks};
+use crate::models::appstate::{Appstate, AppstateWrapper};
+use crate::openapi::ApiDoc;
+use axum::extract::DefaultBodyLimit;
+use axum::http::{header, HeaderValue, Method};
+use axum::routing::get;
+use axum::{middleware, Extension, Router};
+use std::sync::Arc;
+use tower::ServiceBuilder;
+use tower_http::cors::{Any, CorsLayer};
+use tower_http::set_header::SetResponseHeaderLayer;
+use tower_http::trace::TraceLayer;
+use utoipa::OpenApi;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+/// Builds the whole application \
+/// Routes are registered through `routes!` only, so every route ends up in the OpenAPI document
+pub fn app(appstate: Arc<Appstate>) -> Router {
+    let wrapped_appstate = AppstateWrapper(appstate.clone());
+
+    let cors = CorsLayer::new()
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_origin(Any);
+
+    // routes with their own policy like downloads keep it, the config is validated on startup
+    let cache_control = HeaderValue::from_str(&appstate.config.cache.api)
+        .unwrap_or(HeaderValue::from_static("no-store"));
+
+    // middleware is only applied to matched routes, unknown paths are a plain 404
+    let protected_file_routes = OpenApiRouter::new()
+        .routes(routes!(files::upload::stream_upload))
+        .routes(routes!(files::download::serve_file))
+        .routes(routes!(files::delete::delete_file))
+        .routes(routes!(files::search::search_files))
+        .routes(routes!(files::list::list_files))
+        .routes(routes!(files::tags::set_tags))
+        .routes(routes!(files::tags::remove_tag))
+        .routes(routes!(files::tags::bulk_tag))
+        .routes(routes!(files::starred::set_starred))
+        .routes(routes!(files::metadata::set_metadata, files::metadata::remove_metadata))
+        .routes(routes!(files::batch::run_batch))
+        .routes(routes!(files::batch::batch_status))
+        .route_layer(
+            ServiceBuilder::new()
+                .layer(DefaultBodyLimit::max(appstate.config.server.body_limit))
+                .layer(middleware::from_fn(auth))
+                .layer(middleware::from_fn(rate_limit))
+                .layer(Extension(wrapped_appstate.clone()))
+        );
+
+    let change_routes = OpenApiRouter::new()
+        .routes(routes!(changes::list_changes))
+        .routes(routes!(changes::stream_changes))
+        .route_layer(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn(auth))
+                .layer(middleware::from_fn(rate_limit))
+                .layer(Extension(wrapped_appstate.clone()))
+        );
+
+    let webhook_routes = OpenApiRouter::new()
+        .routes(routes!(webhooks::list::list_webhooks, webhooks::create::create_webhook))
+        .routes(routes!(webhooks::delete::delete_webhook))
+        .routes(routes!(webhooks::ping::ping_webhook))
+        .routes(routes!(webhooks::deliveries::list_deliveries))
+        .route_layer(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn(auth))
+                .layer(middleware::from_fn(rate_limit))
+                .layer(Extension(wrapped_appstate.clone()))
+        );
+
+    let audit_routes = OpenApiRouter::new()
+        .routes(routes!(audit::list_audit))
+        .routes(routes!(audit::export_audit))
+        .route_layer(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn(auth))
+                .layer(middleware::from_fn(rate_limit))
+                .layer(Extension(wrapped_appstate.clone()))
+        );
+
+    // the only route a user with a forced password reset can reach
+    let password_routes = OpenApiRouter::new()
+        .routes(routes!(update::password::change::change_password))
+        .route_layer(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn(auth_pending_reset))
+                .layer(Extension(wrapped_appstate.clone()))
+        );
+
+    let protected_user_routes = OpenApiRouter::new()
+        .routes(routes!(update::username::change::change_username))
+        .routes(routes!(update::email::change::change_email))
+        .routes(routes!(users::me::me))
+        .routes(routes!(users::oidc::login::oidc_link))
+        .routes(routes!(users::refresh::refresh_token))
+        .routes(routes!(users::delete::delete_account))
+        .routes(routes!(users::delete::cancel_deletion))
+        .route_layer(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn(auth))
+                .layer(Extension(wrapped_appstate.clone()))
+        );
+
+    let admin_routes = OpenApiRouter::new()
+        .routes(routes!(admin::users::list::list_users))
+        .routes(routes!(admin::users::get::get_user))
+        .routes(routes!(admin::users::permission::change_permission))
+        .routes(routes!(admin::users::quota::set_quota))
+        .routes(routes!(admin::users::disable::set_disabled))
+        .routes(routes!(admin::users::password_reset::force_password_reset))
+        .routes(routes!(admin::users::logout::force_logout))
+        .routes(routes!(admin::jobs::list::list_jobs))
+        .routes(routes!(admin::jobs::get::get_job))
+        .routes(routes!(admin::jobs::retry::retry_job))
+        .routes(routes!(admin::audit::list_audit_log))
+        .routes(routes!(admin::audit::export_audit_log))
+        .route_layer(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn(auth))
+                .layer(middleware::from_fn(admin))
+                .layer(Extension(wrapped_appstate.clone()))
+        );
+
+    let public_user_routes = OpenApiRouter::new()
+        .routes(routes!(users::new::new))
+        .routes(routes!(users::login::login))
+        .routes(routes!(update::email::confirm::confirm_email))
+        .routes(routes!(users::oidc::login::oidc_login))
+        .routes(routes!(users::oidc::callback::oidc_callback));
+
+    let (router, spec) = OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .nest("/v1/file", protected_file_routes)
+        .nest("/v1/changes", change_routes)
+        .nest("/v1/webhooks", webhook_routes)
+        .nest("/v1/audit", audit_routes)
+        .nest("/v1/user", password_routes)
+        .nest("/v1/user", protected_user_routes)
+        .nest("/v1/user", public_user_routes)
+        .nest("/v1/admin", admin_routes)
+        .split_for_parts();
+
+    let mut router = router
+        .route("/v1/openapi.json", get(openapi_json))
+        .route("/v1/docs", get(docs_ui))
+        .route("/v1/docs/swagger-ui.css", get(docs_css))
+        .route("/v1/docs/swagger-ui-bundle.js", get(docs_js))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz));
+    if appstate.config.metrics.enabled {
+        router = router.route("/metrics", get(metrics));
+    }
+
+    router
+        .layer(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn(request_id))
+                .layer(Extension(wrapped_appstate.clone()))
+                .layer(Extension(Arc::new(spec)))
+                .layer(middleware::from_fn(track_metrics))
+                .layer(TraceLayer::new_for_http())
+                .layer(cors)
+                .layer(SetResponseHeaderLayer::if_not_present(header::CACHE_CONTROL, cache_control))
+        )
+        .with_state(wrapped_appstate)
+}
@@ -0,0 +1,137 @@
+use crate::models::appstate::Appstate;
+use crate::models::identity::ExternalIdentity;
+use crate::models::user::{Permission, User};
+use crate::util::auth::provider::AuthProvider;
+use async_trait::async_trait;
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use serde::Deserialize;
+use std::error::Error;
+use std::time::Duration;
+
+/// Settings of the directory, the service account has to be allowed to search for users
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LdapConfig {
+    /// e.g. `ldaps://ldap.example.com:636`
+    pub url: String,
+    pub bind_dn: String,
+    pub bind_password: String,
+    /// Users are searched below this dn
+    pub base_dn: String,
+    /// `{username}` is replaced with the escaped username, e.g. `(uid={username})`
+    pub user_filter: String,
+    /// Attribute holding the dns of the groups of a user
+    pub group_attribute: String,
+    pub email_attribute: String,
+    /// Members of this group become admins, permissions are left alone if not set
+    pub admin_group: Option<String>,
+    /// Seconds to establish the connection
+    pub connect_timeout: u64,
+    /// Seconds the directory has to answer a bind or search
+    pub timeout: u64,
+}
+
+impl Default for LdapConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            bind_dn: String::new(),
+            bind_password: String::new(),
+            base_dn: String::new(),
+            user_filter: "(uid={username})".to_string(),
+            group_attribute: "memberOf".to_string(),
+            email_attribute: "mail".to_string(),
+            admin_group: None,
+            connect_timeout: 5,
+            timeout: 10,
+        }
+    }
+}
+
+/// Verifies by binding as the user found in the directory \
+/// Users are provisioned on their first login and linked through `external_identity`
+pub struct LdapAuthProvider {
+    config: LdapConfig,
+}
+
+impl LdapAuthProvider {
+    pub fn new(config: LdapConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for LdapAuthProvider {
+    async fn authenticate(&self, username: &str, password: String, appstate: &Appstate)
+        -> Result<Option<User>, Box<dyn Error + Send + Sync>> {
+        // an empty password would be an unauthenticated bind that always succeeds
+        if password.is_empty() {
+            return Ok(None)
+        }
+
+        // a hanging directory would hold the login request
+        let settings = LdapConnSettings::new().set_conn_timeout(Duration::from_secs(self.config.connect_timeout));
+        let timeout = Duration::from_secs(self.config.timeout);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url).await?;
+        ldap3::drive!(conn);
+
+        // find the user with the service account
+        ldap.with_timeout(timeout).simple_bind(&self.config.bind_dn, &self.config.bind_password).await?.success()?;
+
+        let filter = self.config.user_filter.replace("{username}", &ldap_escape(username));
+        let attributes = [self.config.group_attribute.as_str(), self.config.email_attribute.as_str()];
+        let (entries, _) = ldap
+            .with_timeout(timeout)
+            .search(&self.config.base_dn, Scope::Subtree, &filter, attributes)
+            .await?
+            .success()?;
+
+        // unknown or ambiguous usernames are rejected
+        let entry = match <[_; 1]>::try_from(entries) {
+            Ok([entry]) => SearchEntry::construct(entry),
+            Err(_) => {
+                ldap.unbind().await?;
+                return Ok(None)
+            }
+        };
+
+        // verify the password by binding as the user
+        let bound = ldap.with_timeout(timeout).simple_bind(&entry.dn, &password).await?.success().is_ok();
+        ldap.unbind().await?;
+        if !bound {
+            return Ok(None)
+        }
+
+        let email = entry.attrs.get(&self.config.email_attribute)
+            .and_then(|values| values.first())
+            .cloned();
+        let permission = self.config.admin_group.as_ref().map(|admin_group| {
+            let is_admin = entry.attrs.get(&self.config.group_attribute)
+                .is_some_and(|groups| groups.iter().any(|g| g.eq_ignore_ascii_case(admin_group)));
+            if is_admin { Permission::ADMIN } else { Permission::USER }
+        });
+
+        // log in the linked user or provision one
+        let identity = ExternalIdentity::new(self.config.url.clone(), entry.dn.to_lowercase(), uuid::Uuid::nil());
+        let mut user = match identity.get_user(appstate).await? {
+            Some(user) => user,
+            None => ExternalIdentity::provision_user(
+                identity.issuer,
+                identity.subject,
+                Some(username),
+                email,
+                appstate
+            ).await?,
+        };
+
+        // keep the permission in sync with the groups
+        if let Some(permission) = permission {
+            if user.permission != permission {
+                appstate.users.set_permission(user.uuid, permission.clone()).await?;
+                user.permission = permission;
+            }
+        }
+
+        Ok(Some(user))
+    }
+}
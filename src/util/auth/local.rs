@@ -0,0 +1,29 @@
+use crate::models::appstate::Appstate;
+use crate::models::user::User;
+use crate::util::auth::provider::AuthProvider;
+use async_trait::async_trait;
+use std::error::Error;
+
+/// Verifies against the argon2 password hashes in the `users` table
+#[derive(Default)]
+pub struct LocalAuthProvider;
+
+#[async_trait]
+impl AuthProvider for LocalAuthProvider {
+    async fn authenticate(&self, username: &str, password: String, appstate: &Appstate)
+        -> Result<Option<User>, Box<dyn Error + Send + Sync>> {
+        // unknown users are verified against a dummy hash to take the same time
+        let user = match appstate.users.get_by_username(username).await? {
+            Some(user) => user,
+            None => {
+                User::compare_dummy_password(password).map_err(|e| e.to_string())?;
+                return Ok(None)
+            }
+        };
+
+        match user.compare_passwords(password).map_err(|e| e.to_string())? {
+            true => Ok(Some(user)),
+            false => Ok(None),
+        }
+    }
+}
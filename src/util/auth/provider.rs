@@ -0,0 +1,12 @@
+use crate::models::appstate::Appstate;
+use crate::models::user::User;
+use async_trait::async_trait;
+use std::error::Error;
+
+/// Verifies username and password logins, `login` asks every configured provider in order
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// Returns the user if the credentials are valid for this provider, None if they aren't
+    async fn authenticate(&self, username: &str, password: String, appstate: &Appstate)
+        -> Result<Option<User>, Box<dyn Error + Send + Sync>>;
+}
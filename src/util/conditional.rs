@@ -0,0 +1,95 @@
+use axum::http::header::{IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, IF_UNMODIFIED_SINCE};
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Outcome of the preconditions of a request
+#[derive(Debug, PartialEq, Eq)]
+pub enum Precondition {
+    /// Handle the request as usual
+    Proceed,
+    /// The client's copy is current, answer with 304
+    NotModified,
+    /// The resource isn't in the state the client expects, answer with 412
+    Failed,
+}
+
+/// Strong validators of a stored file, its content never changes after the upload
+pub struct Validators {
+    etag: String,
+    last_modified: SystemTime,
+}
+
+impl Validators {
+    /// `sha256` is the hex checksum of the content, `timestamp` the upload in seconds since the epoch
+    pub fn new(sha256: &str, timestamp: usize) -> Self {
+        Self {
+            etag: format!("\"{}\"", sha256),
+            last_modified: UNIX_EPOCH + Duration::from_secs(timestamp as u64),
+        }
+    }
+
+    /// True if the request has a precondition, so callers only compute validators when needed
+    pub fn requested(headers: &HeaderMap) -> bool {
+        [IF_MATCH, IF_NONE_MATCH, IF_MODIFIED_SINCE, IF_UNMODIFIED_SINCE].iter().any(|name| headers.contains_key(name))
+    }
+
+    pub fn etag(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.etag).unwrap_or(HeaderValue::from_static("\"\""))
+    }
+
+    pub fn last_modified(&self) -> HeaderValue {
+        HeaderValue::from_str(&httpdate::fmt_http_date(self.last_modified)).unwrap_or(HeaderValue::from_static(""))
+    }
+
+    /// Evaluates the preconditions in the order of RFC 9110 section 13.2.2 \
+    /// `read` is true for GET and HEAD, they get a 304 instead of a 412 when `If-None-Match` matches
+    pub fn evaluate(&self, headers: &HeaderMap, read: bool) -> Precondition {
+        if let Some(tags) = header_str(headers, IF_MATCH) {
+            if !self.matches(tags, true) {
+                return Precondition::Failed
+            }
+        } else if let Some(since) = header_date(headers, IF_UNMODIFIED_SINCE) {
+            if self.last_modified > since {
+                return Precondition::Failed
+            }
+        }
+
+        if let Some(tags) = header_str(headers, IF_NONE_MATCH) {
+            if self.matches(tags, false) {
+                return if read { Precondition::NotModified } else { Precondition::Failed }
+            }
+        } else if let Some(since) = header_date(headers, IF_MODIFIED_SINCE).filter(|_| read) {
+            if self.last_modified <= since {
+                return Precondition::NotModified
+            }
+        }
+
+        Precondition::Proceed
+    }
+
+    /// Whether a requested range may be served, otherwise the whole file is sent \
+    /// `If-Range` has to name the current ETag or exactly the last modification date
+    pub fn range_applies(&self, headers: &HeaderMap) -> bool {
+        match header_str(headers, IF_RANGE) {
+            None => true,
+            Some(value) if value.starts_with('"') => value == self.etag,
+            Some(value) => httpdate::parse_http_date(value).is_ok_and(|date| date == self.last_modified),
+        }
+    }
+
+    /// `tags` is `*` or a list of entity tags, weak tags only match in the weak comparison
+    fn matches(&self, tags: &str, strong: bool) -> bool {
+        tags.split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag == self.etag || (!strong && tag.strip_prefix("W/") == Some(self.etag.as_str())))
+    }
+}
+
+fn header_str(headers: &HeaderMap, name: HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim)
+}
+
+/// Unparsable dates are ignored like the RFC asks for
+fn header_date(headers: &HeaderMap, name: HeaderName) -> Option<SystemTime> {
+    header_str(headers, name).and_then(|v| httpdate::parse_http_date(v).ok())
+}
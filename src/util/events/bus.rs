@@ -0,0 +1,29 @@
+use crate::models::change::Change;
+use async_trait::async_trait;
+use std::error::Error;
+use tokio::sync::broadcast;
+
+/// Changes buffered per subscriber, slower subscribers lag and catch up from the journal
+pub const CAPACITY: usize = 1024;
+
+/// Received by the subscribers of a bus
+#[derive(Clone, Debug)]
+pub enum BusEvent {
+    Change(Change),
+    /// Changes may have been lost, e.g. while the bus reconnected, subscribers catch up from the journal
+    Gap,
+}
+
+/// Delivers journaled changes to the clients connected to any instance
+#[async_trait]
+pub trait EventBus: Send + Sync {
+    /// Sends the change to every subscriber
+    async fn publish(&self, change: &Change) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Changes of all users published from now on
+    fn subscribe(&self) -> broadcast::Receiver<BusEvent>;
+
+    /// Receives changes of other instances, runs forever if the bus has to \
+    /// Returns right away for buses that only live in this process
+    async fn listen(&self) {}
+}
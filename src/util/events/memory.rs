@@ -0,0 +1,35 @@
+use crate::models::change::Change;
+use crate::util::events::bus::{BusEvent, EventBus, CAPACITY};
+use async_trait::async_trait;
+use std::error::Error;
+use tokio::sync::broadcast;
+
+/// Only reaches clients of this instance
+pub struct MemoryEventBus {
+    sender: broadcast::Sender<BusEvent>,
+}
+
+impl MemoryEventBus {
+    pub fn new() -> Self {
+        Self { sender: broadcast::Sender::new(CAPACITY) }
+    }
+}
+
+impl Default for MemoryEventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl EventBus for MemoryEventBus {
+    async fn publish(&self, change: &Change) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // fails only without subscribers
+        let _ = self.sender.send(BusEvent::Change(change.clone()));
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<BusEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,87 @@
+use crate::models::change::Change;
+use crate::util::events::bus::{BusEvent, EventBus, CAPACITY};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::{Pool, Postgres};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Channel of the notifications, shared by all instances on the database
+const CHANNEL: &str = "file_change";
+/// Wait before listening again after the connection broke
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Payload of a notification, the owner isn't part of the serialized change
+#[derive(Serialize, Deserialize)]
+struct Notification {
+    owner_uuid: Uuid,
+    change: Change,
+}
+
+/// Relays changes between instances with LISTEN/NOTIFY \
+/// Changes published by this instance also arrive through the database, so the order is the same everywhere
+pub struct PgEventBus {
+    db_pool: Arc<Pool<Postgres>>,
+    sender: broadcast::Sender<BusEvent>,
+}
+
+impl PgEventBus {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool, sender: broadcast::Sender::new(CAPACITY) }
+    }
+
+    /// Forwards notifications until the connection breaks
+    async fn relay(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut listener = PgListener::connect_with(self.db_pool.as_ref()).await?;
+        listener.listen(CHANNEL).await?;
+        // notifications sent while the connection was down are lost, fails only without subscribers
+        let _ = self.sender.send(BusEvent::Gap);
+        loop {
+            // `recv` would reconnect without telling
+            let Some(notification) = listener.try_recv().await? else {
+                return Err("connection lost".into())
+            };
+            let Notification { owner_uuid, mut change } = match serde_json::from_str(notification.payload()) {
+                Ok(o) => o,
+                Err(e) => {
+                    eprintln!("Ignoring malformed change notification: {}", e);
+                    continue
+                }
+            };
+            change.owner_uuid = owner_uuid;
+            // fails only without subscribers
+            let _ = self.sender.send(BusEvent::Change(change));
+        }
+    }
+}
+
+#[async_trait]
+impl EventBus for PgEventBus {
+    async fn publish(&self, change: &Change) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let payload = serde_json::to_string(&Notification { owner_uuid: change.owner_uuid, change: change.clone() })?;
+        sqlx::query(r"SELECT pg_notify($1, $2)")
+            .bind(CHANNEL)
+            .bind(payload)
+            .execute(self.db_pool.as_ref())
+            .await?;
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<BusEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Subscribers are told to catch up from the journal once listening again, notifications sent in between are lost
+    async fn listen(&self) {
+        loop {
+            if let Err(e) = self.relay().await {
+                eprintln!("Listening for changes failed, retrying in {}s: {}", RECONNECT_DELAY.as_secs(), e);
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
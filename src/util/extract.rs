@@ -0,0 +1,33 @@
+use crate::util::mime;
+use std::error::Error;
+
+/// Upper bound of the indexed text in bytes, postgres refuses search vectors above 1 MB
+pub const MAX_TEXT_LENGTH: usize = 256 * 1024;
+
+/// Reads the searchable text of a file, None for types without text \
+/// Plain text is read as utf-8, pdfs by their text layer
+pub async fn text(path: &str, mime_type: &str) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    if !mime::has_text(mime_type) {
+        return Ok(None)
+    }
+
+    let bytes = tokio::fs::read(path).await?;
+    let mut text = if mime_type == "application/pdf" {
+        // parsing is cpu bound and may panic on malformed files, which only fails the task
+        tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem(&bytes)).await??
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+
+    // postgres can't store nul characters in text
+    text.retain(|c| c != '\0');
+    if text.len() > MAX_TEXT_LENGTH {
+        let mut end = MAX_TEXT_LENGTH;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+
+    if text.trim().is_empty() { Ok(None) } else { Ok(Some(text)) }
+}
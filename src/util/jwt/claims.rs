@@ -1,11 +1,12 @@
-use std::error::Error;
-use std::sync::Arc;
+use axum_extra::extract::cookie::{Cookie, SameSite};
 use chrono::Utc;
 use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
 use uuid::Uuid;
+use crate::config::AuthConfig;
 use crate::models::user::User;
+use crate::repository::error::RepositoryError;
+use crate::repository::user::UserRepository;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct Claims {
@@ -13,14 +14,28 @@ pub(crate) struct Claims {
     pub(crate) tokenid: Uuid,
     pub(crate) iat: usize,
     pub(crate) exp: usize,
+    /// When the user last logged in, kept when the token is refreshed \
+    /// Tokens issued before it was added count as logged in long ago
+    #[serde(default)]
+    pub(crate) auth_time: usize,
 }
 
 
 impl Claims {
+    /// Cookie carrying the jwt \
+    /// Set below `/v1/user` but needed by every route, so its path is `/`
+    pub fn cookie(token: String) -> Cookie<'static> {
+        let mut cookie = Cookie::new("token", token);
+        cookie.set_http_only(true);
+        cookie.set_same_site(SameSite::Strict);
+        cookie.set_path("/");
+        cookie
+    }
+
     /// Validates Claims and returns User if valid
     pub async fn validate_claims(
-        &self, conn: &Arc<Pool<Postgres>>
-    ) -> Result<Option<User>, Box<dyn Error>> {
+        &self, users: &dyn UserRepository
+    ) -> Result<Option<User>, RepositoryError> {
 
         // check for timestamps
         if self.exp < Utc::now().timestamp() as usize {
@@ -28,14 +43,10 @@ impl Claims {
         }
 
         // get user from db
-        // use query_as macro instead (can't figure it out)
-        let query = r"SELECT * FROM users WHERE uuid = $1";
-        let row = sqlx::query(query)
-            .bind(&self.sub.to_string())
-            .fetch_one(conn.as_ref())
-            .await?;
-
-        let user = User::from_pg_row(row)?;
+        let Some(user) = users.get(self.sub).await? else {
+            // user has been deleted
+            return Ok(None)
+        };
 
         // compare ids
         if user.tokenid != self.tokenid {
@@ -45,13 +56,20 @@ impl Claims {
         Ok(Some(user))
     }
 
-    pub fn generate_jwt(jwt_secret: &String, user: &User) -> jsonwebtoken::errors::Result<String> {
+    /// Token of a user that just logged in
+    pub fn generate_jwt(auth: &AuthConfig, user: &User) -> jsonwebtoken::errors::Result<String> {
+        Self::refresh_jwt(auth, user, Utc::now().timestamp() as usize)
+    }
+
+    /// Token of a user that logged in at `auth_time`
+    pub fn refresh_jwt(auth: &AuthConfig, user: &User, auth_time: usize) -> jsonwebtoken::errors::Result<String> {
         let claims = Claims {
             sub: user.uuid,
             tokenid: user.tokenid,
             iat: Utc::now().timestamp() as usize,
-            exp: Utc::now().timestamp() as usize + 31536000, /* 1 year */
+            exp: Utc::now().timestamp() as usize + auth.jwt_lifetime as usize,
+            auth_time,
         };
-        encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret.as_ref()))
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(auth.jwt_secret.as_ref()))
     }
 }
\ No newline at end of file
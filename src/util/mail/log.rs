@@ -0,0 +1,15 @@
+use crate::util::mail::mailer::Mailer;
+use async_trait::async_trait;
+use std::error::Error;
+
+/// Prints mails to stdout instead of sending them, for development and instances without smtp
+#[derive(Default)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        println!("MAIL to: {}; subject: {}\n{}", to, subject, body);
+        Ok(())
+    }
+}
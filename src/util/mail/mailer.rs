@@ -0,0 +1,8 @@
+use async_trait::async_trait;
+use std::error::Error;
+
+/// Sends plain text mails to users
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
@@ -0,0 +1,127 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use sqlx::{Pool, Postgres};
+
+/// Buckets of the request latency in seconds, long ones are uploads and downloads
+const LATENCY_BUCKETS: [f64; 14] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Prometheus metrics of one server instance
+pub struct Metrics {
+    registry: Registry,
+    /// Labels: method, route, status
+    pub http_requests: IntCounterVec,
+    /// Labels: method, route, status
+    pub http_request_duration: HistogramVec,
+    pub bytes_uploaded: IntCounter,
+    pub bytes_downloaded: IntCounter,
+    pub active_uploads: IntGauge,
+    /// Labels: kind (`password`, `lockout`, `token`, `oidc`)
+    pub auth_failures: IntCounterVec,
+    pub db_pool_connections: IntGauge,
+    pub db_pool_idle: IntGauge,
+    pub db_pool_max: IntGauge,
+    /// Refreshed periodically by the metrics job
+    pub storage_used_bytes: IntGauge,
+    pub storage_files: IntGauge,
+    pub users: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("drive".to_string()), None).unwrap();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Handled requests"),
+            &["method", "route", "status"]
+        ).unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time until the response headers were sent")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["method", "route", "status"]
+        ).unwrap();
+        let bytes_uploaded = IntCounter::new("bytes_uploaded_total", "Bytes of uploaded files").unwrap();
+        let bytes_downloaded = IntCounter::new("bytes_downloaded_total", "Bytes of served files").unwrap();
+        let active_uploads = IntGauge::new("active_uploads", "Uploads currently streaming").unwrap();
+        let auth_failures = IntCounterVec::new(
+            Opts::new("auth_failures_total", "Rejected logins and tokens"),
+            &["kind"]
+        ).unwrap();
+        let db_pool_connections = IntGauge::new("db_pool_connections", "Open database connections").unwrap();
+        let db_pool_idle = IntGauge::new("db_pool_idle_connections", "Idle database connections").unwrap();
+        let db_pool_max = IntGauge::new("db_pool_max_connections", "Maximum database connections").unwrap();
+        let storage_used_bytes = IntGauge::new("storage_used_bytes", "Bytes of all stored files").unwrap();
+        let storage_files = IntGauge::new("storage_files", "Number of stored files").unwrap();
+        let users = IntGauge::new("users", "Number of accounts").unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(bytes_uploaded.clone())).unwrap();
+        registry.register(Box::new(bytes_downloaded.clone())).unwrap();
+        registry.register(Box::new(active_uploads.clone())).unwrap();
+        registry.register(Box::new(auth_failures.clone())).unwrap();
+        registry.register(Box::new(db_pool_connections.clone())).unwrap();
+        registry.register(Box::new(db_pool_idle.clone())).unwrap();
+        registry.register(Box::new(db_pool_max.clone())).unwrap();
+        registry.register(Box::new(storage_used_bytes.clone())).unwrap();
+        registry.register(Box::new(storage_files.clone())).unwrap();
+        registry.register(Box::new(users.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            bytes_uploaded,
+            bytes_downloaded,
+            active_uploads,
+            auth_failures,
+            db_pool_connections,
+            db_pool_idle,
+            db_pool_max,
+            storage_used_bytes,
+            storage_files,
+            users,
+        }
+    }
+
+    pub fn auth_failure(&self, kind: &str) {
+        self.auth_failures.with_label_values(&[kind]).inc();
+    }
+
+    /// Pool gauges are read on every scrape as they're cheap
+    pub fn observe_pool(&self, pool: &Pool<Postgres>) {
+        self.db_pool_connections.set(pool.size() as i64);
+        self.db_pool_idle.set(pool.num_idle() as i64);
+        self.db_pool_max.set(pool.options().get_max_connections() as i64);
+    }
+
+    /// Metrics in the prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        // encoding into a vec can't fail
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts an upload as active until dropped, so early returns are covered
+pub struct ActiveUpload<'a>(&'a IntGauge);
+
+impl<'a> ActiveUpload<'a> {
+    pub fn start(gauge: &'a IntGauge) -> Self {
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for ActiveUpload<'_> {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
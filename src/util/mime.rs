@@ -0,0 +1,18 @@
+/// Type of a file judged by its name, `application/octet-stream` if it's unknown
+pub fn guess(filename: &str) -> String {
+    mime_guess::from_path(filename).first_or_octet_stream().essence_str().to_string()
+}
+
+/// Type declared by the client without parameters, guessed from the filename if it's missing or generic
+pub fn detect(filename: &str, declared: Option<&str>) -> String {
+    declared
+        .and_then(|declared| declared.parse::<mime_guess::Mime>().ok())
+        .map(|declared| declared.essence_str().to_lowercase())
+        .filter(|declared| declared != "application/octet-stream")
+        .unwrap_or_else(|| guess(filename))
+}
+
+/// Whether text can be extracted for the search
+pub fn has_text(mime_type: &str) -> bool {
+    mime_type.starts_with("text/") || mime_type == "application/pdf"
+}
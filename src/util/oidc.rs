@@ -0,0 +1,223 @@
+use crate::util::token;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::str::FromStr;
+use tokio::sync::RwLock;
+
+/// Algorithm of keys that don't name one, the one every provider has to support
+const DEFAULT_ALGORITHM: Algorithm = Algorithm::RS256;
+
+/// Settings of the OpenID Connect provider, the client has to be registered there
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct OidcConfig {
+    /// Issuer url, `/.well-known/openid-configuration` is appended for discovery
+    pub issuer: String,
+    pub client_id: String,
+    /// Not needed for public clients as PKCE is always used
+    pub client_secret: Option<String>,
+    /// Url of the callback route, e.g. `https://drive.example.com/v1/user/oidc/callback`
+    pub redirect_url: String,
+    pub scopes: String,
+    /// Where users are sent after logging in
+    pub post_login_redirect: String,
+}
+
+impl Default for OidcConfig {
+    fn default() -> Self {
+        Self {
+            issuer: String::new(),
+            client_id: String::new(),
+            client_secret: None,
+            redirect_url: String::new(),
+            scopes: "openid email profile".to_string(),
+            post_login_redirect: "/".to_string(),
+        }
+    }
+}
+
+/// Subset of the discovery document that is used
+#[derive(Clone, Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Claims of a validated id token
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub preferred_username: Option<String>,
+}
+
+/// Values of one authorization request, kept by the client until the callback
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+impl AuthorizationRequest {
+    pub fn new() -> Self {
+        Self {
+            state: token::generate(),
+            nonce: token::generate(),
+            code_verifier: token::generate(),
+        }
+    }
+
+    /// S256 code challenge of the verifier
+    pub fn code_challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.code_verifier.as_bytes()))
+    }
+}
+
+impl Default for AuthorizationRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Discovered OpenID Connect provider doing the authorization code flow with PKCE
+pub struct OidcProvider {
+    config: OidcConfig,
+    metadata: ProviderMetadata,
+    /// Cached keys, refetched when a token is signed with an unknown key
+    jwks: RwLock<JwkSet>,
+    http: reqwest::Client,
+}
+
+impl OidcProvider {
+    /// Fetches the discovery document and the signing keys of the provider
+    pub async fn discover(config: OidcConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let http = reqwest::Client::new();
+
+        let url = format!("{}/.well-known/openid-configuration", config.issuer.trim_end_matches('/'));
+        let metadata: ProviderMetadata = http.get(url)
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+
+        if metadata.issuer.trim_end_matches('/') != config.issuer.trim_end_matches('/') {
+            return Err("Issuer of discovery document does not match configured issuer".into())
+        }
+
+        let jwks: JwkSet = http.get(&metadata.jwks_uri)
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+
+        Ok(Self {
+            config,
+            metadata,
+            jwks: RwLock::new(jwks),
+            http,
+        })
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.metadata.issuer
+    }
+
+    pub fn post_login_redirect(&self) -> &str {
+        &self.config.post_login_redirect
+    }
+
+    /// Url the user gets redirected to for logging in at the provider
+    pub fn authorization_url(&self, request: &AuthorizationRequest) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let url = reqwest::Url::parse_with_params(&self.metadata.authorization_endpoint, &[
+            ("response_type", "code"),
+            ("client_id", &self.config.client_id),
+            ("redirect_uri", &self.config.redirect_url),
+            ("scope", &self.config.scopes),
+            ("state", &request.state),
+            ("nonce", &request.nonce),
+            ("code_challenge", &request.code_challenge()),
+            ("code_challenge_method", "S256"),
+        ])?;
+
+        Ok(url.into())
+    }
+
+    /// Exchanges the authorization code and returns the validated claims of the id token
+    pub async fn exchange_code(&self, code: &str, request: &AuthorizationRequest)
+        -> Result<IdTokenClaims, Box<dyn Error + Send + Sync>> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", &request.code_verifier),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let response: TokenResponse = self.http.post(&self.metadata.token_endpoint)
+            .form(&form)
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+
+        self.validate_id_token(&response.id_token, &request.nonce).await
+    }
+
+    /// Validates signature, issuer, audience, expiry and nonce of an id token
+    async fn validate_id_token(&self, id_token: &str, nonce: &str)
+        -> Result<IdTokenClaims, Box<dyn Error + Send + Sync>> {
+        let header = decode_header(id_token)?;
+        let kid = header.kid.ok_or("Id token has no key id")?;
+
+        // refetch keys once if the provider rotated them
+        if self.jwks.read().await.find(&kid).is_none() {
+            let jwks: JwkSet = self.http.get(&self.metadata.jwks_uri)
+                .send().await?
+                .error_for_status()?
+                .json().await?;
+            *self.jwks.write().await = jwks;
+        }
+
+        let (key, algorithm) = {
+            let jwks = self.jwks.read().await;
+            let jwk = jwks.find(&kid).ok_or("Id token is signed with an unknown key")?;
+            // never trust the algorithm named by the token itself
+            let algorithm = match jwk.common.key_algorithm {
+                Some(algorithm) => Algorithm::from_str(&algorithm.to_string())?,
+                None => DEFAULT_ALGORITHM,
+            };
+            (DecodingKey::from_jwk(jwk)?, algorithm)
+        };
+        if header.alg != algorithm {
+            return Err("Id token is not signed with the algorithm of its key".into())
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[&self.metadata.issuer]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)?.claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err("Nonce of id token does not match".into())
+        }
+
+        Ok(claims)
+    }
+}
@@ -0,0 +1,92 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Whether the address is reachable from the internet \
+/// Loopback, private, link-local, shared and benchmarking addresses would let users reach into the internal network
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            // "this network" 0.0.0.0/8, shared 100.64.0.0/10 of carrier-grade nat and benchmarking 198.18.0.0/15
+            let this_network = first == 0;
+            let shared = first == 100 && second & 0xc0 == 64;
+            let benchmarking = first == 198 && second & 0xfe == 18;
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || this_network
+                || ip.is_broadcast() || ip.is_multicast() || shared || benchmarking)
+        },
+        IpAddr::V6(ip) => {
+            if ip.is_loopback() || ip.is_unspecified() {
+                return false
+            }
+            if let Some(embedded) = embedded_ipv4(ip) {
+                return is_public(IpAddr::V4(embedded))
+            }
+            let segment = ip.segments()[0];
+            // unique local fc00::/7 and link-local fe80::/10
+            !(ip.is_multicast() || segment & 0xfe00 == 0xfc00 || segment & 0xffc0 == 0xfe80)
+        },
+    }
+}
+
+/// IPv4 address an IPv6 address is translated to, classified like a direct connection to it \
+/// Covers mapped `::ffff:a.b.c.d`, compatible `::a.b.c.d`, NAT64 `64:ff9b::/96` and 6to4 `2002::/16`
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let octets = ip.octets();
+    match segments {
+        // mapped and compatible
+        [0, 0, 0, 0, 0, 0xffff | 0, ..] => Some(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15])),
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15])),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+/// Resolves the host of the url and checks that every address is public, unless the host is allowed
+pub async fn check_url(url: &Url, allowed_hosts: &[String]) -> Result<(), String> {
+    let host = url.host_str().ok_or("Url has no host")?;
+    if allowed_hosts.iter().any(|allowed| allowed == host) {
+        return Ok(())
+    }
+
+    let port = url.port_or_known_default().unwrap_or(0);
+    // brackets of ipv6 literals aren't resolvable
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port)).await
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} does not resolve to any address", host))
+    }
+    match addrs.iter().find(|addr| !is_public(addr.ip())) {
+        Some(addr) => Err(format!("{} resolves to the internal address {}", host, addr.ip())),
+        None => Ok(()),
+    }
+}
+
+/// Resolver of the client sending webhooks, drops internal addresses unless the host is allowed \
+/// Checked on every connection, so a host can't switch to an internal address after `check_url`
+pub struct PublicResolver {
+    allowed_hosts: Vec<String>,
+}
+
+impl PublicResolver {
+    pub fn new(allowed_hosts: Vec<String>) -> Self {
+        Self { allowed_hosts }
+    }
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = self.allowed_hosts.iter().any(|allowed| allowed == name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?
+                .filter(|addr| allowed || is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} does not resolve to a public address", name.as_str()).into())
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use crate::util::ratelimit::store::{Backoff, RateLimitStore};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+
+struct Window {
+    start: i64,
+    hits: u64,
+}
+
+struct Failures {
+    count: u32,
+    last_failure: i64,
+    locked_until: Option<i64>,
+}
+
+/// Keeps counters in memory, they are lost on restart and not shared between instances
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    windows: Mutex<HashMap<String, Window>>,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl MemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(&self, key: &str, window_start: i64) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let mut windows = self.windows.lock().map_err(|e| e.to_string())?;
+        let window = windows
+            .entry(key.to_string())
+            .or_insert(Window { start: window_start, hits: 0 });
+
+        if window.start != window_start {
+            window.start = window_start;
+            window.hits = 0;
+        }
+        window.hits += 1;
+
+        Ok(window.hits)
+    }
+
+    async fn locked_until(&self, key: &str, now: i64) -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
+        let failures = self.failures.lock().map_err(|e| e.to_string())?;
+        Ok(failures
+            .get(key)
+            .and_then(|f| f.locked_until)
+            .filter(|until| *until > now))
+    }
+
+    async fn record_failure(&self, key: &str, backoff: &Backoff, now: i64)
+        -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
+        let mut failures = self.failures.lock().map_err(|e| e.to_string())?;
+        let entry = failures
+            .entry(key.to_string())
+            .or_insert(Failures { count: 0, last_failure: now, locked_until: None });
+
+        if entry.last_failure + backoff.reset < now {
+            entry.count = 0;
+        }
+        entry.count += 1;
+        entry.last_failure = now;
+        entry.locked_until = backoff.lockout(entry.count).map(|secs| now + secs);
+
+        Ok(entry.locked_until)
+    }
+
+    async fn reset_failures(&self, key: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut failures = self.failures.lock().map_err(|e| e.to_string())?;
+        failures.remove(key);
+        Ok(())
+    }
+
+    async fn prune(&self, windows_before: i64, failures_before: i64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        {
+            let mut windows = self.windows.lock().map_err(|e| e.to_string())?;
+            windows.retain(|_, w| w.start >= windows_before);
+        }
+        let mut failures = self.failures.lock().map_err(|e| e.to_string())?;
+        failures.retain(|_, f| f.last_failure >= failures_before);
+        Ok(())
+    }
+}
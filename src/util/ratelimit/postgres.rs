@@ -0,0 +1,98 @@
+use crate::util::ratelimit::store::{Backoff, RateLimitStore};
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use std::error::Error;
+use std::sync::Arc;
+
+/// Keeps counters in the `rate_limit` and `login_failure` tables, shared between instances
+pub struct PgRateLimitStore {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl PgRateLimitStore {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PgRateLimitStore {
+    async fn hit(&self, key: &str, window_start: i64) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let query = r"INSERT INTO rate_limit (key, window_start, hits) VALUES ($1, $2, 1)
+                      ON CONFLICT (key) DO UPDATE SET
+                          hits = CASE WHEN rate_limit.window_start = $2 THEN rate_limit.hits + 1 ELSE 1 END,
+                          window_start = $2
+                      RETURNING hits";
+        let hits: i64 = sqlx::query_scalar(query)
+            .bind(key)
+            .bind(window_start)
+            .fetch_one(self.db_pool.as_ref())
+            .await?;
+
+        Ok(hits as u64)
+    }
+
+    async fn locked_until(&self, key: &str, now: i64) -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
+        let query = r"SELECT locked_until FROM login_failure WHERE key = $1 AND locked_until > $2";
+        let locked_until: Option<i64> = sqlx::query_scalar(query)
+            .bind(key)
+            .bind(now)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        Ok(locked_until)
+    }
+
+    async fn record_failure(&self, key: &str, backoff: &Backoff, now: i64)
+        -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let query = r"INSERT INTO login_failure (key, failures, last_failure) VALUES ($1, 1, $2)
+                      ON CONFLICT (key) DO UPDATE SET
+                          failures = CASE WHEN login_failure.last_failure + $3 < $2 THEN 1 ELSE login_failure.failures + 1 END,
+                          last_failure = $2
+                      RETURNING failures";
+        let failures: i32 = sqlx::query_scalar(query)
+            .bind(key)
+            .bind(now)
+            .bind(backoff.reset)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let locked_until = backoff.lockout(failures as u32).map(|secs| now + secs);
+
+        let query = r"UPDATE login_failure SET locked_until = $1 WHERE key = $2";
+        sqlx::query(query)
+            .bind(locked_until)
+            .bind(key)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(locked_until)
+    }
+
+    async fn reset_failures(&self, key: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let query = r"DELETE FROM login_failure WHERE key = $1";
+        sqlx::query(query)
+            .bind(key)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn prune(&self, windows_before: i64, failures_before: i64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        sqlx::query(r"DELETE FROM rate_limit WHERE window_start < $1")
+            .bind(windows_before)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        sqlx::query(r"DELETE FROM login_failure WHERE last_failure < $1")
+            .bind(failures_before)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::error::Error;
+
+/// Exponential backoff for failed attempts
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Backoff {
+    /// Failures allowed before the first lockout
+    pub max_failures: u32,
+    /// Length of the first lockout in seconds, doubles with every further failure
+    pub base: i64,
+    /// Upper bound for the lockout in seconds
+    pub max: i64,
+    /// Failures are forgotten after this many seconds without a new failure
+    pub reset: i64,
+}
+
+impl Backoff {
+    /// Returns the lockout in seconds after `failures` failed attempts
+    pub fn lockout(&self, failures: u32) -> Option<i64> {
+        if failures < self.max_failures {
+            return None
+        }
+        let exponent = (failures - self.max_failures).min(32);
+        Some(self.base.saturating_mul(1_i64 << exponent).min(self.max))
+    }
+}
+
+/// Counters backing the rate limiting and login lockouts \
+/// All timestamps are unix timestamps in seconds
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Counts a hit for key in the fixed window starting at `window_start`
+    /// and returns the amount of hits in that window
+    async fn hit(&self, key: &str, window_start: i64) -> Result<u64, Box<dyn Error + Send + Sync>>;
+
+    /// Returns the timestamp until which key is locked, if it's locked
+    async fn locked_until(&self, key: &str, now: i64) -> Result<Option<i64>, Box<dyn Error + Send + Sync>>;
+
+    /// Records a failed attempt for key and returns the timestamp until which key is locked
+    async fn record_failure(&self, key: &str, backoff: &Backoff, now: i64)
+        -> Result<Option<i64>, Box<dyn Error + Send + Sync>>;
+
+    /// Forgets all failed attempts of key
+    async fn reset_failures(&self, key: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Removes windows that started and failures that were last recorded before the given timestamps
+    async fn prune(&self, windows_before: i64, failures_before: i64) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
@@ -0,0 +1,27 @@
+use std::future::Future;
+use uuid::Uuid;
+
+/// Header the request id is read from and echoed back in
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request currently handled, `None` outside of the `request_id` middleware
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Runs the future with the given request id
+pub async fn scope<F: Future>(id: String, f: F) -> F::Output {
+    REQUEST_ID.scope(id, f).await
+}
+
+/// Takes the id passed by a proxy if it's sane, otherwise generates a new one
+pub fn from_header(value: Option<&str>) -> String {
+    match value {
+        Some(id) if !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_graphic()) => id.to_string(),
+        _ => Uuid::new_v4().to_string(),
+    }
+}
@@ -0,0 +1,65 @@
+use crate::util::scan::scanner::{Scanner, Verdict};
+use async_trait::async_trait;
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Bytes sent per INSTREAM chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams files to a ClamAV daemon with the INSTREAM command
+pub struct ClamdScanner {
+    address: String,
+    timeout: Duration,
+}
+
+impl ClamdScanner {
+    /// `address` in the form of `host:port`, the timeout covers the whole scan
+    pub fn new(address: &str, timeout: Duration) -> Self {
+        Self { address: address.to_string(), timeout }
+    }
+
+    async fn instream(&self, path: &Path) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut stream = TcpStream::connect(&self.address).await?;
+        stream.write_all(b"zINSTREAM\0").await?;
+
+        // every chunk is prefixed with its length, an empty one ends the stream
+        let mut chunk = vec![0; CHUNK_SIZE];
+        loop {
+            let read = file.read(&mut chunk).await?;
+            stream.write_all(&(read as u32).to_be_bytes()).await?;
+            if read == 0 {
+                break
+            }
+            stream.write_all(&chunk[..read]).await?;
+        }
+
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await?;
+        Ok(String::from_utf8_lossy(&reply).trim_end_matches(['\0', '\n']).to_string())
+    }
+}
+
+#[async_trait]
+impl Scanner for ClamdScanner {
+    async fn scan(&self, path: &Path) -> Result<Verdict, Box<dyn Error + Send + Sync>> {
+        let reply = tokio::time::timeout(self.timeout, self.instream(path)).await
+            .map_err(|_| "clamd didn't answer in time")??;
+        parse_reply(&reply)
+    }
+}
+
+/// Replies look like `stream: OK`, `stream: Eicar-Signature FOUND` or `INSTREAM size limit exceeded. ERROR`
+fn parse_reply(reply: &str) -> Result<Verdict, Box<dyn Error + Send + Sync>> {
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+    if result == "OK" {
+        return Ok(Verdict::Clean)
+    }
+    match result.strip_suffix(" FOUND") {
+        Some(signature) => Ok(Verdict::Infected(signature.to_string())),
+        None => Err(format!("clamd failed to scan: {}", reply).into()),
+    }
+}
@@ -0,0 +1,17 @@
+use async_trait::async_trait;
+use std::error::Error;
+use std::path::Path;
+
+/// Result of a scan that completed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Clean,
+    /// Name of the signature that matched
+    Infected(String),
+}
+
+/// Checks stored files for malware, errors mean the file couldn't be scanned
+#[async_trait]
+pub trait Scanner: Send + Sync {
+    async fn scan(&self, path: &Path) -> Result<Verdict, Box<dyn Error + Send + Sync>>;
+}
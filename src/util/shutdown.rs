@@ -0,0 +1,69 @@
+use tokio::sync::watch;
+
+/// Shutdown state shared between `main` and the handlers \
+/// On a signal the server starts draining, once the drain timeout is over long running requests are aborted
+pub struct Shutdown {
+    draining: watch::Sender<bool>,
+    aborted: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            draining: watch::Sender::new(false),
+            aborted: watch::Sender::new(false),
+        }
+    }
+
+    /// No new connections are accepted, in-flight requests may still finish
+    pub fn begin_drain(&self) {
+        self.draining.send_replace(true);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// Resolves once draining began
+    pub async fn drained(&self) {
+        let _ = self.draining.subscribe().wait_for(|draining| *draining).await;
+    }
+
+    /// Tells requests that are still running to give up and clean up after themselves
+    pub fn abort(&self) {
+        self.aborted.send_replace(true);
+    }
+
+    /// Resolves once the drain timeout is over, meant to be raced against long running work
+    pub async fn aborted(&self) {
+        let _ = self.aborted.subscribe().wait_for(|aborted| *aborted).await;
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resolves on SIGTERM or SIGINT
+pub async fn signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c().await.expect("failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {},
+        _ = terminate => {},
+    }
+}
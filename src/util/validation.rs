@@ -4,7 +4,7 @@
 /// Username validation with following requirements:
 /// - 4-16 chars of length
 /// - only a-z, A-Z, 0-9, ., -, _
-pub fn username(username: &String) -> (bool, String) {
+pub fn username(username: &str) -> (bool, String) {
     // check for length in bounds of 4-16
     if username.len() < 4 || username.len() > 16 {
         return (false, "Length of username not in bounds of 4-16".to_string())
@@ -31,7 +31,7 @@ pub fn username(username: &String) -> (bool, String) {
 /// - 8-30 chars of length
 /// - only a-z, A-Z, 0-9, ., _, -, *, #, %, &, $, ?,
 /// - at least 1 of each listed above
-pub fn password(password: &String) -> (bool, String) {
+pub fn password(password: &str) -> (bool, String) {
     // check for length
     if password.len() < 8 || password.len() > 30 {
         return (false, "Length of password is not in bounds of 8-30".to_string())
@@ -61,10 +61,94 @@ pub fn password(password: &String) -> (bool, String) {
     if !password.chars().any(|c| c.is_ascii_digit()) {
         return (false, "Does not include digit".to_string())
     }
-    if !password.chars().any(|c| { vec!['.', '_', '-', '*', '#', '%', '&', '$', '?'].contains(&c) }) {
+    if !password.chars().any(|c| { ['.', '_', '-', '*', '#', '%', '&', '$', '?'].contains(&c) }) {
         return (false, "Does not include special char".to_string())
     }
 
 
     (true, "".to_string())
-}
\ No newline at end of file
+}
+
+/// Email validation with following requirements:
+/// - at most 254 chars of length
+/// - exactly one @ with a non-empty local part before it
+/// - a domain with at least one . after it
+/// - no whitespace
+pub fn email(email: &str) -> (bool, String) {
+    if email.len() > 254 {
+        return (false, "Email is longer than 254 chars".to_string())
+    }
+
+    if email.chars().any(|c| c.is_whitespace()) {
+        return (false, "Email contains whitespace".to_string())
+    }
+
+    let Some((local, domain)) = email.split_once('@') else {
+        return (false, "Email does not contain @".to_string())
+    };
+
+    if local.is_empty() || domain.contains('@') {
+        return (false, "Email is not of the form local@domain".to_string())
+    }
+
+    if !domain.contains('.') || domain.starts_with('.') || domain.ends_with('.') {
+        return (false, "Email domain is not valid".to_string())
+    }
+
+    (true, "".to_string())
+}
+
+/// Tag validation with following requirements:
+/// - 1-64 chars of length
+/// - no control chars
+pub fn tag(tag: &str) -> (bool, String) {
+    if tag.is_empty() || tag.chars().count() > 64 {
+        return (false, "Length of tag not in bounds of 1-64".to_string())
+    }
+
+    if tag.chars().any(|c| c.is_control()) {
+        return (false, "Tag contains control chars".to_string())
+    }
+
+    (true, "".to_string())
+}
+
+/// Metadata key validation with following requirements:
+/// - 1-64 chars of length
+/// - only a-z, A-Z, 0-9, ., -, _
+pub fn metadata_key(key: &str) -> (bool, String) {
+    if key.is_empty() || key.len() > 64 {
+        return (false, "Length of key not in bounds of 1-64".to_string())
+    }
+
+    if !key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')) {
+        return (false, "Contains non a-z, A-Z, ., _, - char".to_string())
+    }
+
+    (true, "".to_string())
+}
+
+/// Metadata value validation with following requirements:
+/// - at most 1024 chars of length
+pub fn metadata_value(value: &str) -> (bool, String) {
+    if value.chars().count() > 1024 {
+        return (false, "Value is longer than 1024 chars".to_string())
+    }
+
+    (true, "".to_string())
+}
+
+/// Filename validation with following requirements:
+/// - 1-255 chars of length
+/// - no control chars
+pub fn filename(filename: &str) -> (bool, String) {
+    if filename.trim().is_empty() || filename.chars().count() > 255 {
+        return (false, "Length of filename not in bounds of 1-255".to_string())
+    }
+
+    if filename.chars().any(|c| c.is_control()) {
+        return (false, "Filename contains control chars".to_string())
+    }
+
+    (true, "".to_string())
+}
@@ -0,0 +1,1339 @@
+mod common;
+
+use async_trait::async_trait;
+use common::{allow_receivers, EphemeralDatabase, FakeClamd, FakeLdap, IdToken, MockIssuer, TestApp, WebhookReceiver, EICAR, PASSWORD};
+use drive_lib::config::{Config, EventBusBackend, RepositoryBackend, ScanConfig, ScanMode};
+use drive_lib::migrate;
+use drive_lib::models::appstate::Appstate;
+use drive_lib::models::job::{Job, JobStatus, Registry, Schedule};
+use drive_lib::util::auth::ldap::LdapConfig;
+use drive_lib::util::outbound;
+use hmac::{Hmac, Mac};
+use jsonwebtoken::Algorithm;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+
+#[tokio::test]
+async fn signups_are_never_admin() {
+    let app = TestApp::spawn().await;
+    let bob = app.client();
+    let carol = app.client();
+
+    bob.signup("bob_", PASSWORD).await.unwrap();
+    carol.signup("carol", PASSWORD).await.unwrap();
+    let alice = app.admin("alice").await;
+
+    let users = alice.admin_users("").await.unwrap();
+    let permissions: Vec<_> = users.iter().map(|u| (u.username.as_str(), u.permission.as_str())).collect();
+    assert_eq!(permissions, [("alice", "ADMIN"), ("bob_", "USER"), ("carol", "USER")]);
+
+    let failure = bob.admin_users("").await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::FORBIDDEN);
+}
+
+/// Wildcards in the search of the admin api match literally
+async fn admin_user_search(app: &TestApp) {
+    let alice = app.admin("alice").await;
+    app.client().signup("bob_", PASSWORD).await.unwrap();
+    app.client().signup("carol", PASSWORD).await.unwrap();
+
+    let search = |query: &'static str| {
+        let alice = &alice;
+        async move { alice.admin_users(query).await.unwrap().into_iter().map(|u| u.username).collect::<Vec<_>>() }
+    };
+    assert_eq!(search("_").await, ["bob_"]);
+    assert_eq!(search("b_b").await, Vec::<String>::new());
+    assert!(search("%").await.is_empty());
+    assert_eq!(search("CAR").await, ["carol"]);
+}
+
+on_every_backend!(admin_user_search_is_literal, admin_user_search);
+
+#[tokio::test]
+async fn signup_reports_every_invalid_field() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+
+    let body = serde_json::json!({ "username": "ab", "email": "nope", "password": "short" });
+    let response = client.http.post(client.url("/v1/user/new")).json(&body).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let failure: common::Failure = response.json().await.unwrap();
+    assert_eq!(failure.code, "validation_failed");
+    let fields: Vec<_> = failure.details.iter().map(|d| d.field.as_str()).collect();
+    assert_eq!(fields, ["username", "password", "email"]);
+}
+
+#[tokio::test]
+async fn signup_rejects_taken_username() {
+    let app = TestApp::spawn().await;
+
+    app.client().signup("alice", PASSWORD).await.unwrap();
+    let failure = app.client().signup("alice", PASSWORD).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn signup_is_forbidden_without_local_login() {
+    let app = TestApp::spawn_with(|config| config.auth.local_login = false).await;
+
+    let failure = app.client().signup("alice", PASSWORD).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn login_checks_the_password() {
+    let app = TestApp::spawn().await;
+    app.client().signup("alice", PASSWORD).await.unwrap();
+    let client = app.client();
+
+    let failure = client.login("alice", "Wrong-123").await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::UNAUTHORIZED);
+    assert!(client.upload(&[("a.txt", b"a")]).await.is_err());
+
+    client.login("alice", PASSWORD).await.unwrap();
+    client.upload(&[("a.txt", b"a")]).await.unwrap();
+}
+
+#[tokio::test]
+async fn repeated_failed_logins_lock_the_account() {
+    let app = TestApp::spawn().await;
+    app.client().signup("alice", PASSWORD).await.unwrap();
+    let client = app.client();
+
+    for _ in 0..5 {
+        let failure = client.login("alice", "Wrong-123").await.unwrap_err();
+        assert_eq!(failure.status, StatusCode::UNAUTHORIZED);
+    }
+
+    // even the right password is rejected during the lockout
+    let failure = client.login("alice", PASSWORD).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn login_backoff_is_read_from_env() {
+    // only read here, the other tests configure the app directly
+    std::env::set_var("LOGIN_ACCOUNT_MAX_FAILURES", "2");
+    std::env::set_var("LOGIN_IP_RESET", "600");
+    let mut config = Config::default();
+    config.apply_env().unwrap();
+    assert_eq!((config.rate_limit.login_account.max_failures, config.rate_limit.login_account.base), (2, 30));
+    assert_eq!((config.rate_limit.login_ip.max_failures, config.rate_limit.login_ip.reset), (20, 600));
+
+    std::env::set_var("LOGIN_IP_BASE", "soon");
+    assert!(config.apply_env().is_err());
+}
+
+#[tokio::test]
+async fn file_routes_are_rate_limited_per_user() {
+    let app = TestApp::spawn_with(|config| {
+        config.rate_limit.request_limit = 3;
+        config.rate_limit.request_window = 3600;
+    }).await;
+    let alice = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    let bob = app.client();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    // the upload and the downloads share the limit
+    let uploaded = alice.upload(&[("a.txt", b"a")]).await.unwrap();
+    alice.download(uploaded[0].reference_uuid).await.unwrap();
+    alice.download(uploaded[0].reference_uuid).await.unwrap();
+
+    let url = alice.url(&format!("/v1/file/download/{}", uploaded[0].reference_uuid));
+    let response = alice.http.get(url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: i64 = response.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=3600).contains(&retry_after), "{}", retry_after);
+    assert_eq!(alice.upload(&[("b.txt", b"b")]).await.unwrap_err().status, StatusCode::TOO_MANY_REQUESTS);
+
+    // counted per user
+    bob.upload(&[("b.txt", b"b")]).await.unwrap();
+}
+
+#[tokio::test]
+async fn disabled_accounts_look_like_wrong_credentials() {
+    let app = TestApp::spawn().await;
+    let admin = app.admin("alice").await;
+    app.client().signup("bob_", PASSWORD).await.unwrap();
+    let uuid = admin.admin_find("bob_").await.unwrap().uuid;
+    admin.admin_set_disabled(uuid, true).await.unwrap();
+    let client = app.client();
+
+    let wrong = client.login("bob_", "Wrong-123").await.unwrap_err();
+    let disabled = client.login("bob_", PASSWORD).await.unwrap_err();
+    assert_eq!((disabled.status, disabled.message), (wrong.status, wrong.message));
+
+    // the right password doesn't reset the failures while disabled
+    for _ in 0..3 {
+        client.login("bob_", PASSWORD).await.unwrap_err();
+    }
+    admin.admin_set_disabled(uuid, false).await.unwrap();
+    let failure = client.login("bob_", PASSWORD).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn ldap_users_are_provisioned_on_login() {
+    let ldap = FakeLdap::spawn(&[
+        ("carol", "carol-pw", &[FakeLdap::ADMIN_GROUP]),
+        ("dave", "dave-pw", &[]),
+    ]).await;
+    let app = TestApp::spawn_with(|config| config.ldap = Some(ldap.config())).await;
+    let carol = app.client();
+    let dave = app.client();
+
+    assert_eq!(dave.login("dave", "wrong").await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+    assert_eq!(dave.login("nobody", "dave-pw").await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+    dave.login("dave", "dave-pw").await.unwrap();
+    let profile = dave.me().await.unwrap();
+    assert_eq!((profile.username.as_str(), profile.email.as_str(), profile.permission.as_str()),
+               ("dave", "dave@example.com", "USER"));
+
+    // the group decides the permission, the second login keeps the linked account
+    carol.login("carol", "carol-pw").await.unwrap();
+    carol.login("carol", "carol-pw").await.unwrap();
+    let users = carol.admin_users("").await.unwrap();
+    let permissions: Vec<_> = users.iter().map(|u| (u.username.as_str(), u.permission.as_str())).collect();
+    assert_eq!(permissions, [("carol", "ADMIN"), ("dave", "USER")]);
+}
+
+#[tokio::test]
+async fn unreachable_ldap_answers_like_wrong_credentials() {
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let app = TestApp::spawn_with(|config| {
+        config.ldap = Some(LdapConfig {
+            url: format!("ldap://{}", closed),
+            base_dn: FakeLdap::BASE_DN.to_string(),
+            ..Default::default()
+        })
+    }).await;
+    let client = app.client();
+    app.client().signup("alice", PASSWORD).await.unwrap();
+
+    let failure = client.login("dave", "dave-pw").await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::UNAUTHORIZED);
+    client.login("alice", PASSWORD).await.unwrap();
+}
+
+#[tokio::test]
+async fn hanging_ldap_times_out() {
+    // accepts connections but never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    });
+    let app = TestApp::spawn_with(|config| {
+        config.ldap = Some(LdapConfig {
+            url: format!("ldap://{}", address),
+            base_dn: FakeLdap::BASE_DN.to_string(),
+            timeout: 1,
+            ..Default::default()
+        })
+    }).await;
+
+    let started = std::time::Instant::now();
+    let failure = app.client().login("dave", "dave-pw").await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::UNAUTHORIZED);
+    assert!(started.elapsed() < std::time::Duration::from_secs(5), "{:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn readiness_fails_while_draining() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+
+    let (status, readiness) = client.readiness().await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(readiness.ready && readiness.database && readiness.storage && !readiness.draining);
+
+    app.begin_drain();
+    let (status, readiness) = client.readiness().await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(!readiness.ready && readiness.draining && readiness.database);
+}
+
+#[tokio::test]
+async fn readiness_checks_the_database_and_storage() {
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let app = TestApp::spawn_with(|config| {
+        config.database.url = format!("postgres://{}/unreachable", closed);
+        config.database.repository = RepositoryBackend::Postgres;
+    }).await;
+
+    let (status, readiness) = app.client().readiness().await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(!readiness.ready && !readiness.database && readiness.storage && !readiness.draining);
+
+    let app = TestApp::spawn().await;
+    std::fs::remove_dir(app.files.path()).unwrap();
+    let (status, readiness) = app.client().readiness().await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(!readiness.ready && readiness.database && !readiness.storage);
+}
+
+#[tokio::test]
+async fn requests_without_session_are_unauthorized() {
+    let app = TestApp::spawn().await;
+
+    let failure = app.client().upload(&[("a.txt", b"a")]).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn upload_download_delete_roundtrip() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    client.signup("alice", PASSWORD).await.unwrap();
+
+    let uploaded = client.upload(&[("notes.txt", b"hello"), ("data.bin", &[0, 1, 2, 3])]).await.unwrap();
+    assert_eq!(uploaded.len(), 2);
+    assert_eq!(uploaded[0].filename, "notes.txt");
+    assert_eq!(app.stored_files(), 2);
+
+    let download = client.download(uploaded[0].reference_uuid).await.unwrap();
+    assert_eq!(download.bytes, b"hello");
+    assert!(download.content_disposition().contains("notes.txt"));
+    let download = client.download(uploaded[1].reference_uuid).await.unwrap();
+    assert_eq!(download.bytes, [0, 1, 2, 3]);
+
+    client.delete(uploaded[0].reference_uuid).await.unwrap();
+    assert_eq!(app.stored_files(), 1);
+    let failure = client.download(uploaded[0].reference_uuid).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::NOT_FOUND);
+    let failure = client.delete(uploaded[0].reference_uuid).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn uploads_validate_the_filename() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    client.signup("alice", PASSWORD).await.unwrap();
+
+    let too_long = "a".repeat(256);
+    for filename in ["  ", too_long.as_str()] {
+        let failure = client.upload(&[(filename, b"hello")]).await.unwrap_err();
+        assert_eq!((failure.status, failure.code.as_str()), (StatusCode::BAD_REQUEST, "validation_failed"));
+        assert_eq!(failure.details[0].field, "file");
+    }
+    assert_eq!(app.stored_files(), 0);
+    assert!(client.list(None, None).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn downloads_are_conditional_and_cacheable() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    client.signup("alice", PASSWORD).await.unwrap();
+
+    let id = client.upload(&[("notes.txt", b"hello world")]).await.unwrap()[0].reference_uuid;
+    let download = client.download(id).await.unwrap();
+    let header = |download: &common::Download, name: &str| download.headers[name].to_str().unwrap().to_string();
+    let etag = header(&download, "etag");
+    let last_modified = header(&download, "last-modified");
+    // sha256 of "hello world"
+    assert_eq!(etag, "\"b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9\"");
+    assert_eq!(header(&download, "cache-control"), "private, no-cache");
+    assert_eq!(client.list(None, None).await.unwrap()[0].sha256.as_deref(), Some(&etag[1..65]));
+
+    let cached = client.download_with(id, &[("if-none-match", &format!("W/{}, \"other\"", etag))]).await;
+    assert_eq!(cached.status, StatusCode::NOT_MODIFIED);
+    assert!(cached.bytes.is_empty());
+    assert_eq!(header(&cached, "etag"), etag);
+    let cached = client.download_with(id, &[("if-modified-since", &last_modified)]).await;
+    assert_eq!(cached.status, StatusCode::NOT_MODIFIED);
+    let changed = client.download_with(id, &[("if-none-match", "\"other\""), ("if-modified-since", &last_modified)]).await;
+    assert_eq!((changed.status, changed.bytes.as_slice()), (StatusCode::OK, b"hello world".as_slice()));
+
+    let range = client.download_with(id, &[("range", "bytes=6-"), ("if-range", &etag)]).await;
+    assert_eq!((range.status, range.bytes.as_slice()), (StatusCode::PARTIAL_CONTENT, b"world".as_slice()));
+    let range = client.download_with(id, &[("range", "bytes=6-"), ("if-range", "\"other\"")]).await;
+    assert_eq!((range.status, range.bytes.len()), (StatusCode::OK, 11));
+
+    assert_eq!(client.download_with(id, &[("if-match", "\"other\"")]).await.status, StatusCode::PRECONDITION_FAILED);
+    let failure = client.delete_if_match(id, "\"other\"").await.unwrap_err();
+    assert_eq!((failure.status, failure.code.as_str()), (StatusCode::PRECONDITION_FAILED, "precondition_failed"));
+    assert_eq!(app.stored_files(), 1);
+    client.delete_if_match(id, &etag).await.unwrap();
+    assert_eq!(app.stored_files(), 0);
+
+    let response = client.http.get(client.url("/healthz")).send().await.unwrap();
+    assert_eq!(response.headers()["cache-control"], "no-store");
+}
+
+/// Files without a row are removed once nothing wrote to them for an hour, newer ones may still be uploading
+#[tokio::test]
+async fn partial_files_are_removed() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    client.signup("alice", PASSWORD).await.unwrap();
+    client.upload(&[("kept.txt", b"kept")]).await.unwrap();
+
+    let dir = app.files.path().join("crashed");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("stale"), b"partial").unwrap();
+    let stale = std::fs::File::options().write(true).open(dir.join("stale")).unwrap();
+    stale.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 3600)).unwrap();
+    std::fs::write(dir.join("uploading"), b"partial").unwrap();
+
+    assert_eq!(app.remove_partial_files().await, 1);
+    assert_eq!(app.stored_files(), 2);
+    assert!(!dir.join("stale").exists());
+}
+
+#[tokio::test]
+async fn files_of_other_users_are_not_found() {
+    let app = TestApp::spawn().await;
+    let alice = app.client();
+    let bob = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    let uploaded = alice.upload(&[("secret.txt", b"secret")]).await.unwrap();
+    let reference_uuid = uploaded[0].reference_uuid;
+
+    assert_eq!(bob.download(reference_uuid).await.unwrap_err().status, StatusCode::NOT_FOUND);
+    assert_eq!(bob.delete(reference_uuid).await.unwrap_err().status, StatusCode::NOT_FOUND);
+    assert_eq!(alice.download(reference_uuid).await.unwrap().bytes, b"secret");
+}
+
+/// Filenames of the search results
+async fn search(client: &common::TestClient, query: &str) -> Vec<String> {
+    client.search(query).await.unwrap().into_iter().map(|r| r.filename).collect()
+}
+
+#[tokio::test]
+async fn search_matches_names_types_and_text() {
+    let app = TestApp::spawn().await;
+    let alice = app.client();
+    let bob = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    alice.upload(&[
+        ("Quarterly Report.md", b"# Revenue grew"),
+        ("photo.png", &[137, 80, 78, 71]),
+        ("notes.txt", b"meeting about revenue"),
+    ]).await.unwrap();
+
+    assert_eq!(search(&alice, "report").await, ["Quarterly Report.md"]);
+    assert_eq!(search(&alice, "image").await, ["photo.png"]);
+    assert_eq!(search(&alice, "png").await, ["photo.png"]);
+    let mut found = search(&alice, "revenue").await;
+    found.sort();
+    assert_eq!(found, ["Quarterly Report.md", "notes.txt"]);
+
+    let results = alice.search("notes").await.unwrap();
+    assert_eq!((results[0].mime_type.as_str(), results[0].size), ("text/plain", 21));
+
+    assert!(search(&bob, "revenue").await.is_empty());
+    assert_eq!(alice.search("  ").await.unwrap_err().status, StatusCode::BAD_REQUEST);
+}
+
+async fn organize_files(app: &TestApp) {
+    let alice = app.client();
+    let bob = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    let uploaded = alice.upload(&[("a.txt", b"a"), ("b.txt", b"b"), ("c.txt", b"c")]).await.unwrap();
+    let [a, b, c] = [0, 1, 2].map(|i| uploaded[i].reference_uuid);
+
+    assert_eq!(alice.set_tags(a, &[" Work ", "taxes", "work"]).await.unwrap(), ["taxes", "work"]);
+    let failure = alice.set_tags(a, &[""]).await.unwrap_err();
+    assert_eq!(failure.code, "validation_failed");
+    assert_eq!(bob.set_tags(a, &["mine"]).await.unwrap_err().status, StatusCode::NOT_FOUND);
+
+    let tagged = alice.bulk_tag(&[a, b, bob_file(&bob).await], &["2024"], &["taxes"]).await.unwrap();
+    assert_eq!(tagged.updated.len(), 2);
+    assert_eq!(tagged.skipped.len(), 1);
+
+    alice.remove_tag(b, "2024").await.unwrap();
+    alice.set_starred(c, true).await.unwrap();
+    alice.set_metadata(a, "project", "apollo").await.unwrap();
+    alice.set_metadata(a, "status", "draft").await.unwrap();
+    alice.remove_metadata(a, "status").await.unwrap();
+    assert_eq!(alice.set_metadata(a, "bad key", "x").await.unwrap_err().status, StatusCode::BAD_REQUEST);
+
+    let files = alice.list(Some("WORK"), None).await.unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].tags, ["2024", "work"]);
+    assert_eq!(files[0].metadata.iter().collect::<Vec<_>>(), [(&"project".to_string(), &"apollo".to_string())]);
+
+    let starred = alice.list(None, Some(true)).await.unwrap();
+    assert_eq!((starred.len(), starred[0].reference_uuid, starred[0].starred), (1, c, true));
+    assert_eq!(alice.list(None, None).await.unwrap().len(), 3);
+    assert!(alice.list(Some("2024"), None).await.unwrap().iter().all(|f| f.reference_uuid == a));
+
+    assert_eq!(search(&alice, "work").await, ["a.txt"]);
+    assert!(bob.list(Some("work"), None).await.unwrap().is_empty());
+}
+
+/// Uploads a file as bob
+async fn bob_file(bob: &common::TestClient) -> uuid::Uuid {
+    bob.upload(&[("bob.txt", b"bob")]).await.unwrap()[0].reference_uuid
+}
+
+on_every_backend!(tags_stars_and_metadata, organize_files);
+
+async fn batch_operations(app: &TestApp) {
+    let alice = app.client();
+    let bob = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    let uploaded = alice.upload(&[("docs/a.txt", b"a"), ("b.txt", b"b"), ("c.txt", b"c")]).await.unwrap();
+    let [a, b, c] = [0, 1, 2].map(|i| uploaded[i].reference_uuid);
+    let foreign = bob_file(&bob).await;
+
+    let done = alice.batch(&[
+        json!({ "op": "move", "reference_uuid": a, "directory": "archive/2024/" }),
+        json!({ "op": "rename", "reference_uuid": b, "filename": "renamed.txt" }),
+        json!({ "op": "copy", "reference_uuid": c }),
+        json!({ "op": "tag", "reference_uuid": c, "add": ["Work"] }),
+        json!({ "op": "delete", "reference_uuid": b }),
+        json!({ "op": "rename", "reference_uuid": c, "filename": "" }),
+        json!({ "op": "delete", "reference_uuid": foreign }),
+    ]).await.unwrap();
+    assert!(done.finished && done.batch_id.is_none());
+    let statuses: Vec<&str> = done.results.iter().map(|r| r.status.as_str()).collect();
+    assert_eq!(statuses, ["ok", "ok", "ok", "ok", "ok", "invalid", "not_found"]);
+    assert!(done.results[5].message.is_some());
+
+    let copy = done.results[2].copy_uuid.unwrap();
+    assert_eq!(alice.download(copy).await.unwrap().bytes, b"c");
+    let mut names: Vec<String> = alice.list(None, None).await.unwrap().into_iter().map(|f| f.filename).collect();
+    names.sort();
+    assert_eq!(names, ["archive/2024/a.txt", "c.txt", "c.txt"]);
+    assert_eq!(alice.list(Some("work"), None).await.unwrap()[0].reference_uuid, c);
+    assert_eq!(bob.list(None, None).await.unwrap().len(), 1);
+
+    app.spawn_worker();
+    let operations: Vec<_> = (0..150).map(|i| json!({ "op": "tag", "reference_uuid": a, "add": [format!("t{}", i % 10)] })).collect();
+    let started = alice.start_batch(&operations).await.unwrap();
+    let batch_id = started.batch_id.unwrap();
+    assert_eq!(bob.batch_status(batch_id).await.unwrap_err().status, StatusCode::NOT_FOUND);
+    let status = loop {
+        let status = alice.batch_status(batch_id).await.unwrap();
+        if status.finished {
+            break status
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    };
+    assert_eq!((status.total, status.completed), (150, 150));
+    assert!(status.results.iter().enumerate().all(|(i, r)| r.index == i && r.status == "ok"));
+
+    assert_eq!(alice.batch(&[]).await.unwrap_err().status, StatusCode::BAD_REQUEST);
+}
+
+on_every_backend!(batch_operations_on_files, batch_operations, |config| config.jobs.poll_interval = 1);
+
+async fn change_feed(app: &TestApp) {
+    let alice = app.client();
+    let bob = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    let empty = alice.changes(None, None).await.unwrap();
+    assert!(empty.changes.is_empty() && !empty.has_more);
+    let start = empty.cursor;
+
+    let uploaded = alice.upload(&[("a.txt", b"a"), ("b.txt", b"b")]).await.unwrap();
+    let [a, b] = [0, 1].map(|i| uploaded[i].reference_uuid);
+    bob_file(&bob).await;
+    alice.set_starred(a, true).await.unwrap();
+    alice.batch(&[
+        json!({ "op": "rename", "reference_uuid": a, "filename": "c.txt" }),
+        json!({ "op": "move", "reference_uuid": b, "directory": "docs" }),
+    ]).await.unwrap();
+
+    let page = alice.changes(Some(start), Some(3)).await.unwrap();
+    assert!(page.has_more);
+    let rest = alice.changes(Some(page.cursor), None).await.unwrap();
+    assert!(!rest.has_more);
+    let changes: Vec<_> = page.changes.iter().chain(&rest.changes)
+        .map(|c| (c.reference_uuid, c.kind.as_str(), c.filename.as_deref()))
+        .collect();
+    assert_eq!(changes, [
+        (a, "created", Some("a.txt")),
+        (b, "created", Some("b.txt")),
+        (a, "updated", None),
+        (a, "renamed", Some("c.txt")),
+        (b, "moved", Some("docs/b.txt")),
+    ]);
+    assert!(page.changes.iter().chain(&rest.changes).map(|c| c.id).is_sorted());
+
+    alice.delete(a).await.unwrap();
+    let latest = alice.changes(Some(rest.cursor), None).await.unwrap();
+    assert_eq!((latest.changes.len(), latest.changes[0].kind.as_str()), (1, "deleted"));
+    let idle = alice.changes(Some(latest.cursor), None).await.unwrap();
+    assert!(idle.changes.is_empty());
+    assert_eq!(idle.cursor, latest.cursor);
+
+    assert_eq!(bob.changes(None, None).await.unwrap().changes.len(), 1);
+}
+
+#[tokio::test]
+async fn changes_are_pushed_as_events() {
+    let app = TestApp::spawn().await;
+    let alice = app.client();
+    let bob = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    let mut events = alice.events(None).await;
+    bob_file(&bob).await;
+    let a = alice.upload(&[("a.txt", b"a")]).await.unwrap()[0].reference_uuid;
+    let created = events.next().await;
+    assert_eq!((created.reference_uuid, created.kind.as_str()), (a, "created"));
+    alice.set_starred(a, true).await.unwrap();
+    assert_eq!(events.next().await.kind, "updated");
+
+    // a reconnect replays what was missed before switching to live events
+    alice.delete(a).await.unwrap();
+    let mut resumed = alice.events(Some(created.id)).await;
+    assert_eq!(resumed.next().await.kind, "updated");
+    assert_eq!(resumed.next().await.kind, "deleted");
+    let b = alice.upload(&[("b.txt", b"b")]).await.unwrap()[0].reference_uuid;
+    assert_eq!(resumed.next().await.reference_uuid, b);
+
+    let response = alice.http.get(alice.url("/v1/changes/events")).send().await.unwrap();
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let anonymous = reqwest::get(alice.url("/v1/changes/events")).await.unwrap();
+    assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Changes published while the postgres bus reconnects are replayed from the journal
+#[tokio::test]
+async fn events_survive_a_bus_reconnect_with_postgres() {
+    let Some(app) = TestApp::spawn_postgres(|config| config.events.bus = EventBusBackend::Postgres).await else { return };
+    let alice = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+
+    let mut events = alice.events(None).await;
+    let a = alice.upload(&[("a.txt", b"a")]).await.unwrap()[0].reference_uuid;
+    assert_eq!(events.next().await.reference_uuid, a);
+
+    app.break_event_bus().await;
+    let b = alice.upload(&[("b.txt", b"b")]).await.unwrap()[0].reference_uuid;
+    // the bus waits five seconds before listening again
+    let replayed = events.next_within(std::time::Duration::from_secs(15)).await;
+    assert_eq!((replayed.reference_uuid, replayed.kind.as_str()), (b, "created"));
+    let c = alice.upload(&[("c.txt", b"c")]).await.unwrap()[0].reference_uuid;
+    assert_eq!(events.next().await.reference_uuid, c);
+}
+
+on_every_backend!(change_feed_for_sync_clients, change_feed);
+
+/// Delivers subscribed events signed with the secret and logs failed attempts for retries
+async fn webhooks(app: &TestApp) {
+    let alice = app.client();
+    let bob = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+    let mut receiver = WebhookReceiver::spawn().await;
+
+    let invalid = alice.create_webhook("ftp://example.com", &["share.created"]).await.unwrap_err();
+    assert_eq!(invalid.status, StatusCode::BAD_REQUEST);
+    assert_eq!(invalid.details.len(), 2);
+
+    let webhook = alice.create_webhook(&receiver.url, &["file.uploaded", "file.deleted"]).await.unwrap();
+    let secret = webhook.secret.expect("secret is returned on creation");
+    let listed = alice.webhooks().await.unwrap();
+    assert_eq!((listed.len(), listed[0].uuid, listed[0].secret.as_deref()), (1, webhook.uuid, None));
+    assert_eq!(listed[0].events, ["file.deleted", "file.uploaded"]);
+
+    let a = alice.upload(&[("a.txt", b"a")]).await.unwrap()[0].reference_uuid;
+    let uploaded = receiver.next().await;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", uploaded.header("x-drive-timestamp"), uploaded.body).as_bytes());
+    assert_eq!(uploaded.header("x-drive-signature"), format!("sha256={}", hex::encode(mac.finalize().into_bytes())));
+    assert_eq!(uploaded.header("x-drive-event"), "file.uploaded");
+    let payload = uploaded.json();
+    assert_eq!((payload["event"].as_str(), payload["data"]["reference_uuid"].as_str()), (Some("file.uploaded"), Some(a.to_string().as_str())));
+
+    // neither unsubscribed events nor files of other users are delivered
+    alice.set_starred(a, true).await.unwrap();
+    bob_file(&bob).await;
+    alice.delete(a).await.unwrap();
+    assert_eq!(receiver.next().await.header("x-drive-event"), "file.deleted");
+
+    let id = uploaded.header("x-drive-delivery").parse().unwrap();
+    let delivered = alice.attempted_delivery(webhook.uuid, id, 1).await;
+    assert_eq!((delivered.status.as_str(), delivered.response_status), ("delivered", Some(200)));
+
+    // a failed attempt stays queued for the retry worker
+    receiver.answer_with(500);
+    let ping = alice.ping_webhook(webhook.uuid).await.unwrap();
+    assert_eq!(receiver.next().await.header("x-drive-event"), "ping");
+    let failed = alice.attempted_delivery(webhook.uuid, ping.id, 1).await;
+    assert_eq!((failed.status.as_str(), failed.attempts, failed.response_status), ("pending", 1, Some(500)));
+    assert!(failed.error.is_some());
+    let log = alice.deliveries(webhook.uuid).await.unwrap();
+    assert_eq!(log.iter().map(|d| d.event.as_str()).collect::<Vec<_>>(), ["ping", "file.deleted", "file.uploaded"]);
+
+    assert_eq!(bob.deliveries(webhook.uuid).await.unwrap_err().status, StatusCode::NOT_FOUND);
+    alice.delete_webhook(webhook.uuid).await.unwrap();
+    assert!(alice.webhooks().await.unwrap().is_empty());
+    assert_eq!(alice.deliveries(webhook.uuid).await.unwrap_err().status, StatusCode::NOT_FOUND);
+}
+
+on_every_backend!(webhooks_deliver_signed_events, webhooks, allow_receivers);
+
+#[tokio::test]
+async fn webhooks_cannot_reach_internal_addresses() {
+    let app = TestApp::spawn().await;
+    let alice = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    let receiver = WebhookReceiver::spawn().await;
+
+    for url in [&receiver.url, "http://127.0.0.1/", "http://[::1]/", "http://10.0.0.1/", "http://169.254.169.254/", "http://0.0.0.0/"] {
+        let refused = alice.create_webhook(url, &["file.uploaded"]).await.unwrap_err();
+        assert_eq!((refused.status, refused.details.len()), (StatusCode::BAD_REQUEST, 1), "{}", url);
+    }
+}
+
+#[test]
+fn internal_addresses_are_not_public() {
+    let internal = [
+        "100.64.0.1", "100.127.255.254", "198.18.0.1", "198.19.255.254", "10.1.2.3", "127.0.0.1",
+        "0.1.2.3", "::ffff:127.0.0.1", "::ffff:100.64.0.1", "::127.0.0.1", "::10.0.0.1", "::0.0.0.2",
+        "64:ff9b::7f00:1", "64:ff9b::a9fe:a9fe", "2002:7f00:1::", "2002:a00:1::1", "::1", "::", "fd00::1", "fe80::1",
+    ];
+    for ip in internal {
+        assert!(!outbound::is_public(ip.parse().unwrap()), "{} is public", ip);
+    }
+
+    let public = ["100.63.255.255", "100.128.0.1", "198.17.0.1", "198.20.0.1", "93.184.216.34", "64:ff9b::5db8:d822",
+                  "2002:5db8:d822::1", "::ffff:93.184.216.34", "2606:2800:220:1::"];
+    for ip in public {
+        assert!(outbound::is_public(ip.parse().unwrap()), "{} is internal", ip);
+    }
+}
+
+#[tokio::test]
+async fn oversized_upload_is_rejected_and_removed() {
+    let app = TestApp::spawn_with(|config| config.storage.max_file_size = 16).await;
+    let client = app.client();
+    client.signup("alice", PASSWORD).await.unwrap();
+
+    let failure = client.upload(&[("big.bin", &[0; 17])]).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::PAYLOAD_TOO_LARGE);
+    // removed in the background once the upload is dropped
+    for _ in 0..50 {
+        if app.stored_files() == 0 {
+            break
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(app.stored_files(), 0);
+
+    client.upload(&[("small.bin", &[0; 16])]).await.unwrap();
+}
+
+#[tokio::test]
+async fn quota_is_enforced() {
+    let app = TestApp::spawn().await;
+    let admin = app.admin("alice").await;
+    let bob = app.client();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    let uuid = admin.admin_find("bob_").await.unwrap().uuid;
+    admin.admin_set_quota(uuid, Some(10)).await.unwrap();
+
+    bob.upload(&[("a.bin", &[0; 6])]).await.unwrap();
+    let failure = bob.upload(&[("b.bin", &[0; 5])]).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::PAYLOAD_TOO_LARGE);
+    bob.upload(&[("c.bin", &[0; 4])]).await.unwrap();
+
+    let bob_usage = admin.admin_find("bob_").await.unwrap();
+    assert_eq!((bob_usage.usage, bob_usage.files, bob_usage.quota), (10, 2, Some(10)));
+}
+
+#[tokio::test]
+async fn forced_password_reset_only_allows_the_change() {
+    let app = TestApp::spawn().await;
+    let admin = app.admin("alice").await;
+    let bob = app.client();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    let uuid = admin.admin_find("bob_").await.unwrap().uuid;
+    admin.admin_force_password_reset(uuid).await.unwrap();
+
+    assert_eq!(bob.upload(&[("a.txt", b"a")]).await.unwrap_err().status, StatusCode::FORBIDDEN);
+    assert_eq!(bob.me().await.unwrap_err().status, StatusCode::FORBIDDEN);
+    bob.change_password(PASSWORD, "Changed-456").await.unwrap();
+    bob.upload(&[("a.txt", b"a")]).await.unwrap();
+}
+
+#[tokio::test]
+async fn password_change_ends_other_sessions() {
+    let app = TestApp::spawn().await;
+    let first = app.client();
+    let second = app.client();
+    first.signup("alice", PASSWORD).await.unwrap();
+    second.login("alice", PASSWORD).await.unwrap();
+
+    first.change_password(PASSWORD, "Changed-456").await.unwrap();
+
+    first.upload(&[("a.txt", b"a")]).await.unwrap();
+    let failure = second.upload(&[("a.txt", b"a")]).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::UNAUTHORIZED);
+
+    assert_eq!(second.login("alice", PASSWORD).await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+    second.login("alice", "Changed-456").await.unwrap();
+}
+
+#[tokio::test]
+async fn username_change_rejects_taken_username() {
+    let app = TestApp::spawn().await;
+    let alice = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    app.client().signup("bob_", PASSWORD).await.unwrap();
+
+    assert_eq!(alice.change_username("bob_").await.unwrap_err().status, StatusCode::CONFLICT);
+    alice.change_username("alicia").await.unwrap();
+    app.client().login("alicia", PASSWORD).await.unwrap();
+}
+
+#[tokio::test]
+async fn metrics_need_the_token() {
+    let app = TestApp::spawn_with(|config| {
+        config.metrics.enabled = true;
+        config.metrics.bearer_token = Some("metrics-token".to_string());
+    }).await;
+    let client = app.client();
+
+    let response = client.http.get(client.url("/metrics")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.http.get(client.url("/metrics")).bearer_auth("wrong").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.http.get(client.url("/metrics")).bearer_auth("metrics-token").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("http_requests_total"));
+
+    let mut config = common::config(app.files.path());
+    config.metrics.enabled = true;
+    assert!(config.validate().is_err());
+
+    // off unless enabled
+    let disabled = TestApp::spawn().await;
+    let client = disabled.client();
+    let response = client.http.get(client.url("/metrics")).bearer_auth("metrics-token").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn errors_carry_the_request_id() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+
+    let response = client.http.get(client.url("/healthz")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client.http.get(client.url("/v1/user/me")).header("x-request-id", "test-request").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["x-request-id"], "test-request");
+    let failure: common::Failure = response.json().await.unwrap();
+    assert_eq!(failure.request_id.as_deref(), Some("test-request"));
+}
+
+/// Records logins, account changes and file actions, users only see their own entries
+async fn audit_log(app: &TestApp) {
+    let alice = app.admin("alice").await;
+    let bob = app.client();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    assert_eq!(bob.login("bob_", "wrong password").await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+    assert_eq!(bob.login("nobody", PASSWORD).await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+    bob.login("bob_", PASSWORD).await.unwrap();
+    let a = bob.upload(&[("a.txt", b"a")]).await.unwrap()[0].reference_uuid;
+    bob.download(a).await.unwrap();
+    bob.delete(a).await.unwrap();
+    assert_eq!(bob.delete(a).await.unwrap_err().status, StatusCode::NOT_FOUND);
+    alice.change_username("alicia").await.unwrap();
+
+    let log = bob.audit(&[]).await.unwrap();
+    let actions: Vec<_> = log.iter().map(|e| (e.action.as_str(), e.outcome.as_str())).collect();
+    assert_eq!(actions, [
+        ("delete", "failure"), ("delete", "success"), ("download", "success"), ("upload", "success"),
+        ("login", "success"), ("login", "failure"),
+    ]);
+    assert!(log.iter().all(|e| e.actor.as_deref() == Some("bob_") && e.ip.as_deref() == Some("127.0.0.1")));
+    assert_eq!((log[1].target_uuid, log[1].detail.as_deref()), (Some(a), Some("a.txt")));
+    assert_eq!(log[5].detail.as_deref(), Some("wrong username or password"));
+
+    let failed = bob.audit(&[("outcome", "failure")]).await.unwrap();
+    assert_eq!(failed.len(), 2);
+    let file = bob.audit(&[("target", &a.to_string()), ("limit", "1")]).await.unwrap();
+    assert_eq!((file.len(), file[0].action.as_str()), (1, "delete"));
+
+    // the admin sees everyone, failed logins of unknown usernames included
+    let all = alice.admin_audit(&[("action", "login"), ("outcome", "failure")]).await.unwrap();
+    let actors: Vec<_> = all.iter().map(|e| e.actor.as_deref()).collect();
+    assert_eq!(actors, [Some("nobody"), Some("bob_")]);
+    let renamed = alice.audit(&[]).await.unwrap();
+    assert_eq!((renamed[0].action.as_str(), renamed[0].detail.as_deref()), ("username_change", Some("alicia")));
+    assert_eq!(bob.admin_audit(&[]).await.unwrap_err().status, StatusCode::FORBIDDEN);
+
+    let (content_type, csv) = bob.export_audit("/v1/audit/export", "csv").await.unwrap();
+    assert!(content_type.starts_with("text/csv"));
+    let rows: Vec<_> = csv.lines().collect();
+    assert_eq!(rows[0], "id,timestamp,actor_uuid,actor,action,target_uuid,ip,user_agent,outcome,detail");
+    assert_eq!(rows.len(), 7);
+    assert!(rows[1].contains(",bob_,delete,") && rows[1].ends_with(",failure,file not found"));
+    let (_, json) = alice.export_audit("/v1/admin/audit/export", "json").await.unwrap();
+    let exported: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(exported.as_array().unwrap().len(), 9);
+
+    // admin actions are logged as the admin, targeting the user
+    let bob_uuid = alice.admin_find("bob_").await.unwrap().uuid;
+    alice.admin_set_quota(bob_uuid, Some(1024)).await.unwrap();
+    alice.admin_set_disabled(bob_uuid, true).await.unwrap();
+    alice.admin_set_disabled(bob_uuid, false).await.unwrap();
+    alice.admin_force_password_reset(bob_uuid).await.unwrap();
+    let admin: Vec<_> = alice.audit(&[("target", &bob_uuid.to_string())]).await.unwrap()
+        .into_iter()
+        .map(|e| (e.action, e.detail))
+        .collect();
+    assert_eq!(admin, [
+        ("password_reset".to_string(), None),
+        ("account_enable".to_string(), None),
+        ("account_disable".to_string(), None),
+        ("quota_change".to_string(), Some("1024".to_string())),
+    ]);
+}
+
+on_every_backend!(audit_log_of_logins_and_file_actions, audit_log);
+
+fn scan_config(clamd: &FakeClamd, mode: ScanMode) -> Option<ScanConfig> {
+    Some(ScanConfig { clamd_address: clamd.address.clone(), mode, ..ScanConfig::default() })
+}
+
+#[tokio::test]
+async fn uploads_are_scanned_and_infected_files_quarantined() {
+    let clamd = FakeClamd::spawn().await;
+    let app = TestApp::spawn_with(|config| config.scan = scan_config(&clamd, ScanMode::Sync)).await;
+    let alice = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+
+    let uploaded = alice.upload(&[("a.txt", b"a"), ("eicar.com", EICAR)]).await.unwrap();
+    let statuses: Vec<_> = uploaded.iter().map(|f| f.scan_status.as_str()).collect();
+    assert_eq!(statuses, ["clean", "infected"]);
+    let (clean, infected) = (uploaded[0].reference_uuid, uploaded[1].reference_uuid);
+
+    assert_eq!(alice.download(clean).await.unwrap().bytes, b"a");
+    assert_eq!(alice.download(infected).await.unwrap_err().status, StatusCode::FORBIDDEN);
+    let listed = alice.list(None, None).await.unwrap();
+    assert_eq!(listed.iter().find(|f| f.reference_uuid == infected).unwrap().scan_status, "infected");
+    let owner_dir = std::fs::read_dir(app.files.path().join("quarantine")).unwrap().next().unwrap().unwrap();
+    assert!(owner_dir.path().join(infected.to_string()).exists());
+    let copy = alice.batch(&[json!({ "op": "copy", "reference_uuid": infected })]).await.unwrap();
+    assert_eq!(copy.results[0].status, "invalid");
+
+    // files the scanner failed on aren't served until a scan finds them clean
+    clamd.fail(true);
+    let failed = alice.upload(&[("b.txt", b"b")]).await.unwrap().remove(0);
+    assert_eq!(failed.scan_status, "failed");
+    assert_eq!(alice.download(failed.reference_uuid).await.unwrap_err().status, StatusCode::CONFLICT);
+
+    // quarantined files can still be deleted
+    alice.delete(infected).await.unwrap();
+    assert_eq!(app.stored_files(), 2);
+}
+
+async fn background_scan(app: &TestApp) {
+    let alice = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+
+    let uploaded = alice.upload(&[("a.txt", b"a"), ("eicar.com", EICAR)]).await.unwrap();
+    assert!(uploaded.iter().all(|f| f.scan_status == "pending"));
+    assert_eq!(alice.download(uploaded[0].reference_uuid).await.unwrap_err().status, StatusCode::CONFLICT);
+    // copies of unscanned files are scanned on their own
+    let copied = alice.batch(&[json!({ "op": "copy", "reference_uuid": uploaded[0].reference_uuid, "filename": "copy.txt" })]).await.unwrap();
+    let copy = copied.results[0].copy_uuid.unwrap();
+    assert_eq!(alice.download(copy).await.unwrap_err().status, StatusCode::CONFLICT);
+
+    app.spawn_worker();
+    let mut listed = alice.list(None, None).await.unwrap();
+    for _ in 0..50 {
+        if listed.iter().all(|f| f.scan_status != "pending") {
+            break
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        listed = alice.list(None, None).await.unwrap();
+    }
+    let status = |name: &str| listed.iter().find(|f| f.filename == name).unwrap().scan_status.clone();
+    assert_eq!((status("a.txt").as_str(), status("eicar.com").as_str(), status("copy.txt").as_str()), ("clean", "infected", "clean"));
+    assert_eq!(alice.download(uploaded[0].reference_uuid).await.unwrap().bytes, b"a");
+    assert_eq!(alice.download(copy).await.unwrap().bytes, b"a");
+    assert_eq!(alice.download(uploaded[1].reference_uuid).await.unwrap_err().status, StatusCode::FORBIDDEN);
+
+    // sync clients learn about the verdicts
+    let changes = alice.changes(None, None).await.unwrap().changes;
+    let updated = changes.iter().filter(|c| c.kind == "updated").count();
+    assert_eq!((changes.len(), updated), (6, 3));
+}
+
+fn background_scan_config(clamd: &FakeClamd) -> impl FnOnce(&mut Config) + '_ {
+    |config| {
+        config.scan = scan_config(clamd, ScanMode::Async);
+        config.jobs.poll_interval = 1;
+    }
+}
+
+// every variant gets its own clamd, kept until the end of the test
+on_every_backend!(uploads_are_scanned_in_the_background, background_scan, background_scan_config(&FakeClamd::spawn().await));
+
+async fn profile_and_account_deletion(app: &TestApp) {
+    let first = app.client();
+    let second = app.client();
+    first.signup("alice", PASSWORD).await.unwrap();
+    second.login("alice", PASSWORD).await.unwrap();
+
+    first.upload(&[("a.txt", b"hello")]).await.unwrap();
+    let profile = first.me().await.unwrap();
+    assert_eq!((profile.username.as_str(), profile.permission.as_str()), ("alice", "USER"));
+    assert_eq!((profile.usage, profile.deletion_scheduled_for), (5, None));
+
+    let scheduled_for = first.delete_account(PASSWORD).await.unwrap();
+    assert_eq!(first.me().await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+    assert_eq!(second.me().await.unwrap().deletion_scheduled_for, Some(scheduled_for));
+
+    second.cancel_deletion().await.unwrap();
+    assert_eq!(second.me().await.unwrap().deletion_scheduled_for, None);
+}
+
+on_every_backend!(profile_and_account_deletion_of_users, profile_and_account_deletion, |config| config.account.deletion_grace_period = 3600);
+
+/// The new email is applied once the token mailed to it is confirmed
+async fn email_change(app: &TestApp) {
+    let alice = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    app.client().signup("bob_", PASSWORD).await.unwrap();
+
+    let failure = alice.change_email("new@example.com", "Wrong-123").await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::UNAUTHORIZED);
+    assert!(app.mails_to("new@example.com").is_empty());
+
+    alice.change_email("new@example.com", PASSWORD).await.unwrap();
+    let token = app.email_token("new@example.com");
+    assert_eq!(alice.me().await.unwrap().email, "alice@example.com");
+    assert_eq!(app.client().confirm_email("unknown").await.unwrap_err().status, StatusCode::BAD_REQUEST);
+
+    app.client().confirm_email(&token).await.unwrap();
+    assert_eq!(alice.me().await.unwrap().email, "new@example.com");
+    let notified: Vec<_> = app.mails_to("alice@example.com").into_iter().map(|m| m.subject).collect();
+    assert_eq!(notified, ["Your email has been changed"]);
+
+    let reused = app.client().confirm_email(&token).await.unwrap_err();
+    assert_eq!((reused.status, reused.message.as_str()), (StatusCode::BAD_REQUEST, "Token is invalid or expired"));
+
+    // taken regardless of the case
+    alice.change_email("BOB_@example.com", PASSWORD).await.unwrap();
+    let taken = app.client().confirm_email(&app.email_token("BOB_@example.com")).await.unwrap_err();
+    assert_eq!((taken.status, taken.message.as_str()), (StatusCode::CONFLICT, "Email is already taken"));
+    assert_eq!(alice.me().await.unwrap().email, "new@example.com");
+}
+
+on_every_backend!(email_change_needs_the_mailed_token, email_change);
+
+#[tokio::test]
+async fn email_change_tokens_expire() {
+    let app = TestApp::spawn_with(|config| config.account.email_token_lifetime = 1).await;
+    let alice = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+
+    alice.change_email("new@example.com", PASSWORD).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+
+    let failure = app.client().confirm_email(&app.email_token("new@example.com")).await.unwrap_err();
+    assert_eq!(failure.status, StatusCode::BAD_REQUEST);
+    assert_eq!(alice.me().await.unwrap().email, "alice@example.com");
+}
+
+/// Account purges are queued jobs, run here by a worker next to the server
+async fn account_purge(app: &TestApp) {
+    let admin = app.admin("alice").await;
+    let bob = app.client();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+    bob.upload(&[("a.txt", b"a")]).await.unwrap();
+
+    bob.delete_account(PASSWORD).await.unwrap();
+    let queued = admin.admin_jobs(Some("queued")).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!((queued[0].kind.as_str(), queued[0].attempts), ("purge_account", 0));
+    assert!(queued[0].payload["user_uuid"].is_string());
+
+    app.spawn_worker();
+    let mut job = admin.admin_job(queued[0].id).await.unwrap();
+    for _ in 0..50 {
+        if job.status == "succeeded" {
+            break
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        job = admin.admin_job(job.id).await.unwrap();
+    }
+    assert_eq!((job.status.as_str(), job.attempts), ("succeeded", 1));
+    assert_eq!(bob.login("bob_", PASSWORD).await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.stored_files(), 0);
+
+    let retried = admin.admin_retry_job(job.id).await.unwrap_err();
+    assert_eq!(retried.status, StatusCode::CONFLICT);
+    assert_eq!(admin.admin_job(i64::MAX).await.unwrap_err().status, StatusCode::NOT_FOUND);
+    assert_eq!(bob.admin_jobs(None).await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+}
+
+on_every_backend!(account_purge_runs_as_job, account_purge, |config| config.jobs.poll_interval = 1);
+
+/// Workers whose lease expired can't record an outcome, jobs abandoned on their last attempt end up failed
+async fn job_leases(app: &TestApp) {
+    let jobs = app.jobs();
+    let kinds = ["lease_test".to_string()];
+    let id = jobs.insert("lease_test", &json!({}), 2, 0, None).await.unwrap().unwrap();
+
+    // leases that expire right away, like workers that crashed
+    let first = jobs.claim(&kinds, -1).await.unwrap().unwrap();
+    let second = jobs.claim(&kinds, -1).await.unwrap().unwrap();
+    assert_eq!((first.id, second.id, second.attempts), (id, id, 2));
+    let (first_lease, second_lease) = (first.lease_token.unwrap(), second.lease_token.unwrap());
+    assert!(!jobs.renew(id, first_lease, 60).await.unwrap());
+    assert!(!jobs.succeed(id, first_lease).await.unwrap());
+    assert!(!jobs.fail(id, first_lease, "stale", None).await.unwrap());
+    assert_eq!(jobs.get(id).await.unwrap().unwrap().status, JobStatus::Running);
+
+    // out of attempts, so nobody claims it again and the prune pass fails it
+    assert!(jobs.claim(&kinds, 60).await.unwrap().is_none());
+    jobs.prune(0).await.unwrap();
+    let job = jobs.get(id).await.unwrap().unwrap();
+    assert_eq!((job.status, job.last_error.as_deref()), (JobStatus::Failed, Some("worker stopped while running the job")));
+    assert!(!jobs.succeed(id, second_lease).await.unwrap());
+
+    // a retried job is claimed with a new lease
+    assert!(jobs.retry(id).await.unwrap());
+    let third = jobs.claim(&kinds, 60).await.unwrap().unwrap();
+    assert!(!jobs.succeed(id, second_lease).await.unwrap());
+    assert!(jobs.renew(id, third.lease_token.unwrap(), 60).await.unwrap());
+    assert!(jobs.succeed(id, third.lease_token.unwrap()).await.unwrap());
+    assert_eq!(jobs.get(id).await.unwrap().unwrap().status, JobStatus::Succeeded);
+}
+
+#[test]
+fn schedules_compute_their_next_run() {
+    let at = |time: &str| chrono::DateTime::parse_from_rfc3339(time).unwrap().timestamp();
+
+    assert_eq!(Schedule::Every(3600).next_run(at("2026-01-01T00:10:00Z")), Some(at("2026-01-01T01:10:00Z")));
+
+    let nightly = Schedule::cron("0 30 2 * * *").unwrap();
+    assert_eq!(nightly.next_run(at("2026-01-01T00:10:00Z")), Some(at("2026-01-01T02:30:00Z")));
+    // a run at the exact time is the previous one
+    assert_eq!(nightly.next_run(at("2026-01-01T02:30:00Z")), Some(at("2026-01-02T02:30:00Z")));
+    let monthly = Schedule::cron("0 0 0 1 * *").unwrap();
+    assert_eq!(monthly.next_run(at("2026-01-31T23:59:59Z")), Some(at("2026-02-01T00:00:00Z")));
+    assert!(Schedule::cron("every day").is_err());
+}
+
+/// Only one caller wins a due run, cron schedules wait for their first time instead of running right away
+async fn recurring_jobs(app: &TestApp) {
+    let jobs = app.jobs();
+    let now = chrono::Utc::now().timestamp();
+
+    assert!(jobs.schedule_due("interval", now + 60, true).await.unwrap());
+    assert!(!jobs.schedule_due("interval", now + 60, true).await.unwrap());
+    assert!(!jobs.schedule_due("cron", now - 1, false).await.unwrap());
+    // stored as due a second ago
+    assert!(jobs.schedule_due("cron", now + 60, false).await.unwrap());
+    assert!(!jobs.schedule_due("cron", now + 120, false).await.unwrap());
+}
+
+on_every_backend!(recurring_jobs_run_once_per_time, recurring_jobs);
+
+#[derive(Serialize, Deserialize)]
+struct Panics;
+
+#[async_trait]
+impl Job for Panics {
+    const KIND: &'static str = "panics";
+
+    async fn run(self, _: &Appstate) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        panic!("broken job")
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Succeeds;
+
+#[async_trait]
+impl Job for Succeeds {
+    const KIND: &'static str = "succeeds";
+
+    async fn run(self, _: &Appstate) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+/// A panicking job is a failed attempt, the only worker keeps running the jobs after it
+#[tokio::test]
+async fn panicking_jobs_fail_without_killing_the_worker() {
+    let app = TestApp::spawn_with(|config| {
+        config.jobs.concurrency = 1;
+        config.jobs.poll_interval = 1;
+    }).await;
+    let jobs = app.jobs();
+    let panics = jobs.insert(Panics::KIND, &json!(null), 2, 0, None).await.unwrap().unwrap();
+    let succeeds = jobs.insert(Succeeds::KIND, &json!(null), 2, 1, None).await.unwrap().unwrap();
+    app.spawn_worker_with(Registry::new().register::<Panics>().register::<Succeeds>());
+
+    let mut succeeded = None;
+    for _ in 0..100 {
+        let job = jobs.get(succeeds).await.unwrap().unwrap();
+        if job.status == JobStatus::Succeeded {
+            succeeded = Some(job);
+            break
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert!(succeeded.is_some(), "the worker died with the panicking job");
+    let panicked = jobs.get(panics).await.unwrap().unwrap();
+    assert_eq!((panicked.status, panicked.attempts), (JobStatus::Queued, 1));
+    assert_eq!(panicked.last_error.as_deref(), Some("panicked: broken job"));
+}
+
+on_every_backend!(job_leases_are_owned_by_their_claim, job_leases);
+
+/// Databases set up by hand before migrations were tracked are upgraded, orphaned files stop the upgrade
+#[tokio::test]
+async fn untracked_databases_are_migrated_with_postgres() {
+    let Some(server_url) = common::database_server() else { return };
+    let database = EphemeralDatabase::create_empty(&server_url).await;
+    let pool = sqlx::PgPool::connect(&database.url).await.unwrap();
+    sqlx::raw_sql(include_str!("../migrations/0001_bundled.sql")).execute(&pool).await.unwrap();
+    sqlx::raw_sql(
+        "INSERT INTO users (uuid, username, email, password, tokenid) \
+         VALUES ('5a1a4b1e-0000-4000-8000-000000000001', 'alice', 'alice@example.com', 'hash', '5a1a4b1e-0000-4000-8000-000000000004');
+         INSERT INTO file (reference_uuid, owner_uuid, filename) VALUES
+             ('5a1a4b1e-0000-4000-8000-000000000002', '5a1a4b1e-0000-4000-8000-000000000001', 'kept.txt'),
+             ('5a1a4b1e-0000-4000-8000-000000000003', '5a1a4b1e-0000-4000-8000-000000000009', 'orphan.txt');",
+    ).execute(&pool).await.unwrap();
+
+    let error = migrate::run(&pool).await.unwrap_err().to_string();
+    assert!(error.contains("1 files belong to users that no longer exist"), "{}", error);
+
+    sqlx::query("DELETE FROM file WHERE filename = 'orphan.txt'").execute(&pool).await.unwrap();
+    migrate::run(&pool).await.unwrap();
+    let files: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM file").fetch_one(&pool).await.unwrap();
+    assert_eq!(files, 1);
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn search_with_postgres() {
+    let Some(app) = TestApp::spawn_postgres(|_| {}).await else { return };
+    let alice = app.client();
+    let bob = app.client();
+    alice.signup("alice", PASSWORD).await.unwrap();
+    bob.signup("bob_", PASSWORD).await.unwrap();
+
+    alice.upload(&[
+        ("Quarterly Report.md", b"# Revenue grew"),
+        ("photo.png", &[137, 80, 78, 71]),
+        ("notes.txt", b"meeting about the revenue and 100% of it"),
+    ]).await.unwrap();
+
+    // typos still find the name
+    assert_eq!(search(&alice, "quartely").await, ["Quarterly Report.md"]);
+    assert_eq!(search(&alice, "image/png").await, ["photo.png"]);
+    assert_eq!(search(&alice, "meeting revenue").await, ["notes.txt"]);
+    // wildcards of the substring match are escaped
+    assert!(search(&alice, "%").await.is_empty());
+
+    assert!(search(&bob, "revenue").await.is_empty());
+}
+
+#[tokio::test]
+async fn oidc_logins_are_verified() {
+    let issuer = MockIssuer::spawn().await;
+    let app = TestApp::spawn_with(|config| config.oidc = Some(issuer.config())).await;
+
+    let rejected = [
+        IdToken { forged: true, ..IdToken::new("alice") },
+        IdToken { audience: Some("another-client".to_string()), ..IdToken::new("alice") },
+        IdToken { nonce: Some("replayed".to_string()), ..IdToken::new("alice") },
+        IdToken { expires_in: -600, ..IdToken::new("alice") },
+        IdToken { algorithm: Algorithm::HS256, ..IdToken::new("alice") },
+    ];
+    for token in rejected {
+        let client = app.client();
+        assert_eq!(client.oidc_login(&issuer, token).await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+        assert_eq!(client.me().await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+    }
+
+    let alice = app.client();
+    alice.oidc_login(&issuer, IdToken::new("alice")).await.unwrap();
+    let profile = alice.me().await.unwrap();
+    assert_eq!((profile.email.as_str(), profile.permission.as_str()), ("alice@example.com", "USER"));
+
+    // the identity stays linked to the provisioned user
+    let again = app.client();
+    again.oidc_login(&issuer, IdToken::new("alice")).await.unwrap();
+    assert_eq!(again.me().await.unwrap().uuid, profile.uuid);
+}
+
+/// The pending login is consumed by the callback, its cookie expires on the path it was set on
+#[tokio::test]
+async fn oidc_callback_expires_the_pending_login() {
+    let issuer = MockIssuer::spawn().await;
+    let app = TestApp::spawn_with(|config| config.oidc = Some(issuer.config())).await;
+    let client = app.client();
+
+    let response = client.http.get(client.url("/v1/user/oidc/login")).send().await.unwrap();
+    let pending = response.headers()["set-cookie"].to_str().unwrap().to_string();
+    assert!(pending.starts_with("oidc=") && pending.contains("Path=/v1/user/oidc"), "{}", pending);
+    let location = reqwest::Url::parse(response.headers()["location"].to_str().unwrap()).unwrap();
+    let state = location.query_pairs().find(|(key, _)| key == "state").unwrap().1.into_owned();
+    let code = issuer.authorize(&location, IdToken::new("alice"));
+
+    let query = [("code", code), ("state", state)];
+    let response = client.http.get(client.url("/v1/user/oidc/callback")).query(&query).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let removed = response.headers().get_all("set-cookie").iter()
+        .map(|cookie| cookie.to_str().unwrap())
+        .find(|cookie| cookie.starts_with("oidc="))
+        .expect("pending login cookie isn't removed")
+        .to_string();
+    assert!(removed.contains("Path=/v1/user/oidc") && removed.contains("Max-Age=0"), "{}", removed);
+
+    let replay = client.http.get(client.url("/v1/user/oidc/callback")).query(&query).send().await.unwrap();
+    assert_eq!(replay.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Users provisioned by single sign-on have no password, a fresh login confirms the deletion instead
+#[tokio::test]
+async fn sso_users_can_delete_their_account() {
+    let issuer = MockIssuer::spawn().await;
+    let app = TestApp::spawn_with(|config| config.oidc = Some(issuer.config())).await;
+
+    let alice = app.client();
+    alice.oidc_login(&issuer, IdToken::new("alice")).await.unwrap();
+    alice.refresh_token().await.unwrap();
+    alice.delete_account_after_login().await.unwrap();
+    assert_eq!(alice.me().await.unwrap_err().status, StatusCode::UNAUTHORIZED);
+
+    // an old login isn't enough
+    let stale = TestApp::spawn_with(|config| {
+        config.oidc = Some(issuer.config());
+        config.account.reauthentication_window = 0;
+    }).await;
+    let bob = stale.client();
+    bob.oidc_login(&issuer, IdToken::new("bob")).await.unwrap();
+    let failure = bob.delete_account_after_login().await.unwrap_err();
+    assert_eq!((failure.status, failure.message.as_str()), (StatusCode::UNAUTHORIZED, "Log in again to confirm the deletion"));
+}
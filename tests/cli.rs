@@ -0,0 +1,93 @@
+mod common;
+
+use clap::Parser;
+use common::{TestApp, PASSWORD};
+use drive_lib::cli::args::{Cli, Command};
+use drive_lib::cli::{storage, users};
+use std::path::{Path, PathBuf};
+
+/// First stored file below `root`
+fn stored_file(root: &Path) -> PathBuf {
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                dirs.push(entry.path());
+            } else {
+                return entry.path()
+            }
+        }
+    }
+    panic!("no file below {}", root.display())
+}
+
+#[test]
+fn commands_are_parsed() {
+    assert!(Cli::try_parse_from(["drive"]).unwrap().command.is_none());
+    assert!(matches!(Cli::try_parse_from(["drive", "serve"]).unwrap().command, Some(Command::Serve)));
+
+    let cli = Cli::try_parse_from(["drive", "create-admin", "alice", "alice@example.com"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::CreateAdmin { username, email }) if username == "alice" && email == "alice@example.com"));
+
+    assert!(matches!(Cli::try_parse_from(["drive", "verify-storage"]).unwrap().command, Some(Command::VerifyStorage { fix: false })));
+    assert!(matches!(Cli::try_parse_from(["drive", "verify-storage", "--fix"]).unwrap().command, Some(Command::VerifyStorage { fix: true })));
+
+    let cli = Cli::try_parse_from(["drive", "import", "alice", "/srv/old"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Import { source, .. }) if source == Path::new("/srv/old")));
+}
+
+#[test]
+fn invalid_arguments_are_rejected() {
+    for args in [
+        &["drive", "create-admin", "alice"][..],
+        &["drive", "reset-password"],
+        &["drive", "verify-storage", "--repair"],
+        &["drive", "unknown"],
+    ] {
+        assert!(Cli::try_parse_from(args).is_err(), "{:?}", args);
+    }
+}
+
+#[tokio::test]
+async fn create_admin_validates_and_creates() {
+    let app = TestApp::spawn().await;
+    let create = |username: &str, password: &str| {
+        users::create_admin(username.to_string(), format!("{}@example.com", username), password.to_string(), app.appstate())
+    };
+
+    let invalid = create("al", "short").await.unwrap_err().to_string();
+    assert!(invalid.starts_with("username: ") && invalid.contains("\npassword: "), "{}", invalid);
+
+    create("alice", PASSWORD).await.unwrap();
+    assert_eq!(create("alice", PASSWORD).await.unwrap_err().to_string(), "Username is already taken");
+
+    let client = app.client();
+    client.login("alice", PASSWORD).await.unwrap();
+    assert_eq!(client.me().await.unwrap().permission, "ADMIN");
+}
+
+#[tokio::test]
+async fn verify_storage_reports_and_fixes_problems() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    client.signup("alice", PASSWORD).await.unwrap();
+    client.upload(&[("a.txt", b"a"), ("b.txt", b"b")]).await.unwrap();
+    storage::verify(false, app.appstate()).await.unwrap();
+
+    // a missing file and an orphan that nothing wrote to for a while
+    std::fs::remove_file(stored_file(app.files.path())).unwrap();
+    let orphan = app.files.path().join("orphan");
+    std::fs::write(&orphan, b"orphan").unwrap();
+    let file = std::fs::File::options().write(true).open(&orphan).unwrap();
+    file.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 3600)).unwrap();
+
+    let problems = storage::verify(false, app.appstate()).await.unwrap_err().to_string();
+    assert_eq!(problems, "Found 2 problems, run with --fix to repair them");
+    assert!(orphan.exists());
+
+    storage::verify(true, app.appstate()).await.unwrap();
+    storage::verify(false, app.appstate()).await.unwrap();
+    assert!(!orphan.exists());
+    assert_eq!(client.list(None, None).await.unwrap().len(), 1);
+}
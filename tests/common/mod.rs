@@ -0,0 +1,1295 @@
+//! Runs the whole application on a random port and talks to it over http \
+//! Users and files are kept in memory unless a test asks for an ephemeral postgres database
+#![allow(dead_code)]
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use async_trait::async_trait;
+use base64::Engine;
+use drive_lib::app;
+use drive_lib::cli::users;
+use drive_lib::config::{Config, RateLimitBackend, RepositoryBackend};
+use drive_lib::jobs::{partial_files, worker};
+use drive_lib::migrate;
+use drive_lib::models::appstate::Appstate;
+use drive_lib::models::job::Registry;
+use drive_lib::repository::job::JobRepository;
+use drive_lib::router;
+use drive_lib::util::auth::ldap::{LdapAuthProvider, LdapConfig};
+use drive_lib::util::mail::mailer::Mailer;
+use drive_lib::util::oidc::{OidcConfig, OidcProvider};
+use jsonwebtoken::{Algorithm, EncodingKey};
+use reqwest::header::{HeaderMap, CONTENT_DISPOSITION};
+use reqwest::multipart::{Form, Part};
+use reqwest::{RequestBuilder, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Connection, PgConnection};
+use std::collections::BTreeMap;
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+/// Passes the password validation
+pub const PASSWORD: &str = "Secret-123";
+
+/// Server used by the postgres tests, they pass without running if it isn't set \
+/// Every test creates its own database on it and drops it afterwards
+pub const DATABASE_URL_VAR: &str = "TEST_DATABASE_URL";
+
+/// Postgres server of `DATABASE_URL_VAR`, None tells the calling test to skip itself
+pub fn database_server() -> Option<String> {
+    let url = std::env::var(DATABASE_URL_VAR).ok();
+    if url.is_none() {
+        eprintln!("skipped, {} isn't set", DATABASE_URL_VAR);
+    }
+    url
+}
+
+/// Runs `$test(&TestApp)` once per backend, as the tests `$name::memory` and `$name::postgres` \
+/// `$adjust` is applied to the config of both, e.g. `allow_receivers`
+#[macro_export]
+macro_rules! on_every_backend {
+    ($name:ident, $test:ident) => {
+        $crate::on_every_backend!($name, $test, |_| {});
+    };
+    ($name:ident, $test:ident, $adjust:expr) => {
+        mod $name {
+            use super::*;
+
+            #[tokio::test]
+            async fn memory() {
+                $test(&$crate::common::TestApp::spawn_with($adjust).await).await;
+            }
+
+            #[tokio::test]
+            async fn postgres() {
+                if let Some(app) = $crate::common::TestApp::spawn_postgres($adjust).await {
+                    $test(&app).await;
+                }
+            }
+        }
+    };
+}
+
+/// Lets webhooks reach the `WebhookReceiver`, internal addresses are refused otherwise
+pub fn allow_receivers(config: &mut Config) {
+    config.webhooks.allowed_hosts = vec!["localhost".to_string()];
+}
+
+/// Valid config without a reachable database
+pub fn config(file_location: &Path) -> Config {
+    let mut config = Config::default();
+    config.server.bind_address = "127.0.0.1:0".to_string();
+    config.database.url = "postgres://localhost/unused".to_string();
+    config.database.repository = RepositoryBackend::Memory;
+    config.auth.jwt_secret = "test-jwt-secret".to_string();
+    config.auth.cookie_secret = "test-cookie-secret".repeat(4);
+    config.storage.file_location = file_location.to_string_lossy().to_string();
+    config.rate_limit.store = RateLimitBackend::Memory;
+    config
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Readiness {
+    pub ready: bool,
+    pub draining: bool,
+    pub database: bool,
+    pub storage: bool,
+}
+
+/// Running server, stopped at the end of the test
+pub struct TestApp {
+    pub address: SocketAddr,
+    /// Temporary `FILE_LOCATION`
+    pub files: TempDir,
+    config: Config,
+    /// Same state the server runs on, shares the memory repositories with it
+    appstate: Arc<Appstate>,
+    mailer: Arc<RecordingMailer>,
+    database: Option<EphemeralDatabase>,
+}
+
+impl TestApp {
+    pub async fn spawn() -> Self {
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Lets the test adjust the config before the server starts
+    pub async fn spawn_with(adjust: impl FnOnce(&mut Config)) -> Self {
+        let files = TempDir::new().unwrap();
+        let mut config = config(files.path());
+        adjust(&mut config);
+
+        Self::start(config, files, None).await
+    }
+
+    /// Keeps everything in a fresh postgres database on the server in `TEST_DATABASE_URL`, None if it isn't set
+    pub async fn spawn_postgres(adjust: impl FnOnce(&mut Config)) -> Option<Self> {
+        let server_url = database_server()?;
+        let database = EphemeralDatabase::create(&server_url).await;
+
+        let files = TempDir::new().unwrap();
+        let mut config = config(files.path());
+        config.database.url = database.url.clone();
+        config.database.repository = RepositoryBackend::Postgres;
+        adjust(&mut config);
+
+        Some(Self::start(config, files, Some(database)).await)
+    }
+
+    async fn start(config: Config, files: TempDir, database: Option<EphemeralDatabase>) -> Self {
+        let listener = tokio::net::TcpListener::bind(&config.server.bind_address).await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        config.validate().unwrap();
+        let pool = PgPoolOptions::new().connect_lazy(&config.database.url).unwrap();
+        let mailer = Arc::new(RecordingMailer::default());
+        let mut appstate = app::appstate(Arc::new(config.clone()), Arc::new(pool)).unwrap().with_mailer(mailer.clone());
+        // set up by `serve` like this
+        if let Some(oidc) = &config.oidc {
+            appstate = appstate.with_oidc(Arc::new(OidcProvider::discover(oidc.clone()).await.unwrap()));
+        }
+        if let Some(ldap) = &config.ldap {
+            appstate = appstate.with_auth_provider(Arc::new(LdapAuthProvider::new(ldap.clone())));
+        }
+        let appstate = Arc::new(appstate);
+        let events = appstate.events.clone();
+        tokio::spawn(async move { events.listen().await });
+        let app = router::app(appstate.clone());
+        let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>());
+        tokio::spawn(server.into_future());
+
+        Self { address, files, config, appstate, mailer, database }
+    }
+
+    /// Cuts the connection the postgres event bus listens on, like a database restart would
+    pub async fn break_event_bus(&self) {
+        let database = self.database.as_ref().expect("only postgres apps have an event bus to break");
+        let mut connection = PgConnection::connect(&database.url).await.unwrap();
+        let query = r"SELECT pg_terminate_backend(pid) FROM pg_stat_activity
+                      WHERE datname = current_database() AND query ILIKE 'listen%'";
+        let terminated: Vec<bool> = sqlx::query_scalar(query).fetch_all(&mut connection).await.unwrap();
+        assert!(!terminated.is_empty(), "the event bus isn't listening");
+    }
+
+    /// Starts draining like the first shutdown signal does
+    pub fn begin_drain(&self) {
+        self.appstate.shutdown.begin_drain();
+    }
+
+    /// Runs the background jobs on the repositories of the app, like `drive worker` next to the server
+    pub fn spawn_worker(&self) {
+        tokio::spawn(worker::run(self.appstate.clone()));
+    }
+
+    /// Runs the jobs of `registry` instead of the ones of the release
+    pub fn spawn_worker_with(&self, registry: Registry) {
+        tokio::spawn(worker::run_with(self.appstate.clone(), registry));
+    }
+
+    /// State the server runs on, for the commands of the cli
+    pub fn appstate(&self) -> &Appstate {
+        &self.appstate
+    }
+
+    /// Queue the server and the workers share, for tests of the queue itself
+    pub fn jobs(&self) -> Arc<dyn JobRepository> {
+        self.appstate.jobs.clone()
+    }
+
+    /// Mails sent to `to` so far, oldest first
+    pub fn mails_to(&self, to: &str) -> Vec<Mail> {
+        self.mailer.sent.lock().unwrap().iter().filter(|m| m.to == to).cloned().collect()
+    }
+
+    /// Token of the last confirmation mail sent to `to`
+    pub fn email_token(&self, to: &str) -> String {
+        let mail = self.mails_to(to).pop().unwrap_or_else(|| panic!("no mail sent to {}", to));
+        // on its own line after the introduction
+        mail.body.split("\n\n").nth(2).unwrap().to_string()
+    }
+
+    /// Runs one pass of the partial file cleanup the server starts with
+    pub async fn remove_partial_files(&self) -> usize {
+        partial_files::remove(&self.appstate).await.unwrap()
+    }
+
+    /// Creates an admin like `drive create-admin` and returns a client logged in as it
+    pub async fn admin(&self, username: &str) -> TestClient {
+        let email = format!("{}@example.com", username);
+        users::create_admin(username.to_string(), email, PASSWORD.to_string(), &self.appstate).await.unwrap();
+        let client = self.client();
+        client.login(username, PASSWORD).await.unwrap();
+        client
+    }
+
+    /// Client with its own cookies, i.e. a separate session
+    pub fn client(&self) -> TestClient {
+        TestClient {
+            // redirects are checked by the tests, e.g. the one to the identity provider
+            http: reqwest::Client::builder().cookie_store(true).redirect(reqwest::redirect::Policy::none()).build().unwrap(),
+            base_url: format!("http://{}", self.address),
+        }
+    }
+
+    /// Number of files below the temporary `FILE_LOCATION`
+    pub fn stored_files(&self) -> usize {
+        let mut count = 0;
+        let mut dirs = vec![self.files.path().to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let entry = entry.unwrap();
+                if entry.file_type().unwrap().is_dir() {
+                    dirs.push(entry.path());
+                } else {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Keeps the mails instead of sending them
+#[derive(Default)]
+pub struct RecordingMailer {
+    sent: Mutex<Vec<Mail>>,
+}
+
+#[async_trait]
+impl Mailer for RecordingMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mail = Mail { to: to.to_string(), subject: subject.to_string(), body: body.to_string() };
+        self.sent.lock().unwrap().push(mail);
+        Ok(())
+    }
+}
+
+/// Database created for a single test, dropped together with the `TestApp`
+pub struct EphemeralDatabase {
+    server_url: String,
+    name: String,
+    pub url: String,
+}
+
+impl EphemeralDatabase {
+    async fn create(server_url: &str) -> Self {
+        let database = Self::create_empty(server_url).await;
+        let pool = sqlx::PgPool::connect(&database.url).await.unwrap();
+        migrate::run(&pool).await.unwrap();
+        pool.close().await;
+        database
+    }
+
+    /// Database without any migrations applied, on the server in `TEST_DATABASE_URL`
+    pub async fn create_empty(server_url: &str) -> Self {
+        let name = format!("drive_test_{}", Uuid::new_v4().simple());
+        let mut connection = PgConnection::connect(server_url).await.unwrap();
+        sqlx::raw_sql(&format!(r#"CREATE DATABASE "{}""#, name)).execute(&mut connection).await.unwrap();
+
+        let mut url = Url::parse(server_url).unwrap();
+        url.set_path(&name);
+        let url = url.to_string();
+
+        Self { server_url: server_url.to_string(), name, url }
+    }
+}
+
+impl Drop for EphemeralDatabase {
+    fn drop(&mut self) {
+        let (server_url, name) = (self.server_url.clone(), self.name.clone());
+        // drop can't await, the server of the test may still hold connections
+        let cleanup = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                let mut connection = PgConnection::connect(&server_url).await?;
+                sqlx::raw_sql(&format!(r#"DROP DATABASE IF EXISTS "{}" WITH (FORCE)"#, name))
+                    .execute(&mut connection)
+                    .await
+                    .map(|_| ())
+            })
+        });
+        if let Ok(Err(e)) = cleanup.join() {
+            eprintln!("Failed to drop test database {}: {}", self.name, e);
+        }
+    }
+}
+
+/// Error response of the api
+#[derive(Debug, Deserialize)]
+pub struct Failure {
+    #[serde(skip, default)]
+    pub status: StatusCode,
+    pub code: String,
+    pub message: String,
+    pub details: Vec<FieldError>,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub reason: String,
+}
+
+pub type ApiResult<T> = Result<T, Failure>;
+
+#[derive(Debug, Deserialize)]
+pub struct UploadedFile {
+    pub reference_uuid: Uuid,
+    pub filename: String,
+    pub scan_status: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FileInfo {
+    pub reference_uuid: Uuid,
+    pub filename: String,
+    pub mime_type: String,
+    pub size: usize,
+    pub starred: bool,
+    pub tags: Vec<String>,
+    pub metadata: BTreeMap<String, String>,
+    pub sha256: Option<String>,
+    pub scan_status: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkTagged {
+    pub updated: Vec<Uuid>,
+    pub skipped: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchItem {
+    pub index: usize,
+    pub reference_uuid: Uuid,
+    pub status: String,
+    pub message: Option<String>,
+    pub copy_uuid: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchStatus {
+    pub batch_id: Option<Uuid>,
+    pub total: usize,
+    pub completed: usize,
+    pub finished: bool,
+    pub results: Vec<BatchItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Change {
+    pub id: i64,
+    pub reference_uuid: Uuid,
+    pub kind: String,
+    pub filename: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Changes {
+    pub changes: Vec<Change>,
+    pub cursor: i64,
+    pub has_more: bool,
+}
+
+/// Server-sent events of `/v1/changes/events`
+pub struct EventStream {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl EventStream {
+    /// Next change, panics if none arrives within five seconds
+    pub async fn next(&mut self) -> Change {
+        self.next_within(Duration::from_secs(5)).await
+    }
+
+    pub async fn next_within(&mut self, timeout: Duration) -> Change {
+        tokio::time::timeout(timeout, async {
+            loop {
+                // events end with an empty line, keep-alive comments carry no data
+                while let Some(end) = self.buffer.find("\n\n") {
+                    let event: String = self.buffer.drain(..end + 2).collect();
+                    if let Some(data) = event.lines().find_map(|line| line.strip_prefix("data:")) {
+                        return serde_json::from_str(data.trim()).unwrap()
+                    }
+                }
+                let chunk = self.response.chunk().await.unwrap().expect("event stream ended");
+                self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+            }
+        }).await.expect("no event in time")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Webhook {
+    pub uuid: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    /// Only returned on creation
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Delivery {
+    pub id: i64,
+    pub event: String,
+    pub status: String,
+    pub attempts: u32,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Request received by a `WebhookReceiver`
+#[derive(Debug)]
+pub struct Received {
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl Received {
+    pub fn header(&self, name: &str) -> &str {
+        self.headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default()
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// Local http server standing in for the receiver of webhooks, answers every request with `status`
+pub struct WebhookReceiver {
+    /// On `localhost`, which apps only reach with `allow_receivers`
+    pub url: String,
+    status: Arc<AtomicU16>,
+    requests: tokio::sync::mpsc::UnboundedReceiver<Received>,
+}
+
+impl WebhookReceiver {
+    pub async fn spawn() -> Self {
+        let status = Arc::new(AtomicU16::new(200));
+        let (sender, requests) = tokio::sync::mpsc::unbounded_channel();
+        let answer = status.clone();
+        let router = axum::Router::new().route("/hook", axum::routing::post(
+            move |headers: HeaderMap, body: String| async move {
+                let _ = sender.send(Received { headers, body });
+                StatusCode::from_u16(answer.load(Ordering::SeqCst)).unwrap()
+            }
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://localhost:{}/hook", listener.local_addr().unwrap().port());
+        tokio::spawn(axum::serve(listener, router).into_future());
+        Self { url, status, requests }
+    }
+
+    /// Status of the following answers
+    pub fn answer_with(&self, status: u16) {
+        self.status.store(status, Ordering::SeqCst);
+    }
+
+    /// Next request, panics if none arrives within five seconds
+    pub async fn next(&mut self) -> Received {
+        tokio::time::timeout(Duration::from_secs(5), self.requests.recv()).await
+            .expect("no webhook within five seconds")
+            .expect("receiver stopped")
+    }
+}
+
+/// Content that the `FakeClamd` reports as infected, the test string of the EICAR
+pub const EICAR: &[u8] = br"X5O!P%@AP[4\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+/// Local tcp server speaking the INSTREAM protocol of clamd, finds only the `EICAR` string
+pub struct FakeClamd {
+    pub address: String,
+    failing: Arc<AtomicBool>,
+}
+
+impl FakeClamd {
+    pub async fn spawn() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let failing = Arc::new(AtomicBool::new(false));
+        let fail = failing.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(Self::answer(stream, fail.load(Ordering::SeqCst)));
+            }
+        });
+        Self { address, failing }
+    }
+
+    /// Answers the following scans with an error
+    pub fn fail(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    async fn answer(mut stream: tokio::net::TcpStream, fail: bool) {
+        let mut command = [0; 10];
+        stream.read_exact(&mut command).await.unwrap();
+        assert_eq!(&command, b"zINSTREAM\0");
+
+        let mut content = Vec::new();
+        loop {
+            let length = stream.read_u32().await.unwrap() as usize;
+            if length == 0 {
+                break
+            }
+            let mut chunk = vec![0; length];
+            stream.read_exact(&mut chunk).await.unwrap();
+            content.extend(chunk);
+        }
+
+        let reply: &[u8] = match (fail, content.windows(EICAR.len()).any(|w| w == EICAR)) {
+            (true, _) => b"INSTREAM size limit exceeded. ERROR\0",
+            (_, true) => b"stream: Eicar-Test-Signature FOUND\0",
+            (_, false) => b"stream: OK\0",
+        };
+        stream.write_all(reply).await.unwrap();
+    }
+}
+
+/// Local tcp server speaking just enough ldap for the provider: simple binds, `uid` searches and unbinds \
+/// Users are `uid={uid},ou=people,dc=example,dc=com` with the mail `{uid}@example.com`
+pub struct FakeLdap {
+    pub url: String,
+}
+
+impl FakeLdap {
+    pub const BASE_DN: &str = "ou=people,dc=example,dc=com";
+    pub const ADMIN_GROUP: &str = "cn=admins,ou=groups,dc=example,dc=com";
+    const SERVICE_DN: &str = "cn=service,dc=example,dc=com";
+    const SERVICE_PASSWORD: &str = "service-secret";
+
+    /// Takes `(uid, password, groups)` of every user
+    pub async fn spawn(users: &[(&str, &str, &[&str])]) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ldap://{}", listener.local_addr().unwrap());
+        let users: Arc<Vec<LdapUser>> = Arc::new(users.iter().map(|(uid, password, groups)| LdapUser {
+            uid: uid.to_string(),
+            password: password.to_string(),
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+        }).collect());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(Self::answer(stream, users.clone()));
+            }
+        });
+        Self { url }
+    }
+
+    /// Provider settings for this directory, members of `ADMIN_GROUP` become admins
+    pub fn config(&self) -> LdapConfig {
+        LdapConfig {
+            url: self.url.clone(),
+            bind_dn: Self::SERVICE_DN.to_string(),
+            bind_password: Self::SERVICE_PASSWORD.to_string(),
+            base_dn: Self::BASE_DN.to_string(),
+            admin_group: Some(Self::ADMIN_GROUP.to_string()),
+            ..LdapConfig::default()
+        }
+    }
+
+    async fn answer(mut stream: tokio::net::TcpStream, users: Arc<Vec<LdapUser>>) {
+        while let Some(message) = ber::read(&mut stream).await {
+            let message = ber::elements(&message);
+            let id = &message[0].1;
+            let (tag, operation) = &message[1];
+            let reply = match tag {
+                // bind
+                0x60 => {
+                    let fields = ber::elements(operation);
+                    let (dn, password) = (ber::string(&fields[1].1), ber::string(&fields[2].1));
+                    let valid = (dn == Self::SERVICE_DN && password == Self::SERVICE_PASSWORD)
+                        || users.iter().any(|u| u.dn() == dn && u.password == password);
+                    ber::message(id, 0x61, &ber::result(if valid { 0 } else { 49 }))
+                },
+                // search, only equality filters on uid
+                0x63 => {
+                    let fields = ber::elements(operation);
+                    let filter = ber::elements(&fields[6].1);
+                    let uid = ber::string(&filter[1].1);
+                    let mut reply = Vec::new();
+                    for user in users.iter().filter(|u| u.uid.eq_ignore_ascii_case(&uid)) {
+                        let groups: Vec<u8> = user.groups.iter().flat_map(|g| ber::tlv(0x04, g.as_bytes())).collect();
+                        let attributes = [
+                            ber::tlv(0x30, &[ber::tlv(0x04, b"mail"), ber::tlv(0x31, &ber::tlv(0x04, format!("{}@example.com", user.uid).as_bytes()))].concat()),
+                            ber::tlv(0x30, &[ber::tlv(0x04, b"memberOf"), ber::tlv(0x31, &groups)].concat()),
+                        ].concat();
+                        let entry = [ber::tlv(0x04, user.dn().as_bytes()), ber::tlv(0x30, &attributes)].concat();
+                        reply.extend(ber::message(id, 0x64, &entry));
+                    }
+                    reply.extend(ber::message(id, 0x65, &ber::result(0)));
+                    reply
+                },
+                // unbind or anything unsupported ends the connection
+                _ => return,
+            };
+            if stream.write_all(&reply).await.is_err() {
+                return
+            }
+        }
+    }
+}
+
+struct LdapUser {
+    uid: String,
+    password: String,
+    groups: Vec<String>,
+}
+
+impl LdapUser {
+    fn dn(&self) -> String {
+        format!("uid={},{}", self.uid, FakeLdap::BASE_DN)
+    }
+}
+
+/// Minimal BER for `FakeLdap`
+mod ber {
+    use tokio::io::AsyncReadExt;
+
+    /// Content of the next element on the stream, None once it's closed
+    pub async fn read(stream: &mut tokio::net::TcpStream) -> Option<Vec<u8>> {
+        let _tag = stream.read_u8().await.ok()?;
+        let first = stream.read_u8().await.ok()? as usize;
+        let length = match first & 0x80 {
+            0 => first,
+            _ => {
+                let mut length = 0;
+                for _ in 0..first & 0x7f {
+                    length = length << 8 | stream.read_u8().await.ok()? as usize;
+                }
+                length
+            },
+        };
+        let mut content = vec![0; length];
+        stream.read_exact(&mut content).await.ok()?;
+        Some(content)
+    }
+
+    /// Splits consecutive elements into tag and content
+    pub fn elements(mut bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut elements = Vec::new();
+        while !bytes.is_empty() {
+            let tag = bytes[0];
+            let (length, header) = match bytes[1] & 0x80 {
+                0 => (bytes[1] as usize, 2),
+                _ => {
+                    let count = (bytes[1] & 0x7f) as usize;
+                    let length = bytes[2..2 + count].iter().fold(0, |length, b| length << 8 | *b as usize);
+                    (length, 2 + count)
+                },
+            };
+            elements.push((tag, bytes[header..header + length].to_vec()));
+            bytes = &bytes[header + length..];
+        }
+        elements
+    }
+
+    pub fn string(bytes: &[u8]) -> String {
+        String::from_utf8_lossy(bytes).to_string()
+    }
+
+    pub fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![tag];
+        match content.len() {
+            length @ 0..=127 => bytes.push(length as u8),
+            length => bytes.extend([0x82, (length >> 8) as u8, length as u8]),
+        }
+        bytes.extend(content);
+        bytes
+    }
+
+    /// `LDAPResult` without matched dn and message
+    pub fn result(code: u8) -> Vec<u8> {
+        [tlv(0x0a, &[code]), tlv(0x04, b""), tlv(0x04, b"")].concat()
+    }
+
+    /// Answer to the request with the message id `id`
+    pub fn message(id: &[u8], tag: u8, operation: &[u8]) -> Vec<u8> {
+        tlv(0x30, &[tlv(0x02, id), tlv(tag, operation)].concat())
+    }
+}
+
+/// Local OpenID Connect provider with discovery, keys and a token endpoint \
+/// The authorization itself is skipped, `authorize` hands out the code the browser would bring back
+pub struct MockIssuer {
+    pub url: String,
+    codes: Arc<std::sync::Mutex<BTreeMap<String, Authorized>>>,
+}
+
+/// Id token of an authorized code, waiting for the exchange
+struct Authorized {
+    code_challenge: String,
+    claims: serde_json::Value,
+    token: IdToken,
+}
+
+/// How the `MockIssuer` makes the next id token, valid unless changed
+#[derive(Clone)]
+pub struct IdToken {
+    pub subject: String,
+    pub email: Option<String>,
+    /// Defaults to the client id
+    pub audience: Option<String>,
+    /// Defaults to the nonce of the authorization request
+    pub nonce: Option<String>,
+    /// Negative for tokens that expired already
+    pub expires_in: i64,
+    pub algorithm: Algorithm,
+    /// Swaps the claims after signing, like a token changed on the way
+    pub forged: bool,
+}
+
+impl IdToken {
+    pub fn new(subject: &str) -> Self {
+        Self {
+            subject: subject.to_string(),
+            email: Some(format!("{}@example.com", subject)),
+            audience: None,
+            nonce: None,
+            expires_in: 300,
+            algorithm: Algorithm::RS256,
+            forged: false,
+        }
+    }
+}
+
+impl MockIssuer {
+    pub const CLIENT_ID: &str = "drive";
+    const KEY_ID: &str = "test-key";
+    /// Public part of `oidc_key.pem`
+    const KEY_MODULUS: &str = "tH3Xh_UgiqaIibh4RUQ5uIxMBS8xKNCosG4X0D5MLPxeWYFyevbmHtC3i351u3ICROLJYGaOLOuLLzlNUdvKB__SfG-wB-KdfZWiWCOrOYZULk-0n0EkZpntOpaeAO2iMQip4c7xPfwM5AA69SQTDuToipLTplnIlXpII_TOR7RZsG3yp-92Yw7CS45DU33dVi0l8g8CykGMPzIeiUXr_6eE9nowhX4IMubLpeqr6fV7j53_0A70I1TGt2Btg5_rp1F25VbUNkvqQoXTozrerw_iRrGPC5LPno75gFVxGBP2JkB2-dltIOfvrZCvLinoyi4TYpT0aPOmvVKFjNCy0Q";
+
+    pub async fn spawn() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let codes: Arc<std::sync::Mutex<BTreeMap<String, Authorized>>> = Arc::default();
+
+        let discovery = json!({
+            "issuer": url,
+            "authorization_endpoint": format!("{}/authorize", url),
+            "token_endpoint": format!("{}/token", url),
+            "jwks_uri": format!("{}/jwks", url),
+        });
+        let jwks = json!({ "keys": [{
+            "kty": "RSA", "use": "sig", "alg": "RS256", "kid": Self::KEY_ID, "n": Self::KEY_MODULUS, "e": "AQAB",
+        }] });
+        let pending = codes.clone();
+        let router = axum::Router::new()
+            .route("/.well-known/openid-configuration", axum::routing::get(move || async move { axum::Json(discovery) }))
+            .route("/jwks", axum::routing::get(move || async move { axum::Json(jwks) }))
+            .route("/token", axum::routing::post(move |axum::Form(form): axum::Form<BTreeMap<String, String>>| async move {
+                let authorized = pending.lock().unwrap().remove(&form["code"]);
+                match authorized {
+                    Some(authorized) if authorized.code_challenge == challenge(&form["code_verifier"]) => {
+                        Ok(axum::Json(json!({ "id_token": Self::sign(authorized.claims, &authorized.token) })))
+                    },
+                    _ => Err(StatusCode::BAD_REQUEST),
+                }
+            }));
+        tokio::spawn(axum::serve(listener, router).into_future());
+        Self { url, codes }
+    }
+
+    /// Settings of the app to log in here, the redirect url isn't followed by the tests
+    pub fn config(&self) -> OidcConfig {
+        OidcConfig {
+            issuer: self.url.clone(),
+            client_id: Self::CLIENT_ID.to_string(),
+            redirect_url: "http://drive.example.com/v1/user/oidc/callback".to_string(),
+            ..OidcConfig::default()
+        }
+    }
+
+    /// Accepts the authorization request the app redirected to and returns the code for the callback
+    pub fn authorize(&self, request: &Url, token: IdToken) -> String {
+        let query: BTreeMap<String, String> = request.query_pairs().into_owned().collect();
+        assert_eq!(query["client_id"], Self::CLIENT_ID);
+        assert_eq!(query["code_challenge_method"], "S256");
+
+        let now = chrono::Utc::now().timestamp();
+        let claims = json!({
+            "iss": self.url,
+            "sub": token.subject,
+            "aud": token.audience.clone().unwrap_or(Self::CLIENT_ID.to_string()),
+            "nonce": token.nonce.clone().unwrap_or(query["nonce"].clone()),
+            "email": token.email,
+            "email_verified": true,
+            "iat": now,
+            "exp": now + token.expires_in,
+        });
+        let code = Uuid::new_v4().to_string();
+        let authorized = Authorized { code_challenge: query["code_challenge"].clone(), claims, token };
+        self.codes.lock().unwrap().insert(code.clone(), authorized);
+        code
+    }
+
+    fn sign(claims: serde_json::Value, token: &IdToken) -> String {
+        let mut header = jsonwebtoken::Header::new(token.algorithm);
+        header.kid = Some(Self::KEY_ID.to_string());
+        let key = match token.algorithm {
+            // the public key as secret, the classic confusion of the algorithms
+            Algorithm::HS256 => EncodingKey::from_secret(Self::KEY_MODULUS.as_bytes()),
+            _ => EncodingKey::from_rsa_pem(include_bytes!("oidc_key.pem")).unwrap(),
+        };
+        let signed = jsonwebtoken::encode(&header, &claims, &key).unwrap();
+        if !token.forged {
+            return signed
+        }
+
+        let mut parts: Vec<String> = signed.split('.').map(str::to_string).collect();
+        let mut forged = claims;
+        forged["sub"] = json!("someone-else");
+        parts[1] = URL_SAFE_NO_PAD.encode(forged.to_string());
+        parts.join(".")
+    }
+}
+
+/// S256 code challenge of a PKCE verifier
+fn challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+#[derive(Debug)]
+pub struct Download {
+    pub status: StatusCode,
+    pub bytes: Vec<u8>,
+    pub headers: HeaderMap,
+}
+
+impl Download {
+    pub fn content_disposition(&self) -> &str {
+        self.headers.get(CONTENT_DISPOSITION).and_then(|v| v.to_str().ok()).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    pub uuid: Uuid,
+    pub username: String,
+    pub email: String,
+    pub permission: String,
+    pub usage: usize,
+    pub quota: Option<usize>,
+    pub deletion_scheduled_for: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminUser {
+    pub uuid: Uuid,
+    pub username: String,
+    pub permission: String,
+    pub quota: Option<usize>,
+    pub usage: usize,
+    pub files: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Job {
+    pub id: i64,
+    pub kind: String,
+    pub status: String,
+    pub attempts: u32,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor: Option<String>,
+    pub action: String,
+    pub target_uuid: Option<Uuid>,
+    pub outcome: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// Typed client of the api, keeps the session cookie between requests
+pub struct TestClient {
+    pub http: reqwest::Client,
+    base_url: String,
+}
+
+impl TestClient {
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Signs up with `{username}@example.com` and keeps the session
+    pub async fn signup(&self, username: &str, password: &str) -> ApiResult<()> {
+        let body = json!({ "username": username, "email": format!("{}@example.com", username), "password": password });
+        self.send(self.http.post(self.url("/v1/user/new")).json(&body), StatusCode::CREATED).await
+    }
+
+    pub async fn login(&self, username: &str, password: &str) -> ApiResult<()> {
+        let body = json!({ "username": username, "password": password });
+        self.send(self.http.post(self.url("/v1/user/login")).json(&body), StatusCode::OK).await
+    }
+
+    /// Keeps the time of the login in the new token
+    pub async fn refresh_token(&self) -> ApiResult<()> {
+        self.send(self.http.get(self.url("/v1/user/refresh_token")), StatusCode::OK).await
+    }
+
+    /// Status and checks of `/readyz`, which answers either way
+    pub async fn readiness(&self) -> (StatusCode, Readiness) {
+        let response = self.http.get(self.url("/readyz")).send().await.unwrap();
+        (response.status(), response.json().await.unwrap())
+    }
+
+    pub async fn me(&self) -> ApiResult<Profile> {
+        self.json(self.http.get(self.url("/v1/user/me")), StatusCode::OK).await
+    }
+
+    pub async fn change_username(&self, new_username: &str) -> ApiResult<()> {
+        let body = json!({ "new_username": new_username });
+        self.send(self.http.put(self.url("/v1/user/username/change")).json(&body), StatusCode::OK).await
+    }
+
+    pub async fn change_password(&self, old_password: &str, new_password: &str) -> ApiResult<()> {
+        let body = json!({ "old_password": old_password, "new_password": new_password });
+        self.send(self.http.put(self.url("/v1/user/password/change")).json(&body), StatusCode::OK).await
+    }
+
+    /// Sends a token to the new email, applied by `confirm_email`
+    pub async fn change_email(&self, new_email: &str, password: &str) -> ApiResult<()> {
+        let body = json!({ "new_email": new_email, "password": password });
+        self.send(self.http.put(self.url("/v1/user/email/change")).json(&body), StatusCode::ACCEPTED).await
+    }
+
+    pub async fn confirm_email(&self, token: &str) -> ApiResult<()> {
+        let body = json!({ "token": token });
+        self.send(self.http.post(self.url("/v1/user/email/confirm")).json(&body), StatusCode::OK).await
+    }
+
+    /// Schedules the deletion of the own account, returns when it's purged
+    pub async fn delete_account(&self, password: &str) -> ApiResult<i64> {
+        self.schedule_deletion(json!({ "password": password })).await
+    }
+
+    /// Confirmed by a recent login instead of the password
+    pub async fn delete_account_after_login(&self) -> ApiResult<i64> {
+        self.schedule_deletion(json!({})).await
+    }
+
+    async fn schedule_deletion(&self, body: serde_json::Value) -> ApiResult<i64> {
+        #[derive(Deserialize)]
+        struct Scheduled {
+            scheduled_for: i64,
+        }
+        let scheduled: Scheduled = self.json(self.http.delete(self.url("/v1/user")).json(&body), StatusCode::ACCEPTED).await?;
+        Ok(scheduled.scheduled_for)
+    }
+
+    pub async fn cancel_deletion(&self) -> ApiResult<()> {
+        self.send(self.http.delete(self.url("/v1/user/deletion")), StatusCode::NO_CONTENT).await
+    }
+
+    /// Single sign-on at the `MockIssuer`, which answers the code exchange with the given id token
+    pub async fn oidc_login(&self, issuer: &MockIssuer, token: IdToken) -> ApiResult<()> {
+        let response = self.http.get(self.url("/v1/user/oidc/login")).send().await.unwrap();
+        if response.status() != StatusCode::SEE_OTHER {
+            return Err(failure(response).await)
+        }
+        let location = Url::parse(response.headers()["location"].to_str().unwrap()).unwrap();
+        let state = location.query_pairs().find(|(key, _)| key == "state").unwrap().1.into_owned();
+
+        let code = issuer.authorize(&location, token);
+        let callback = self.http.get(self.url("/v1/user/oidc/callback")).query(&[("code", code), ("state", state)]);
+        self.send(callback, StatusCode::SEE_OTHER).await
+    }
+
+    /// Uploads all files in a single multipart request
+    pub async fn upload(&self, files: &[(&str, &[u8])]) -> ApiResult<Vec<UploadedFile>> {
+        let form = files.iter().fold(Form::new(), |form, (filename, content)| {
+            form.part("file", Part::bytes(content.to_vec()).file_name(filename.to_string()))
+        });
+        self.json(self.http.post(self.url("/v1/file/upload")).multipart(form), StatusCode::CREATED).await
+    }
+
+    pub async fn download(&self, reference_uuid: Uuid) -> ApiResult<Download> {
+        let response = self.http.get(self.url(&format!("/v1/file/download/{}", reference_uuid))).send().await.unwrap();
+        if response.status() != StatusCode::OK {
+            return Err(failure(response).await)
+        }
+        let headers = response.headers().clone();
+        Ok(Download { status: StatusCode::OK, bytes: response.bytes().await.unwrap().to_vec(), headers })
+    }
+
+    /// Download with extra headers like `If-None-Match`, any status is returned as is
+    pub async fn download_with(&self, reference_uuid: Uuid, headers: &[(&str, &str)]) -> Download {
+        let request = headers.iter().fold(
+            self.http.get(self.url(&format!("/v1/file/download/{}", reference_uuid))),
+            |request, (name, value)| request.header(*name, *value),
+        );
+        let response = request.send().await.unwrap();
+        let (status, headers) = (response.status(), response.headers().clone());
+        Download { status, bytes: response.bytes().await.unwrap().to_vec(), headers }
+    }
+
+    pub async fn delete(&self, reference_uuid: Uuid) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/file/delete/{}", reference_uuid));
+        self.send(self.http.delete(url), StatusCode::NO_CONTENT).await
+    }
+
+    pub async fn delete_if_match(&self, reference_uuid: Uuid, etag: &str) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/file/delete/{}", reference_uuid));
+        self.send(self.http.delete(url).header("if-match", etag), StatusCode::NO_CONTENT).await
+    }
+
+    pub async fn search(&self, query: &str) -> ApiResult<Vec<FileInfo>> {
+        self.json(self.http.get(self.url("/v1/file/search")).query(&[("q", query)]), StatusCode::OK).await
+    }
+
+    /// Newest first, optionally only files with `tag` or starred ones
+    pub async fn list(&self, tag: Option<&str>, starred: Option<bool>) -> ApiResult<Vec<FileInfo>> {
+        let mut request = self.http.get(self.url("/v1/file/list"));
+        if let Some(tag) = tag {
+            request = request.query(&[("tag", tag)]);
+        }
+        if let Some(starred) = starred {
+            request = request.query(&[("starred", starred)]);
+        }
+        self.json(request, StatusCode::OK).await
+    }
+
+    /// Returns the tags as they're stored
+    pub async fn set_tags(&self, reference_uuid: Uuid, tags: &[&str]) -> ApiResult<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Tags {
+            tags: Vec<String>,
+        }
+        let url = self.url(&format!("/v1/file/tags/{}", reference_uuid));
+        let tags: Tags = self.json(self.http.put(url).json(&json!({ "tags": tags })), StatusCode::OK).await?;
+        Ok(tags.tags)
+    }
+
+    pub async fn remove_tag(&self, reference_uuid: Uuid, tag: &str) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/file/tags/{}/{}", reference_uuid, tag));
+        self.send(self.http.delete(url), StatusCode::NO_CONTENT).await
+    }
+
+    pub async fn bulk_tag(&self, reference_uuids: &[Uuid], add: &[&str], remove: &[&str]) -> ApiResult<BulkTagged> {
+        let body = json!({ "reference_uuids": reference_uuids, "add": add, "remove": remove });
+        self.json(self.http.post(self.url("/v1/file/tags")).json(&body), StatusCode::OK).await
+    }
+
+    /// Small batches are answered right away
+    pub async fn batch(&self, operations: &[serde_json::Value]) -> ApiResult<BatchStatus> {
+        let body = json!({ "operations": operations });
+        self.json(self.http.post(self.url("/v1/file/batch")).json(&body), StatusCode::OK).await
+    }
+
+    /// Large batches run in the background
+    pub async fn start_batch(&self, operations: &[serde_json::Value]) -> ApiResult<BatchStatus> {
+        let body = json!({ "operations": operations });
+        self.json(self.http.post(self.url("/v1/file/batch")).json(&body), StatusCode::ACCEPTED).await
+    }
+
+    pub async fn batch_status(&self, batch_id: Uuid) -> ApiResult<BatchStatus> {
+        self.json(self.http.get(self.url(&format!("/v1/file/batch/{}", batch_id))), StatusCode::OK).await
+    }
+
+    pub async fn changes(&self, cursor: Option<i64>, limit: Option<i64>) -> ApiResult<Changes> {
+        let mut request = self.http.get(self.url("/v1/changes"));
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        self.json(request, StatusCode::OK).await
+    }
+
+    pub async fn events(&self, last_event_id: Option<i64>) -> EventStream {
+        let mut request = self.http.get(self.url("/v1/changes/events"));
+        if let Some(id) = last_event_id {
+            request = request.header("last-event-id", id);
+        }
+        let response = request.send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        EventStream { response, buffer: String::new() }
+    }
+
+    pub async fn create_webhook(&self, url: &str, events: &[&str]) -> ApiResult<Webhook> {
+        let body = json!({ "url": url, "events": events });
+        self.json(self.http.post(self.url("/v1/webhooks")).json(&body), StatusCode::CREATED).await
+    }
+
+    pub async fn webhooks(&self) -> ApiResult<Vec<Webhook>> {
+        #[derive(Deserialize)]
+        struct Webhooks {
+            webhooks: Vec<Webhook>,
+        }
+        let list: Webhooks = self.json(self.http.get(self.url("/v1/webhooks")), StatusCode::OK).await?;
+        Ok(list.webhooks)
+    }
+
+    pub async fn delete_webhook(&self, webhook: Uuid) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/webhooks/{}", webhook));
+        self.send(self.http.delete(url), StatusCode::NO_CONTENT).await
+    }
+
+    pub async fn ping_webhook(&self, webhook: Uuid) -> ApiResult<Delivery> {
+        let url = self.url(&format!("/v1/webhooks/{}/ping", webhook));
+        self.json(self.http.post(url), StatusCode::ACCEPTED).await
+    }
+
+    /// Newest first
+    pub async fn deliveries(&self, webhook: Uuid) -> ApiResult<Vec<Delivery>> {
+        #[derive(Deserialize)]
+        struct Deliveries {
+            deliveries: Vec<Delivery>,
+        }
+        let url = self.url(&format!("/v1/webhooks/{}/deliveries", webhook));
+        let list: Deliveries = self.json(self.http.get(url), StatusCode::OK).await?;
+        Ok(list.deliveries)
+    }
+
+    /// Waits until the latest attempt of a delivery is recorded, the receiver gets the request before that
+    pub async fn attempted_delivery(&self, webhook: Uuid, id: i64, attempts: u32) -> Delivery {
+        for _ in 0..50 {
+            let deliveries = self.deliveries(webhook).await.unwrap();
+            let delivery = deliveries.into_iter().find(|d| d.id == id).expect("delivery exists");
+            if delivery.attempts >= attempts && (delivery.response_status.is_some() || delivery.error.is_some()) {
+                return delivery
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("attempt {} of delivery {} wasn't recorded", attempts, id)
+    }
+
+    pub async fn set_starred(&self, reference_uuid: Uuid, starred: bool) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/file/starred/{}", reference_uuid));
+        self.send(self.http.put(url).json(&json!({ "starred": starred })), StatusCode::OK).await
+    }
+
+    pub async fn set_metadata(&self, reference_uuid: Uuid, key: &str, value: &str) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/file/metadata/{}/{}", reference_uuid, key));
+        self.send(self.http.put(url).json(&json!({ "value": value })), StatusCode::OK).await
+    }
+
+    pub async fn remove_metadata(&self, reference_uuid: Uuid, key: &str) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/file/metadata/{}/{}", reference_uuid, key));
+        self.send(self.http.delete(url), StatusCode::NO_CONTENT).await
+    }
+
+    pub async fn admin_users(&self, search: &str) -> ApiResult<Vec<AdminUser>> {
+        self.json(self.http.get(self.url("/v1/admin/users")).query(&[("search", search)]), StatusCode::OK).await
+    }
+
+    /// Uuid of a user, looked up through the admin api
+    pub async fn admin_find(&self, username: &str) -> ApiResult<AdminUser> {
+        let users = self.admin_users(username).await?;
+        Ok(users.into_iter().find(|u| u.username == username).expect("user exists"))
+    }
+
+    pub async fn admin_jobs(&self, status: Option<&str>) -> ApiResult<Vec<Job>> {
+        #[derive(Deserialize)]
+        struct Jobs {
+            jobs: Vec<Job>,
+        }
+        let mut request = self.http.get(self.url("/v1/admin/jobs"));
+        if let Some(status) = status {
+            request = request.query(&[("status", status)]);
+        }
+        let list: Jobs = self.json(request, StatusCode::OK).await?;
+        Ok(list.jobs)
+    }
+
+    pub async fn admin_job(&self, id: i64) -> ApiResult<Job> {
+        self.json(self.http.get(self.url(&format!("/v1/admin/jobs/{}", id))), StatusCode::OK).await
+    }
+
+    pub async fn admin_retry_job(&self, id: i64) -> ApiResult<Job> {
+        self.json(self.http.post(self.url(&format!("/v1/admin/jobs/{}/retry", id))), StatusCode::OK).await
+    }
+
+    /// Own audit log, newest first
+    pub async fn audit(&self, query: &[(&str, &str)]) -> ApiResult<Vec<AuditEntry>> {
+        self.audit_page("/v1/audit", query).await
+    }
+
+    pub async fn admin_audit(&self, query: &[(&str, &str)]) -> ApiResult<Vec<AuditEntry>> {
+        self.audit_page("/v1/admin/audit", query).await
+    }
+
+    async fn audit_page(&self, path: &str, query: &[(&str, &str)]) -> ApiResult<Vec<AuditEntry>> {
+        #[derive(Deserialize)]
+        struct AuditLog {
+            entries: Vec<AuditEntry>,
+        }
+        let log: AuditLog = self.json(self.http.get(self.url(path)).query(query), StatusCode::OK).await?;
+        Ok(log.entries)
+    }
+
+    /// Content type and body of the export
+    pub async fn export_audit(&self, path: &str, format: &str) -> ApiResult<(String, String)> {
+        let response = self.http.get(self.url(path)).query(&[("format", format)]).send().await.unwrap();
+        if response.status() != StatusCode::OK {
+            return Err(failure(response).await)
+        }
+        let content_type = response.headers()[reqwest::header::CONTENT_TYPE].to_str().unwrap().to_string();
+        Ok((content_type, response.text().await.unwrap()))
+    }
+
+    pub async fn admin_set_quota(&self, user: Uuid, quota: Option<usize>) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/admin/users/{}/quota", user));
+        self.send(self.http.put(url).json(&json!({ "quota": quota })), StatusCode::OK).await
+    }
+
+    pub async fn admin_set_disabled(&self, user: Uuid, disabled: bool) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/admin/users/{}/disabled", user));
+        self.send(self.http.put(url).json(&json!({ "disabled": disabled })), StatusCode::OK).await
+    }
+
+    pub async fn admin_force_password_reset(&self, user: Uuid) -> ApiResult<()> {
+        let url = self.url(&format!("/v1/admin/users/{}/password_reset", user));
+        self.send(self.http.put(url), StatusCode::OK).await
+    }
+
+    async fn send(&self, request: RequestBuilder, expected: StatusCode) -> ApiResult<()> {
+        let response = request.send().await.unwrap();
+        if response.status() != expected {
+            return Err(failure(response).await)
+        }
+        Ok(())
+    }
+
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder, expected: StatusCode) -> ApiResult<T> {
+        let response = request.send().await.unwrap();
+        if response.status() != expected {
+            return Err(failure(response).await)
+        }
+        Ok(response.json().await.unwrap())
+    }
+}
+
+/// Parses an error response, panics if it isn't one
+async fn failure(response: reqwest::Response) -> Failure {
+    let status = response.status();
+    let text = response.text().await.unwrap();
+    let mut failure: Failure = serde_json::from_str(&text)
+        .unwrap_or_else(|_| panic!("expected an error body with status {}, got {}", status, text));
+    failure.status = status;
+    failure
+}
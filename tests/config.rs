@@ -0,0 +1,66 @@
+mod common;
+
+use drive_lib::config::{Config, ConfigError};
+use drive_lib::util::auth::ldap::LdapConfig;
+use std::path::Path;
+
+/// Setting the error starts with and the change breaking it
+type Case = (&'static str, fn(&mut Config));
+
+fn invalid(config: &Config) -> Vec<String> {
+    match config.validate() {
+        Err(ConfigError::Invalid(errors)) => errors,
+        other => panic!("expected validation errors, got {:?}", other.map_err(|e| e.to_string())),
+    }
+}
+
+#[test]
+fn test_config_is_valid() {
+    common::config(Path::new("files")).validate().unwrap();
+}
+
+/// Every case breaks one value and has to be reported with the setting and its variable
+#[test]
+fn invalid_values_are_reported() {
+    let cases: [Case; 12] = [
+        ("server.bind_address (BIND_ADDRESS)", |c| c.server.bind_address = "localhost".to_string()),
+        ("database.url (DATABASE_URL)", |c| c.database.url.clear()),
+        ("auth.cookie_secret (COOKIE_SECRET)", |c| c.auth.cookie_secret = "short".to_string()),
+        ("storage.file_location (FILE_LOCATION)", |c| c.storage.file_location.clear()),
+        ("jobs.concurrency (JOB_CONCURRENCY)", |c| c.jobs.concurrency = 0),
+        ("rate_limit.login_ip", |c| c.rate_limit.login_ip.max = c.rate_limit.login_ip.base - 1),
+        ("account.email_token_lifetime (EMAIL_TOKEN_LIFETIME)", |c| c.account.email_token_lifetime = 0),
+        ("account.reauthentication_window (REAUTHENTICATION_WINDOW)", |c| c.account.reauthentication_window = -1),
+        ("metrics.bearer_token (METRICS_TOKEN)", |c| c.metrics.enabled = true),
+        ("metrics.bearer_token (METRICS_TOKEN)", |c| {
+            c.metrics.enabled = true;
+            c.metrics.bearer_token = Some(String::new());
+        }),
+        ("ldap.user_filter (LDAP_USER_FILTER)", |c| {
+            c.ldap = Some(LdapConfig { url: "ldap://localhost".to_string(), base_dn: "dc=example".to_string(), user_filter: "(uid=*)".to_string(), ..Default::default() })
+        }),
+        ("ldap.timeout (LDAP_TIMEOUT)", |c| {
+            c.ldap = Some(LdapConfig { url: "ldap://localhost".to_string(), base_dn: "dc=example".to_string(), timeout: 0, ..Default::default() })
+        }),
+    ];
+
+    for (setting, adjust) in cases {
+        let mut config = common::config(Path::new("files"));
+        adjust(&mut config);
+        let errors = invalid(&config);
+        assert_eq!(errors.len(), 1, "{}: {:?}", setting, errors);
+        assert!(errors[0].starts_with(setting), "{}: {:?}", setting, errors);
+    }
+}
+
+#[test]
+fn every_problem_is_reported_at_once() {
+    let mut config = common::config(Path::new("files"));
+    config.storage.file_location.clear();
+    config.jobs.concurrency = 0;
+    config.metrics.enabled = true;
+
+    let errors = invalid(&config);
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    assert!(config.validate().unwrap_err().to_string().starts_with("invalid configuration:"));
+}
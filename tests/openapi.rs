@@ -0,0 +1,144 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use drive_lib::app::build_app;
+use serde_json::Value;
+use std::path::Path;
+use tower::ServiceExt;
+
+/// Routes that are registered with a plain `.route` and intentionally not documented
+const UNDOCUMENTED_ROUTES: [&str; 7] = [
+    "/v1/openapi.json",
+    "/v1/docs",
+    "/v1/docs/swagger-ui.css",
+    "/v1/docs/swagger-ui-bundle.js",
+    "/metrics",
+    "/healthz",
+    "/readyz",
+];
+
+/// App without a reachable database, none of the probed requests gets past the extractors or the auth middleware
+fn app() -> Router {
+    build_app(common::config(Path::new("unused"))).unwrap()
+}
+
+async fn send(app: &Router, method: Method, uri: &str) -> (StatusCode, Value) {
+    let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+async fn spec(app: &Router) -> Value {
+    let (status, spec) = send(app, Method::GET, "/v1/openapi.json").await;
+    assert_eq!(status, StatusCode::OK);
+    spec
+}
+
+/// Every documented operation has to reach its handler or middleware instead of the router's fallback
+#[tokio::test]
+async fn documented_operations_are_routed() {
+    let app = app();
+    let spec = spec(&app).await;
+    let paths = spec["paths"].as_object().unwrap();
+    assert!(!paths.is_empty());
+
+    for (path, item) in paths {
+        // fill in every path parameter with a valid uuid
+        let uri = path.split('/')
+            .map(|segment| if segment.starts_with('{') { "00000000-0000-0000-0000-000000000000" } else { segment })
+            .collect::<Vec<_>>()
+            .join("/");
+
+        for method in item.as_object().unwrap().keys() {
+            let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+            let (status, body) = send(&app, method.clone(), &uri).await;
+
+            assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {} is documented but not routed", method, path);
+            assert!(
+                !(status == StatusCode::NOT_FOUND && body["message"] == "Not Found"),
+                "{} {} is documented but not routed", method, path
+            );
+        }
+    }
+}
+
+/// Routes outside of `routes!` don't show up in the document, only the docs themselves may do that
+#[test]
+fn routes_are_registered_with_their_documentation() {
+    let source = include_str!("../src/router.rs");
+
+    for (index, _) in source.match_indices(".route(\"") {
+        let path = source[index + ".route(\"".len()..].split('"').next().unwrap();
+        assert!(UNDOCUMENTED_ROUTES.contains(&path), "{} is routed without being documented", path);
+    }
+    assert!(!source.contains(".route_service("), "services can't be documented");
+}
+
+/// Request and response bodies have to be registered as schemas
+#[tokio::test]
+async fn referenced_schemas_exist() {
+    let spec = spec(&app()).await;
+    let schemas = spec["components"]["schemas"].as_object().unwrap();
+
+    fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    refs.push(reference.clone());
+                }
+                map.values().for_each(|v| collect_refs(v, refs));
+            },
+            Value::Array(values) => values.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {},
+        }
+    }
+
+    let mut refs = Vec::new();
+    collect_refs(&spec, &mut refs);
+    assert!(!refs.is_empty());
+
+    for reference in refs {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        assert!(schemas.contains_key(name), "{} is referenced but not registered", reference);
+    }
+}
+
+/// The docs page only loads assets the server ships itself
+#[tokio::test]
+async fn docs_are_self_contained() {
+    let app = app();
+    let request = Request::builder().uri("/v1/docs").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let page = String::from_utf8(to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+    assert!(!page.contains("https://"), "docs load remote assets");
+
+    for (asset, content_type) in [("/v1/docs/swagger-ui.css", "text/css"), ("/v1/docs/swagger-ui-bundle.js", "text/javascript")] {
+        assert!(page.contains(asset), "{} isn't used by the docs", asset);
+        let request = Request::builder().uri(asset).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()["content-type"].to_str().unwrap().starts_with(content_type));
+    }
+}
+
+#[tokio::test]
+async fn undocumented_path_is_not_found() {
+    let (status, body) = send(&app(), Method::GET, "/v1/file/does-not-exist").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
+
+/// Background batches are tracked by their own id, not by the id of a queued job
+#[tokio::test]
+async fn batch_status_is_documented_by_batch_id() {
+    let spec = spec(&app()).await;
+    let operation = &spec["paths"]["/v1/file/batch/{batch_id}"]["get"];
+    assert_eq!(operation["parameters"][0]["name"], "batch_id");
+
+    let properties = spec["components"]["schemas"]["BatchStatus"]["properties"].as_object().unwrap();
+    assert!(properties.contains_key("batch_id") && !properties.contains_key("job_id"));
+}